@@ -11,7 +11,10 @@ fn bench_serde_float_as_binary_vs_text(c: &mut Criterion) {
             b.iter(|| {
                 let blob = serde_sqlite_jsonb::to_vec_with_options(
                     &v,
-                    serde_sqlite_jsonb::Options { binary_float: true },
+                    serde_sqlite_jsonb::Options {
+                        binary_float: true,
+                        ..Default::default()
+                    },
                 )
                 .unwrap();
 
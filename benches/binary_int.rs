@@ -0,0 +1,44 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde_sqlite_jsonb::Options;
+
+fn bench_binary_int_vs_text(c: &mut Criterion) {
+    let binary = Options {
+        binary_int: true,
+        ..Options::default()
+    };
+    let mut group = c.benchmark_group("array of integers");
+    for len in [1000, 100_000] {
+        let values: Vec<i64> = (0..len).map(|i| i * 7919 - len / 2).collect();
+        for (name, options) in
+            [("text", Options::default()), ("binary", binary)]
+        {
+            group.bench_with_input(
+                BenchmarkId::new(format!("serialize {name}"), len),
+                &values,
+                |b, values| {
+                    b.iter(|| {
+                        serde_sqlite_jsonb::to_vec_with_options(values, options)
+                            .unwrap()
+                    })
+                },
+            );
+            let blob =
+                serde_sqlite_jsonb::to_vec_with_options(&values, options)
+                    .unwrap();
+            group.bench_with_input(
+                BenchmarkId::new(format!("deserialize {name}"), len),
+                &blob,
+                |b, blob| {
+                    b.iter(|| {
+                        serde_sqlite_jsonb::from_slice::<Vec<i64>>(blob)
+                            .unwrap()
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_binary_int_vs_text);
+criterion_main!(benches);
@@ -0,0 +1,77 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde_derive::{Deserialize, Serialize};
+use serde_sqlite_jsonb::{BytesEncoding, Options};
+
+/// A byte buffer that serializes with `serialize_bytes`,
+/// which is what `Options::bytes_encoding` applies to.
+#[serde_with::serde_as]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Blob {
+    #[serde_as(as = "serde_with::Bytes")]
+    data: Vec<u8>,
+}
+
+const ENCODINGS: [(&str, BytesEncoding); 3] = [
+    ("array", BytesEncoding::Array),
+    ("base64", BytesEncoding::Base64),
+    ("hex", BytesEncoding::Hex),
+];
+
+fn options(bytes_encoding: BytesEncoding) -> Options {
+    Options {
+        bytes_encoding,
+        ..Options::default()
+    }
+}
+
+fn bench_bytes_encoding(c: &mut Criterion) {
+    let blob = Blob {
+        data: (0..4096).map(|i| (i * 7 % 256) as u8).collect(),
+    };
+
+    // criterion only measures time, so report the size of each encoding once
+    for (name, encoding) in ENCODINGS {
+        let encoded =
+            serde_sqlite_jsonb::to_vec_with_options(&blob, options(encoding))
+                .unwrap();
+        println!("{name}: 4096 bytes stored in {} bytes", encoded.len());
+    }
+
+    let mut group = c.benchmark_group("serialize 4KB of bytes");
+    for (name, encoding) in ENCODINGS {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(name),
+            &options(encoding),
+            |b, &options| {
+                b.iter(|| {
+                    serde_sqlite_jsonb::to_vec_with_options(&blob, options)
+                        .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("deserialize 4KB of bytes");
+    for (name, encoding) in ENCODINGS {
+        let options = options(encoding);
+        let encoded =
+            serde_sqlite_jsonb::to_vec_with_options(&blob, options).unwrap();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(name),
+            &encoded,
+            |b, encoded| {
+                b.iter(|| {
+                    serde_sqlite_jsonb::from_slice_with_options::<Blob>(
+                        encoded, options,
+                    )
+                    .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_bytes_encoding);
+criterion_main!(benches);
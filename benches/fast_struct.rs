@@ -0,0 +1,262 @@
+//! Prototype of a specialized encoder and decoder for plain structs, which
+//! writes and reads JSONB elements directly instead of going through serde,
+//! compared with the serde derive on the same `Person` type.
+//!
+//! `jsonb_fast_struct!` stands in for a `#[derive(JsonbFast)]`: it only
+//! handles structs whose fields are integers, booleans, strings, or vectors
+//! of those, and produces the same bytes as `to_vec`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde_derive::{Deserialize, Serialize};
+use serde_sqlite_jsonb::{
+    read_and_validate_header, write_minimal_header, ElementType, Error, Header,
+    Options, Result,
+};
+
+/// A value that is written and read as a single JSONB element
+trait FastField: Sized {
+    fn write(&self, buf: &mut Vec<u8>);
+    fn read(input: &mut &[u8]) -> Result<Self>;
+}
+
+/// Read the header of the next element, and split its payload off `input`
+fn next_element<'a>(input: &mut &'a [u8]) -> Result<(Header, &'a [u8])> {
+    let header = read_and_validate_header(&mut *input, true)?;
+    let len = usize::try_from(header.payload_size)
+        .ok()
+        .filter(|&len| len <= input.len())
+        .ok_or(Error::Truncated {
+            payload_size: header.payload_size,
+            available: input.len() as u64,
+        })?;
+    let (payload, rest) = input.split_at(len);
+    *input = rest;
+    Ok((header, payload))
+}
+
+/// Write the header and payload of an element whose payload is built by
+/// `write_payload`, right after the header.
+fn write_container(
+    buf: &mut Vec<u8>,
+    element_type: ElementType,
+    write_payload: impl FnOnce(&mut Vec<u8>),
+) {
+    let start = buf.len();
+    write_payload(buf);
+    let mut header = Vec::with_capacity(9);
+    write_minimal_header(&mut header, element_type, (buf.len() - start) as u64);
+    buf.splice(start..start, header);
+}
+
+impl FastField for usize {
+    fn write(&self, buf: &mut Vec<u8>) {
+        let digits = self.to_string();
+        write_minimal_header(buf, ElementType::Int, digits.len() as u64);
+        buf.extend_from_slice(digits.as_bytes());
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self> {
+        let start = *input;
+        let (header, payload) = next_element(input)?;
+        if header.element_type == ElementType::Int {
+            if let Some(n) = std::str::from_utf8(payload)
+                .ok()
+                .and_then(|digits| digits.parse().ok())
+            {
+                return Ok(n);
+            }
+        }
+        // anything unusual is left to serde
+        serde_sqlite_jsonb::from_slice(&start[..start.len() - input.len()])
+    }
+}
+
+impl FastField for bool {
+    fn write(&self, buf: &mut Vec<u8>) {
+        let t = if *self {
+            ElementType::True
+        } else {
+            ElementType::False
+        };
+        write_minimal_header(buf, t, 0);
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self> {
+        match next_element(input)?.0.element_type {
+            ElementType::True => Ok(true),
+            ElementType::False => Ok(false),
+            t => Err(Error::Message(format!("expected boolean, found {t}"))),
+        }
+    }
+}
+
+impl FastField for String {
+    fn write(&self, buf: &mut Vec<u8>) {
+        let t = Options::unescaped_strings(self);
+        write_minimal_header(buf, t, self.len() as u64);
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self> {
+        let start = *input;
+        let (header, payload) = next_element(input)?;
+        match header.element_type {
+            ElementType::Text | ElementType::TextRaw => {
+                String::from_utf8(payload.to_vec()).map_err(Error::Utf8)
+            }
+            // strings with escapes are left to serde
+            _ => serde_sqlite_jsonb::from_slice(
+                &start[..start.len() - input.len()],
+            ),
+        }
+    }
+}
+
+impl<T: FastField> FastField for Vec<T> {
+    fn write(&self, buf: &mut Vec<u8>) {
+        write_container(buf, ElementType::Array, |buf| {
+            for item in self {
+                item.write(buf);
+            }
+        });
+    }
+
+    fn read(input: &mut &[u8]) -> Result<Self> {
+        let (header, mut items) = next_element(input)?;
+        if header.element_type != ElementType::Array {
+            return Err(Error::Message(format!(
+                "expected array, found {}",
+                header.element_type
+            )));
+        }
+        let mut values = Vec::new();
+        while !items.is_empty() {
+            values.push(T::read(&mut items)?);
+        }
+        Ok(values)
+    }
+}
+
+/// Implement [`FastField`] for a struct with named fields, as a JSONB object
+macro_rules! jsonb_fast_struct {
+    ($name:ident { $($field:ident: $ty:ty),* $(,)? }) => {
+        impl FastField for $name {
+            fn write(&self, buf: &mut Vec<u8>) {
+                write_container(buf, ElementType::Object, |buf| {
+                    $(
+                        stringify!($field).to_string().write(buf);
+                        self.$field.write(buf);
+                    )*
+                });
+            }
+
+            fn read(input: &mut &[u8]) -> Result<Self> {
+                let (header, mut entries) = next_element(input)?;
+                if header.element_type != ElementType::Object {
+                    return Err(Error::Message(format!(
+                        "expected object, found {}",
+                        header.element_type
+                    )));
+                }
+                $(let mut $field: Option<$ty> = None;)*
+                while !entries.is_empty() {
+                    let key = String::read(&mut entries)?;
+                    match key.as_str() {
+                        $(stringify!($field) => {
+                            $field = Some(FastField::read(&mut entries)?);
+                        })*
+                        // unknown fields are skipped without being decoded
+                        _ => {
+                            next_element(&mut entries)?;
+                        }
+                    }
+                }
+                Ok($name {
+                    $($field: $field.ok_or_else(|| {
+                        Error::Message(format!(
+                            "missing field `{}`",
+                            stringify!($field)
+                        ))
+                    })?,)*
+                })
+            }
+        }
+    };
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Person {
+    id: usize,
+    name: String,
+    phone_numbers: Vec<String>,
+    active: bool,
+    data: String,
+}
+
+jsonb_fast_struct!(Person {
+    id: usize,
+    name: String,
+    phone_numbers: Vec<String>,
+    active: bool,
+    data: String,
+});
+
+fn person(data_size: usize) -> Person {
+    Person {
+        id: 123,
+        name: "John Doe".to_string(),
+        phone_numbers: vec![
+            "1234".to_string(),
+            "+33 1 23 45 67 89".to_string(),
+        ],
+        active: true,
+        data: "x".repeat(data_size),
+    }
+}
+
+fn bench_fast_struct_vs_serde(c: &mut Criterion) {
+    let mut group = c.benchmark_group("person struct");
+    for data_size in [50, 1000] {
+        let person = person(data_size);
+        let blob = serde_sqlite_jsonb::to_vec(&person).unwrap();
+        let mut fast_blob = Vec::new();
+        person.write(&mut fast_blob);
+        assert_eq!(fast_blob, blob, "both encoders write the same bytes");
+        assert_eq!(Person::read(&mut &blob[..]).unwrap(), person);
+
+        group.bench_with_input(
+            BenchmarkId::new("serialize with serde", data_size),
+            &person,
+            |b, person| b.iter(|| serde_sqlite_jsonb::to_vec(person).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("serialize with jsonb_fast_struct", data_size),
+            &person,
+            |b, person| {
+                b.iter(|| {
+                    let mut buf = Vec::new();
+                    person.write(&mut buf);
+                    buf
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("deserialize with serde", data_size),
+            &blob,
+            |b, blob| {
+                b.iter(|| {
+                    serde_sqlite_jsonb::from_slice::<Person>(blob).unwrap()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("deserialize with jsonb_fast_struct", data_size),
+            &blob,
+            |b, blob| b.iter(|| Person::read(&mut &blob[..]).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_fast_struct_vs_serde);
+criterion_main!(benches);
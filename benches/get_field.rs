@@ -0,0 +1,62 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rusqlite::Connection;
+use serde_derive::Deserialize;
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Person {
+    id: usize,
+    name: String,
+    phone_numbers: Vec<String>,
+    active: bool,
+    data: String,
+}
+
+/// Let SQLite build a realistic jsonb blob, with `phone_count` phone numbers
+/// stored before the field we are interested in.
+fn person_blob(conn: &Connection, phone_count: usize) -> Vec<u8> {
+    let phone_numbers = (0..phone_count)
+        .map(|i| format!("\"+33 6 {i:08}\""))
+        .collect::<Vec<_>>()
+        .join(",");
+    let json = format!(
+        r#"{{"id": 123, "phone_numbers": [{phone_numbers}],
+            "data": "{}", "active": true, "name": "John Doe"}}"#,
+        "x".repeat(phone_count)
+    );
+    conn.query_row("select jsonb(?)", [json], |row| row.get(0))
+        .unwrap()
+}
+
+fn bench_full_deserialize_vs_get_field(c: &mut Criterion) {
+    let conn = Connection::open_in_memory().unwrap();
+    let mut group = c.benchmark_group("reading the name of a person");
+    for phone_count in [10, 100, 1000, 10000] {
+        let blob = person_blob(&conn, phone_count);
+        group.bench_with_input(
+            BenchmarkId::new("full from_slice", phone_count),
+            &blob,
+            |b, blob| {
+                b.iter(|| {
+                    let person: Person =
+                        serde_sqlite_jsonb::from_slice(blob).unwrap();
+                    person.name
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("get_field", phone_count),
+            &blob,
+            |b, blob| {
+                b.iter(|| {
+                    serde_sqlite_jsonb::get_field::<String>(blob, "name")
+                        .unwrap()
+                        .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_full_deserialize_vs_get_field);
+criterion_main!(benches);
@@ -0,0 +1,29 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn bench_decode_int_array(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode array of integers");
+    for len in [1000, 100_000] {
+        let values: Vec<i64> = (0..len).map(|i| i * 7919 - len / 2).collect();
+        let blob = serde_sqlite_jsonb::to_vec(&values).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("from_slice::<Vec<i64>>", len),
+            &blob,
+            |b, blob| {
+                b.iter(|| {
+                    serde_sqlite_jsonb::from_slice::<Vec<i64>>(blob).unwrap()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("decode_int_array", len),
+            &blob,
+            |b, blob| {
+                b.iter(|| serde_sqlite_jsonb::decode_int_array(blob).unwrap())
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode_int_array);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+/// A map that serializes its keys and values with separate
+/// `serialize_key` and `serialize_value` calls, like serde's default `serialize_entry`.
+struct SeparateKeyValue<'a>(&'a BTreeMap<String, i64>);
+
+impl Serialize for SeparateKeyValue<'_> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (k, v) in self.0 {
+            map.serialize_key(k)?;
+            map.serialize_value(v)?;
+        }
+        map.end()
+    }
+}
+
+fn bench_serialize_map_entries(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize map");
+    for map_size in [100, 1000, 10000] {
+        let map: BTreeMap<String, i64> =
+            (0..map_size).map(|i| (format!("key {i}"), i)).collect();
+        group.bench_with_input(
+            BenchmarkId::new("serialize_entry", map_size),
+            &map,
+            |b, map| b.iter(|| serde_sqlite_jsonb::to_vec(map).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("serialize_key + serialize_value", map_size),
+            &map,
+            |b, map| {
+                b.iter(|| {
+                    serde_sqlite_jsonb::to_vec(&SeparateKeyValue(map)).unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_serialize_map_entries);
+criterion_main!(benches);
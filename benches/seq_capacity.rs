@@ -0,0 +1,44 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+
+/// A sequence that does not tell the serializer its length up front,
+/// so no space can be reserved for its elements.
+struct NoLenHint<'a>(&'a [i64]);
+
+impl Serialize for NoLenHint<'_> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        for v in self.0 {
+            seq.serialize_element(v)?;
+        }
+        seq.end()
+    }
+}
+
+fn bench_serialize_seq_capacity(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize Vec<i64>");
+    for len in [1000, 100_000, 1_000_000] {
+        let values: Vec<i64> = (0..len).collect();
+        group.bench_with_input(
+            BenchmarkId::new("with length hint", len),
+            &values,
+            |b, values| b.iter(|| serde_sqlite_jsonb::to_vec(values).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("without length hint", len),
+            &values,
+            |b, values| {
+                b.iter(|| {
+                    serde_sqlite_jsonb::to_vec(&NoLenHint(values)).unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_serialize_seq_capacity);
+criterion_main!(benches);
@@ -10,7 +10,10 @@ pub fn create_table(conn: &Connection) {
 }
 
 pub fn insert_data(conn: &Connection, data: &Vec<f32>, binary_float: bool) {
-    let options = serde_sqlite_jsonb::Options { binary_float };
+    let options = serde_sqlite_jsonb::Options {
+        binary_float,
+        ..Default::default()
+    };
     let blob = serde_sqlite_jsonb::to_vec_with_options(data, options).unwrap();
     conn.execute("INSERT INTO float_data (data) VALUES (?)", [blob])
         .unwrap();
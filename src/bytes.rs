@@ -0,0 +1,282 @@
+//! The ways of storing byte strings, chosen with
+//! [`Options::bytes_encoding`](crate::Options::bytes_encoding).
+
+use crate::error::{Error, Result};
+
+/// How values that serialize as bytes are stored, for types that use
+/// `serialize_bytes` (such as `serde_bytes::ByteBuf`, or `Vec<u8>` fields
+/// marked with `serde_with::Bytes`). A plain `Vec<u8>` is serialized as a
+/// sequence by serde, and is always stored as an array.
+///
+/// To store a single `Vec<u8>` field as text whatever the options, mark it
+/// with `#[serde(with = "serde_sqlite_jsonb::as_base64_bytes")]` or
+/// [`as_hex_bytes`](crate::as_hex_bytes) instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BytesEncoding {
+    /// An array of integers, one per byte. This is the default.
+    #[default]
+    Array,
+    /// A base64 string, with the standard alphabet and padding.
+    Base64,
+    /// A string of lowercase hexadecimal digits, two per byte.
+    Hex,
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl BytesEncoding {
+    /// Encode `bytes` as text, or return `None` for [`BytesEncoding::Array`]
+    pub(crate) fn encode(self, bytes: &[u8]) -> Option<String> {
+        match self {
+            BytesEncoding::Array => None,
+            BytesEncoding::Base64 => Some(encode_base64(bytes)),
+            BytesEncoding::Hex => Some(encode_hex(bytes)),
+        }
+    }
+
+    /// Decode text written by [`BytesEncoding::encode`]
+    pub(crate) fn decode(self, text: &str) -> Result<Vec<u8>> {
+        match self {
+            BytesEncoding::Array => {
+                Err(Error::Message("bytes are not stored as text".into()))
+            }
+            BytesEncoding::Base64 => decode_base64(text),
+            BytesEncoding::Hex => decode_hex(text),
+        }
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from_be_bytes([0, b[0], b[1], b[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (n >> (18 - 6 * i)) & 0x3f;
+                out.push(char::from(BASE64_ALPHABET[index as usize]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>> {
+    let invalid = || Error::Message(format!("invalid base64: {text:?}"));
+    if text.len() % 4 != 0 {
+        return Err(invalid());
+    }
+    let data = text.trim_end_matches('=');
+    if text.len() - data.len() > 2 {
+        return Err(invalid());
+    }
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for c in data.bytes() {
+        let value = BASE64_ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .ok_or_else(invalid)?;
+        acc =
+            (acc << 6) | u32::try_from(value).map_err(Error::IntConversion)?;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits).to_le_bytes()[0]);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Ok(out)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for &b in bytes {
+        out.push(char::from(DIGITS[usize::from(b >> 4)]));
+        out.push(char::from(DIGITS[usize::from(b & 0xf)]));
+    }
+    out
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>> {
+    let invalid = || Error::Message(format!("invalid hex: {text:?}"));
+    if text.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let digit = |d: u8| char::from(d).to_digit(16).ok_or_else(invalid);
+            let byte = digit(pair[0])? * 16 + digit(pair[1])?;
+            u8::try_from(byte).map_err(Error::IntConversion)
+        })
+        .collect()
+}
+
+/// Store a `Vec<u8>` field as a base64 string, for use with
+/// `#[serde(with = "serde_sqlite_jsonb::as_base64_bytes")]`.
+///
+/// Unlike [`Options::bytes_encoding`](crate::Options::bytes_encoding), this
+/// only applies to the fields it marks, and works with any serde format.
+///
+/// ```
+/// #[derive(serde_derive::Serialize, serde_derive::Deserialize)]
+/// struct File {
+///     #[serde(with = "serde_sqlite_jsonb::as_base64_bytes")]
+///     data: Vec<u8>,
+/// }
+///
+/// let blob = serde_sqlite_jsonb::to_vec(&File { data: b"foo".to_vec() })?;
+/// assert_eq!(blob, b"\xac\x47data\x47Zm9v");
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+pub mod as_base64_bytes {
+    use super::BytesEncoding;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Serialize bytes as a base64 string
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the serializer.
+    pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]> + ?Sized,
+        S: Serializer,
+    {
+        serializer.serialize_str(&super::encode_base64(bytes.as_ref()))
+    }
+
+    /// Deserialize bytes from a base64 string
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is not a valid base64 string.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+        BytesEncoding::Base64
+            .decode(&text)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Store a `Vec<u8>` field as a string of hexadecimal digits, for use with
+/// `#[serde(with = "serde_sqlite_jsonb::as_hex_bytes")]`.
+/// See [`as_base64_bytes`](crate::as_base64_bytes).
+pub mod as_hex_bytes {
+    use super::BytesEncoding;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Serialize bytes as a string of lowercase hexadecimal digits
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the serializer.
+    pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]> + ?Sized,
+        S: Serializer,
+    {
+        serializer.serialize_str(&super::encode_hex(bytes.as_ref()))
+    }
+
+    /// Deserialize bytes from a string of hexadecimal digits
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is not a valid hexadecimal string.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+        BytesEncoding::Hex
+            .decode(&text)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        // RFC 4648 test vectors
+        for (bytes, text) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(encode_base64(bytes.as_bytes()), text);
+            assert_eq!(decode_base64(text).unwrap(), bytes.as_bytes());
+        }
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode_base64(&encode_base64(&all)).unwrap(), all);
+        for bad in ["Zg=", "Z===", "Zm9v!A==", "Zg==Zg=="] {
+            assert!(decode_base64(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(encode_hex(&[0, 0x1f, 0xab, 0xff]), "001fabff");
+        assert_eq!(decode_hex("001fABff").unwrap(), [0, 0x1f, 0xab, 0xff]);
+        assert!(decode_hex("abc").is_err());
+        assert!(decode_hex("zz").is_err());
+    }
+
+    #[test]
+    fn test_per_field_text_encodings() {
+        #[derive(
+            Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+        )]
+        struct Record {
+            #[serde(with = "as_base64_bytes")]
+            base64: Vec<u8>,
+            #[serde(with = "as_hex_bytes")]
+            hex: Vec<u8>,
+            array: Vec<u8>,
+        }
+
+        let record = Record {
+            base64: b"foo".to_vec(),
+            hex: vec![0x1f, 0xab],
+            array: vec![1, 2],
+        };
+        let blob = crate::to_vec(&record).unwrap();
+        // {"base64": "Zm9v", "hex": "1fab", "array": [1, 2]}
+        assert_eq!(
+            blob,
+            b"\xcc\x20\x67base64\x47Zm9v\x37hex\x471fab\x57array\x4b\x131\x132"
+        );
+        assert_eq!(crate::from_slice::<Record>(&blob).unwrap(), record);
+        // the global option does not change the marked fields
+        let options = crate::Options {
+            bytes_encoding: BytesEncoding::Hex,
+            ..crate::Options::default()
+        };
+        assert_eq!(crate::to_vec_with_options(&record, options).unwrap(), blob);
+        // {"base64": "!", "hex": "", "array": []}
+        assert!(crate::from_slice::<Record>(
+            b"\xcc\x14\x67base64\x17!\x37hex\x07\x57array\x0b"
+        )
+        .is_err());
+    }
+}
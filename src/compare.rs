@@ -0,0 +1,246 @@
+//! Comparison of JSONB values by meaning rather than by bytes.
+
+use crate::error::{Error, Result};
+use crate::extract::{split_element, Element};
+use crate::header::ElementType;
+use crate::number::Number;
+
+/// Whether two JSONB blobs hold the same JSON value.
+///
+/// The same value can be encoded in several ways: with headers that are
+/// larger than needed, with numbers written as `Int`, `Int5`, `Float`,
+/// `Float5` or `BinaryFloat` elements, with strings that use different
+/// escapes, and with object keys in any order. All of these compare equal.
+///
+/// Integers compare exactly with each other. As soon as one side is a
+/// float, both are compared as `f64`, so `1` equals `1.0`.
+/// Objects are compared without regard to key order. If a key appears
+/// several times, its values are paired in the order they appear.
+///
+/// ```
+/// use serde_sqlite_jsonb::jsonb_equal;
+///
+/// // {"a": 1, "b": 2} and {"b": 2.0, "a": 1}
+/// let a = b"\x8c\x17a\x131\x17b\x132";
+/// let b = b"\xac\x17b\x352.0\x17a\x131";
+/// assert!(jsonb_equal(a, b)?);
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if either blob is not a single valid JSONB element.
+/// Nested elements after the first difference are not checked.
+pub fn jsonb_equal(a: &[u8], b: &[u8]) -> Result<bool> {
+    elements_equal(&single_element(a)?, &single_element(b)?)
+}
+
+fn single_element(data: &[u8]) -> Result<Element<'_>> {
+    let (element, rest) = split_element(data)?;
+    if rest.is_empty() {
+        Ok(element)
+    } else {
+        Err(Error::TrailingCharacters)
+    }
+}
+
+/// The JSON kind of an element, regardless of how it is encoded
+#[derive(PartialEq)]
+enum Kind {
+    Null,
+    True,
+    False,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+fn kind(element: &Element) -> Result<Kind> {
+    Ok(match element.header.element_type {
+        ElementType::Null => Kind::Null,
+        ElementType::True => Kind::True,
+        ElementType::False => Kind::False,
+        ElementType::Int
+        | ElementType::Int5
+        | ElementType::Float
+        | ElementType::Float5
+        | ElementType::BinaryFloat
+        | ElementType::Reserved14 => Kind::Number,
+        ElementType::Text
+        | ElementType::TextJ
+        | ElementType::Text5
+        | ElementType::TextRaw => Kind::String,
+        ElementType::Array => Kind::Array,
+        ElementType::Object => Kind::Object,
+        t @ ElementType::Reserved13 => return Err(Error::unexpected(t)),
+    })
+}
+
+fn elements_equal(a: &Element, b: &Element) -> Result<bool> {
+    let kind_a = kind(a)?;
+    if kind_a != kind(b)? {
+        return Ok(false);
+    }
+    match kind_a {
+        Kind::Null | Kind::True | Kind::False => Ok(true),
+        Kind::Number => numbers_equal(a, b),
+        Kind::String => strings_equal(a, b),
+        Kind::Array => arrays_equal(a.payload, b.payload),
+        Kind::Object => objects_equal(a.payload, b.payload),
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn numbers_equal(a: &Element, b: &Element) -> Result<bool> {
+    let a: Number = crate::from_slice(a.bytes)?;
+    let b: Number = crate::from_slice(b.bytes)?;
+    let as_f64 = |n: &Number| match n {
+        Number::Int(i) => Some(*i as f64),
+        Number::UInt(u) => Some(*u as f64),
+        Number::Float(f) => Some(*f),
+        Number::Text(t) => t.parse().ok(),
+    };
+    Ok(match (&a, &b) {
+        (Number::Float(_), _) | (_, Number::Float(_)) => {
+            as_f64(&a) == as_f64(&b)
+        }
+        // integers too large for a u128 are compared by their digits
+        _ => a == b,
+    })
+}
+
+fn strings_equal(a: &Element, b: &Element) -> Result<bool> {
+    let unescaped = |t| matches!(t, ElementType::Text | ElementType::TextRaw);
+    if unescaped(a.header.element_type) && unescaped(b.header.element_type) {
+        return Ok(a.payload == b.payload);
+    }
+    let a: String = crate::from_slice(a.bytes)?;
+    let b: String = crate::from_slice(b.bytes)?;
+    Ok(a == b)
+}
+
+fn children(mut payload: &[u8]) -> Result<Vec<Element<'_>>> {
+    let mut elements = Vec::new();
+    while !payload.is_empty() {
+        let (element, rest) = split_element(payload)?;
+        elements.push(element);
+        payload = rest;
+    }
+    Ok(elements)
+}
+
+fn arrays_equal(a: &[u8], b: &[u8]) -> Result<bool> {
+    let a = children(a)?;
+    let b = children(b)?;
+    if a.len() != b.len() {
+        return Ok(false);
+    }
+    for (x, y) in a.iter().zip(&b) {
+        if !elements_equal(x, y)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// The entries of an object, sorted by key
+fn sorted_entries(payload: &[u8]) -> Result<Vec<(String, Element<'_>)>> {
+    let elements = children(payload)?;
+    let mut entries = Vec::with_capacity(elements.len() / 2);
+    for pair in elements.chunks(2) {
+        let key: String = crate::from_slice(pair[0].bytes)?;
+        match pair.get(1) {
+            Some(value) => entries.push((key, *value)),
+            None => return Err(Error::KeyWithoutValue(Some(key))),
+        }
+    }
+    // a stable sort keeps the values of repeated keys in their order
+    entries.sort_by(|x, y| x.0.cmp(&y.0));
+    Ok(entries)
+}
+
+fn objects_equal(a: &[u8], b: &[u8]) -> Result<bool> {
+    let a = sorted_entries(a)?;
+    let b = sorted_entries(b)?;
+    if a.len() != b.len() {
+        return Ok(false);
+    }
+    for ((key_a, x), (key_b, y)) in a.iter().zip(&b) {
+        if key_a != key_b || !elements_equal(x, y)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encodings_of_one_are_equal() {
+        let mut binary_float = vec![0x8f];
+        binary_float.extend_from_slice(&1.0f64.to_le_bytes());
+        let encodings: [&[u8]; 5] = [
+            b"\x131",      // Int
+            b"\xc3\x011",  // Int with a larger header than needed
+            b"\x340x1",    // Int5
+            b"\x351.0",    // Float
+            &binary_float, // BinaryFloat
+        ];
+        for a in encodings {
+            for b in encodings {
+                assert!(jsonb_equal(a, b).unwrap(), "{a:?} != {b:?}");
+            }
+        }
+        assert!(!jsonb_equal(b"\x131", b"\x132").unwrap());
+        assert!(!jsonb_equal(b"\x351.5", b"\x131").unwrap());
+        assert!(!jsonb_equal(b"\x131", b"\x171").unwrap());
+    }
+
+    #[cfg(feature = "serde_json5")]
+    #[test]
+    fn test_float5_equals_int() {
+        assert!(jsonb_equal(b"\x261.", b"\x131").unwrap());
+    }
+
+    #[test]
+    fn test_object_key_order_is_ignored() {
+        // {"a":1,"b":2} and {"b":2,"a":1}
+        let ab = b"\x8c\x17a\x131\x17b\x132";
+        let ba = b"\x8c\x17b\x132\x17a\x131";
+        assert!(jsonb_equal(ab, ba).unwrap());
+        // {"a":1,"b":3}
+        assert!(!jsonb_equal(ab, b"\x8c\x17a\x131\x17b\x133").unwrap());
+        // {"a":1}
+        assert!(!jsonb_equal(ab, b"\x4c\x17a\x131").unwrap());
+        // {"a":1,"c":2}
+        assert!(!jsonb_equal(ab, b"\x8c\x17a\x131\x17c\x132").unwrap());
+    }
+
+    #[test]
+    fn test_nested_values() {
+        // ["a\n", [true, null]] with the string as TextRaw and TextJ
+        let raw = b"\x6b\x2aa\n\x2b\x01\x00";
+        let escaped = b"\x7b\x38a\\n\x2b\x01\x00";
+        assert!(jsonb_equal(raw, escaped).unwrap());
+        // ["a\n", [true, false]]
+        assert!(!jsonb_equal(raw, b"\x6b\x2aa\n\x2b\x01\x02").unwrap());
+        // arrays are ordered: [[true, null], "a\n"]
+        assert!(!jsonb_equal(raw, b"\x6b\x2b\x01\x00\x2aa\n").unwrap());
+    }
+
+    #[test]
+    fn test_invalid_blobs() {
+        assert!(matches!(
+            jsonb_equal(b"\x131\x00", b"\x131"),
+            Err(Error::TrailingCharacters)
+        ));
+        assert!(jsonb_equal(b"\x131", b"\x23").is_err());
+        assert!(matches!(
+            jsonb_equal(b"\x2c\x17a", b"\x2c\x17a"),
+            Err(Error::KeyWithoutValue(Some(k))) if k == "a"
+        ));
+    }
+}
@@ -6,22 +6,78 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use crate::bytes::BytesEncoding;
 use crate::error::{Error, Result};
-use crate::header::{ElementType, Header};
-use serde::de::{self, Deserialize, IntoDeserializer, SeqAccess, Visitor};
-use std::io::Read;
+use crate::header::{
+    encode_minimal_header, parse_header, write_minimal_header, ElementType,
+    Header,
+};
+use crate::log::LENGTH_PREFIX_SIZE;
+use crate::options::Options;
+use serde::de::{
+    self, Deserialize, DeserializeOwned, IntoDeserializer, SeqAccess, Visitor,
+};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+mod map_key;
+
+use map_key::MapKeyDeserializer;
 
 /// A structure that deserializes `SQLite` JSONB data into Rust values.
-pub struct Deserializer<R: Read> {
+pub struct Deserializer<'de, R: Read> {
     /// The reader that the deserializer reads from.
     reader: R,
+    /// The input, when it is a byte slice, so that strings can be borrowed
+    /// from it. Nested deserializers hold the payload of their container.
+    input: Option<&'de [u8]>,
+    /// A header that was read ahead by [`Deserializer::peek_header`].
+    peeked: Option<Header>,
+    options: Options,
+    /// The keys read so far, when deserializing an object with
+    /// [`Options::reject_duplicate_keys`].
+    seen_keys: HashSet<String>,
+    /// The last key read with [`Options::reject_duplicate_keys`],
+    /// to report it if its value is missing.
+    last_key: Option<String>,
+    /// A buffer reused for the payloads of strings and numbers, so that
+    /// reading them does not allocate. Nested deserializers borrow it while
+    /// they run, see [`Deserializer::with_scratch`].
+    scratch: Vec<u8>,
+    /// The number of bytes left in the input, when it is known in advance:
+    /// for byte slices, and the containers read from them.
+    remaining: Option<u64>,
+    /// The part of [`Options::max_total_size`] that is not used yet.
+    /// Nested deserializers take it over while they run.
+    budget: Option<u64>,
+    /// The hook set with [`Deserializer::on_key`].
+    /// Nested deserializers take it over while they run.
+    on_key: Option<KeyHook<'de>>,
 }
 
-impl<'a> Deserializer<&'a [u8]> {
+/// How much of a payload is reserved in advance when the length of the
+/// input is not known, such as when reading from a stream.
+const MAX_UNCHECKED_RESERVE: usize = 1 << 16;
+
+/// A callback that observes the keys of objects, see [`Deserializer::on_key`]
+type KeyHook<'de> = Box<dyn FnMut(&str) + Send + 'de>;
+
+impl<'a> Deserializer<'a, &'a [u8]> {
     /// Deserialize an instance of type `T` from a byte slice of `SQLite` JSONB data.
     #[must_use]
     pub fn from_bytes(input: &'a [u8]) -> Self {
-        Deserializer { reader: input }
+        Deserializer::from_bytes_with_options(input, Options::default())
+    }
+
+    /// Deserialize an instance of type `T` from a byte slice of `SQLite` JSONB data,
+    /// using the given options.
+    #[must_use]
+    pub fn from_bytes_with_options(input: &'a [u8], options: Options) -> Self {
+        let mut de = Deserializer::new(input, options);
+        de.input = Some(input);
+        de.remaining = Some(input.len() as u64);
+        de
     }
 }
 
@@ -34,52 +90,443 @@ pub fn from_slice<'a, T>(s: &'a [u8]) -> Result<T>
 where
     T: Deserialize<'a>,
 {
-    let mut deserializer = Deserializer::from_bytes(s);
+    from_slice_with_options(s, Options::default())
+}
+
+/// Deserialize an instance of type `T` from a byte slice of `SQLite` JSONB data,
+/// using the given options.
+///
+/// # Errors
+///
+/// Returns an error if the input data is invalid or if deserialization fails.
+pub fn from_slice_with_options<'a, T>(
+    s: &'a [u8],
+    options: Options,
+) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    check_not_json_text(s)?;
+    let mut deserializer = Deserializer::from_bytes_with_options(s, options);
     let t = T::deserialize(&mut deserializer)?;
-    if deserializer.reader.is_empty() {
+    if deserializer.peeked.is_none() && deserializer.reader.is_empty() {
         Ok(t)
     } else {
         Err(Error::TrailingCharacters)
     }
 }
 
-/// Deserialize an instance of type `T` from a byte slice of `SQLite` JSONB data.
+/// Deserialize `SQLite` JSONB data into an existing value, which can reuse its
+/// allocations: a `Vec` or a `String` keeps its capacity, for instance.
+/// This is useful when decoding many blobs one after the other.
+///
+/// ```
+/// let mut values: Vec<i64> = Vec::with_capacity(16);
+/// for blob in [&b"\x4b\x131\x132"[..], b"\x2b\x133"] {
+///     serde_sqlite_jsonb::from_slice_in_place(blob, &mut values)?;
+///     assert!(values.capacity() >= 16);
+/// }
+/// assert_eq!(values, [3]);
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
 ///
 /// # Errors
 ///
 /// Returns an error if the input data is invalid or if deserialization fails.
-pub fn from_reader<'a, R: Read, T>(reader: R) -> Result<T>
+/// The value may then be partially overwritten.
+pub fn from_slice_in_place<'a, T>(s: &'a [u8], place: &mut T) -> Result<()>
+where
+    T: Deserialize<'a>,
+{
+    check_not_json_text(s)?;
+    let mut deserializer = Deserializer::from_bytes(s);
+    T::deserialize_in_place(&mut deserializer, place)?;
+    if deserializer.peeked.is_none() && deserializer.reader.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::TrailingCharacters)
+    }
+}
+
+/// Deserialize an instance of type `T` from `SQLite` JSONB data followed by
+/// its CRC-32, as written with [`Options::append_crc32`].
+///
+/// # Errors
+///
+/// Returns [`Error::ChecksumMismatch`] if the data was altered, and an error
+/// if the input data is invalid or if deserialization fails.
+pub fn from_slice_checked<'a, T>(s: &'a [u8]) -> Result<T>
 where
     T: Deserialize<'a>,
 {
-    let mut deserializer = Deserializer { reader };
+    if s.len() < 4 {
+        return Err(Error::Io(std::io::Error::from(
+            std::io::ErrorKind::UnexpectedEof,
+        )));
+    }
+    let (value, checksum) = s.split_at(s.len() - 4);
+    let stored = u32::from_be_bytes([
+        checksum[0],
+        checksum[1],
+        checksum[2],
+        checksum[3],
+    ]);
+    let computed = crate::checksum::crc32(value);
+    if stored != computed {
+        return Err(Error::ChecksumMismatch { stored, computed });
+    }
+    from_slice(value)
+}
+
+/// Detect the common mistake of passing JSON text (as returned by `SQLite`'s `json()`)
+/// instead of a JSONB blob. JSON text bytes, read as a JSONB header, almost never
+/// declare a payload that matches the length of the input.
+fn check_not_json_text(s: &[u8]) -> Result<()> {
+    let (header, header_len) = match crate::header::parse_header(s) {
+        Ok(parsed) => parsed,
+        Err(_) => return Ok(()),
+    };
+    let matches_length =
+        u64::try_from(s.len() - header_len) == Ok(header.payload_size);
+    // only scan the input when it is not a well-formed blob
+    if matches_length || !looks_like_json_text(s) {
+        Ok(())
+    } else {
+        Err(Error::LikelyJsonText)
+    }
+}
+
+fn looks_like_json_text(s: &[u8]) -> bool {
+    s.iter()
+        .all(|&b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+        && s.iter()
+            .find(|b| !b.is_ascii_whitespace())
+            .map_or(false, |&b| {
+                matches!(
+                    b,
+                    b'{' | b'[' | b'"' | b'-' | b'0'
+                        ..=b'9' | b't' | b'f' | b'n'
+                )
+            })
+}
+
+/// Deserialize an instance of type `T` from a reader of `SQLite` JSONB data.
+///
+/// Nothing can be borrowed from a reader, so `T` must own its data:
+/// decode strings to `String` rather than `&str`, or use [`from_slice`].
+///
+/// ```compile_fail
+/// let name: &str = serde_sqlite_jsonb::from_reader(&b"\x47John"[..]).unwrap();
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the input data is invalid or if deserialization fails.
+pub fn from_reader<R: Read, T>(reader: R) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    from_reader_with_options(reader, Options::default())
+}
+
+/// Deserialize an instance of type `T` from a reader of `SQLite` JSONB data,
+/// using the given options.
+///
+/// # Errors
+///
+/// Returns an error if the input data is invalid or if deserialization fails.
+pub fn from_reader_with_options<R: Read, T>(
+    reader: R,
+    options: Options,
+) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let mut deserializer = Deserializer::new(reader, options);
     let t = T::deserialize(&mut deserializer)?;
-    let Deserializer { mut reader } = deserializer;
-    if reader.read(&mut [0])? == 0 {
+    if deserializer.peeked.is_none()
+        && read_byte(&mut deserializer.reader)?.is_none()
+    {
         Ok(t)
     } else {
         Err(Error::TrailingCharacters)
     }
 }
 
-impl<R: Read> Deserializer<R> {
-    fn with_header(&mut self, header: Header) -> Deserializer<impl Read + '_> {
-        // a little bit of a hack to "unread" a header that was already read
-        let header_bytes = std::io::Cursor::new(header.serialize());
-        let reader = header_bytes.chain(&mut self.reader);
-        Deserializer { reader }
+/// Deserialize a single JSONB value from the start of a reader, and return it
+/// together with the reader, positioned right after the value.
+///
+/// Unlike [`from_reader`], the bytes that follow the value are not checked,
+/// so that other data can be read after it.
+///
+/// # Errors
+///
+/// Returns an error if the input data is invalid or if deserialization fails.
+pub fn from_reader_with_remainder<R: Read, T>(reader: R) -> Result<(T, R)>
+where
+    T: DeserializeOwned,
+{
+    let mut deserializer = Deserializer::new(reader, Options::default());
+    let t = T::deserialize(&mut deserializer)?;
+    Ok((t, deserializer.reader))
+}
+
+/// Deserialize a JSONB value framed by its length, as a 4-byte big-endian
+/// integer, from a reader.
+///
+/// Exactly the length prefix and the value are read, so that the next framed
+/// value can be read from the same reader.
+///
+/// # Errors
+///
+/// Returns an error if the reader ends before the end of the value,
+/// if the input data is invalid, or if deserialization fails.
+pub fn from_length_prefixed_reader<R: Read, T>(mut reader: R) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let mut prefix = [0u8; LENGTH_PREFIX_SIZE];
+    reader.read_exact(&mut prefix)?;
+    let len = u32::from_be_bytes(prefix);
+    // Do not allocate the announced length before the data is actually there
+    let mut data = Vec::new();
+    reader.take(u64::from(len)).read_to_end(&mut data)?;
+    if data.len() != len as usize {
+        return Err(Error::Io(std::io::Error::from(
+            std::io::ErrorKind::UnexpectedEof,
+        )));
+    }
+    from_slice(&data)
+}
+
+/// Deserialize an instance of type `T` from a trait object reader.
+///
+/// This is equivalent to [`from_reader`], but is not monomorphized for each
+/// reader type, which keeps binaries small when parsing from many kinds of readers.
+///
+/// # Errors
+///
+/// Returns an error if the input data is invalid or if deserialization fails.
+pub fn from_dyn_reader<T>(reader: &mut dyn Read) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    from_reader(reader)
+}
+
+/// Read the header of the JSONB element at the start of `reader`, and leave
+/// the reader positioned at the start of its payload.
+///
+/// Pass `&mut reader` to keep using the reader afterwards. This is the
+/// streaming counterpart of [`is_jsonb`](crate::is_jsonb): the payload
+/// itself is not read. With `reject_reserved`, an element of a type that
+/// is reserved for future expansion, which this crate cannot decode, is an
+/// error.
+///
+/// `Reserved14` is not rejected, although `SQLite` reserves it too: this
+/// crate decodes it as the binary integers written with
+/// [`Options::binary_int`]. To only accept data that `SQLite` can read,
+/// also check that the element type is not `Reserved14`.
+///
+/// ```
+/// use serde_sqlite_jsonb::{read_and_validate_header, ElementType};
+///
+/// // [1, 2]
+/// let mut reader: &[u8] = b"\x4b\x131\x132";
+/// let header = read_and_validate_header(&mut reader, true)?;
+/// assert_eq!(header.element_type, ElementType::Array);
+/// assert_eq!(header.payload_size, 4);
+/// assert_eq!(reader, b"\x131\x132");
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the reader is empty, if it ends within the header,
+/// or if `reject_reserved` is set and the element type is reserved.
+pub fn read_and_validate_header<R: Read>(
+    reader: R,
+    reject_reserved: bool,
+) -> Result<Header> {
+    let header = Deserializer::new(reader, Options::default()).read_header()?;
+    match header.element_type {
+        t @ ElementType::Reserved13 if reject_reserved => {
+            Err(Error::unexpected(t))
+        }
+        _ => Ok(header),
+    }
+}
+
+/// Copy the JSONB element at the start of `reader` to `writer`, byte for
+/// byte, without decoding it. Returns the number of bytes copied.
+///
+/// The header of the element gives the size of its whole payload, nested
+/// elements included, so exactly one element is read: pass `&mut reader` to
+/// read what follows it. The payload is not validated.
+///
+/// ```
+/// // {"a": [1]}, followed by another element
+/// let mut reader: &[u8] = b"\x5c\x17a\x2b\x131\x00";
+/// let mut copy = Vec::new();
+/// assert_eq!(serde_sqlite_jsonb::copy_element(&mut reader, &mut copy)?, 6);
+/// assert_eq!(copy, b"\x5c\x17a\x2b\x131");
+/// assert_eq!(reader, b"\x00");
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the reader ends before the end of the element, or
+/// if reading or writing fails. The bytes read until then may already have
+/// been written.
+pub fn copy_element<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+) -> Result<usize> {
+    let mut header = [0u8; 9];
+    header[0] = match read_byte(&mut reader)? {
+        Some(b) => b,
+        None => return Err(Error::Empty),
+    };
+    let header_len = match header[0] >> 4 {
+        0..=11 => 1,
+        12 => 2,
+        13 => 3,
+        14 => 5,
+        _ => 9,
+    };
+    let read = read_up_to(&mut reader, &mut header[1..header_len])?;
+    if 1 + read < header_len {
+        return Err(Error::InvalidHeaderLength {
+            expected: header_len,
+            available: 1 + read,
+        });
+    }
+    let (Header { payload_size, .. }, _) = parse_header(&header[..header_len])?;
+    let total = usize::try_from(payload_size)
+        .ok()
+        .and_then(|size| size.checked_add(header_len))
+        .ok_or_else(|| {
+            Error::Message(format!(
+                "an element of {payload_size} bytes cannot be copied on this platform"
+            ))
+        })?;
+    writer.write_all(&header[..header_len])?;
+    let copied = std::io::copy(&mut reader.take(payload_size), &mut writer)?;
+    if copied < payload_size {
+        return Err(Error::Truncated {
+            payload_size,
+            available: copied,
+        });
+    }
+    Ok(total)
+}
+
+impl<'de, R: Read> Deserializer<'de, R> {
+    fn new(reader: R, options: Options) -> Self {
+        Deserializer {
+            reader,
+            input: None,
+            peeked: None,
+            options,
+            seen_keys: HashSet::new(),
+            last_key: None,
+            scratch: Vec::new(),
+            remaining: None,
+            budget: options.max_total_size,
+            on_key: None,
+        }
+    }
+
+    /// Run `f` on a new deserializer for `reader`, which holds the given
+    /// scratch buffer, size budget and key hook until `f` returns. The parent
+    /// deserializer does not read anything while its child runs, so they can
+    /// share the same buffer, and the child spends from the same budget.
+    #[allow(clippy::too_many_arguments)]
+    fn with_scratch<T>(
+        reader: R,
+        options: Options,
+        input: Option<&'de [u8]>,
+        remaining: Option<u64>,
+        scratch: &mut Vec<u8>,
+        budget: &mut Option<u64>,
+        on_key: &mut Option<KeyHook<'de>>,
+        f: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let mut de = Deserializer::new(reader, options);
+        de.input = input;
+        de.remaining = remaining;
+        de.scratch = std::mem::take(scratch);
+        de.budget = *budget;
+        de.on_key = on_key.take();
+        let result = f(&mut de);
+        *scratch = de.scratch;
+        *budget = de.budget;
+        *on_key = de.on_key;
+        result
+    }
+
+    /// Call `hook` with each object key, in every nested object, as it is
+    /// read. The keys are reported whatever the deserialized type does with
+    /// them, which helps monitoring data that gains unexpected fields.
+    /// The values of ignored fields are skipped without being read, so the
+    /// keys of objects nested in them are not reported.
+    ///
+    /// Keys are decoded as usual for the deserialized type: the hook gets a
+    /// copy of the text of string keys, and of numeric keys as they are
+    /// written. It may borrow local state for as long as the deserializer.
+    ///
+    /// ```
+    /// use serde_sqlite_jsonb::Deserializer;
+    ///
+    /// // {"id": 1, "extra": true}
+    /// let blob = b"\xcc\x0c\x27id\x131\x57extra\x01";
+    /// let mut keys = Vec::new();
+    /// let mut de = Deserializer::from_bytes(blob);
+    /// de.on_key(|key| keys.push(key.to_owned()));
+    /// let _: std::collections::HashMap<String, serde::de::IgnoredAny> =
+    ///     serde::Deserialize::deserialize(&mut de)?;
+    /// drop(de);
+    /// assert_eq!(keys, ["id", "extra"]);
+    /// # Ok::<(), serde_sqlite_jsonb::Error>(())
+    /// ```
+    pub fn on_key(&mut self, hook: impl FnMut(&str) + Send + 'de) {
+        self.on_key = Some(Box::new(hook));
+    }
+
+    /// Read the header of the next element without consuming it.
+    /// The next deserialization will start from that same element.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no next element, or if its header cannot be read.
+    pub fn peek_header(&mut self) -> Result<Header> {
+        let header = self.read_header()?;
+        self.peeked = Some(header);
+        Ok(header)
+    }
+
+    /// Check whether the next element is a JSON `null`, without consuming it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no next element, or if its header cannot be read.
+    pub fn peek_is_null(&mut self) -> Result<bool> {
+        Ok(self.peek_header()?.element_type == ElementType::Null)
     }
 
     fn read_header(&mut self) -> Result<Header> {
+        if let Some(header) = self.peeked.take() {
+            return Ok(header);
+        }
         /*  The upper four bits of the first byte of the header determine
           - size of the header
           - and possibly also the size of the payload.
         */
-        let mut header_0 = [0u8; 1];
-        if self.reader.read(&mut header_0)? == 0 {
-            return Err(Error::Empty);
-        }
-        let first_byte = header_0[0];
+        let first_byte = match read_byte(&mut self.reader)? {
+            Some(b) => b,
+            None => return Err(Error::Empty),
+        };
         let upper_four_bits = first_byte >> 4;
         /*
          If the upper four bits have a value between 0 and 11,
@@ -107,25 +554,114 @@ impl<R: Read> Deserializer<R> {
         } else {
             let mut buf = [0u8; 8];
             let start = 8 - bytes_to_read;
-            self.reader.read_exact(&mut buf[start..8])?;
+            let read = read_up_to(&mut self.reader, &mut buf[start..8])?;
+            if read < bytes_to_read {
+                return Err(Error::InvalidHeaderLength {
+                    expected: 1 + bytes_to_read,
+                    available: 1 + read,
+                });
+            }
             u64::from_be_bytes(buf)
         };
+        if self.options.require_minimal_headers {
+            let (_, minimal_len) =
+                encode_minimal_header(ElementType::Null, payload_size);
+            if minimal_len != 1 + bytes_to_read {
+                return Err(Error::NonMinimalHeader {
+                    header_len: 1 + bytes_to_read,
+                    minimal_len,
+                });
+            }
+        }
+        if let Some(remaining) = self.remaining {
+            // the whole element is consumed from here on, or fails to parse
+            let available = remaining.saturating_sub(1 + bytes_to_read as u64);
+            if payload_size > available {
+                return Err(Error::Truncated {
+                    payload_size,
+                    available,
+                });
+            }
+            self.remaining = Some(available - payload_size);
+        }
+        let element_type = ElementType::from(first_byte);
+        // the elements of arrays and objects count on their own
+        let mut cost = 1 + bytes_to_read as u64;
+        if !matches!(element_type, ElementType::Array | ElementType::Object) {
+            cost = cost.saturating_add(payload_size);
+        }
+        self.spend_budget(cost)?;
         Ok(Header {
-            element_type: ElementType::from(first_byte),
+            element_type,
             payload_size,
         })
     }
 
-    fn read_payload_string(&mut self, header: Header) -> Result<String> {
+    /// Count `cost` bytes against [`Options::max_total_size`], before they
+    /// are allocated.
+    fn spend_budget(&mut self, cost: u64) -> Result<()> {
+        if let Some(budget) = self.budget {
+            self.budget = Some(budget.checked_sub(cost).ok_or(
+                Error::SizeLimitExceeded {
+                    limit: self.options.max_total_size.unwrap_or_default(),
+                },
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Read the payload of an element into the scratch buffer, and return
+    /// the number of bytes read, which is less than the payload size if the
+    /// input ends early.
+    fn read_payload_to_scratch(&mut self, header: Header) -> Result<usize> {
+        let payload_size = usize::try_from(header.payload_size)
+            .map_err(Error::IntConversion)?;
+        self.scratch.clear();
+        // The size comes from the input: without a known input length to
+        // check it against, the buffer grows as the payload is actually read
+        let reserved = match self.remaining {
+            Some(_) => payload_size,
+            None => payload_size.min(MAX_UNCHECKED_RESERVE),
+        };
+        self.scratch.reserve(reserved);
+        let mut reader = (&mut self.reader).take(header.payload_size);
+        Ok(reader.read_to_end(&mut self.scratch)?)
+    }
+
+    fn read_payload_str(&mut self, header: Header) -> Result<&str> {
         let payload_size = usize::try_from(header.payload_size)
             .map_err(Error::IntConversion)?;
-        let mut str = String::with_capacity(payload_size);
-        let read = self.reader_with_limit(header).read_to_string(&mut str)?;
-        assert_eq!(read, payload_size);
-        Ok(str)
+        if self.read_payload_to_scratch(header)? != payload_size {
+            return Err(std::io::Error::from(
+                std::io::ErrorKind::UnexpectedEof,
+            )
+            .into());
+        }
+        payload_utf8(&self.scratch)
+    }
+
+    /// The payload of the element whose header was just read, borrowed from
+    /// the input when it is a byte slice. The payload is not consumed.
+    fn borrow_payload(&self, header: Header) -> Option<&'de [u8]> {
+        let input = self.input?;
+        let after = usize::try_from(self.remaining?).ok()?;
+        let end = input.len().checked_sub(after)?;
+        let start =
+            end.checked_sub(usize::try_from(header.payload_size).ok()?)?;
+        input.get(start..end)
+    }
+
+    /// Read the text of a numeric element.
+    /// `SQLite` never puts whitespace around numbers, so it is rejected.
+    fn read_numeric_payload(&mut self, header: Header) -> Result<&str> {
+        let element_type = header.element_type;
+        let text = self.read_payload_str(header)?;
+        check_numeric_payload(element_type, text.as_bytes())?;
+        Ok(text)
     }
 
-    fn drop_payload(&mut self, header: Header) -> Result<ElementType> {
+    /// Skip the payload of an element, and return the number of bytes skipped.
+    fn drop_payload(&mut self, header: Header) -> Result<u64> {
         let mut remaining = usize::try_from(header.payload_size)
             .map_err(Error::IntConversion)?;
         while remaining > 0 {
@@ -134,7 +670,7 @@ impl<R: Read> Deserializer<R> {
             self.reader.read_exact(&mut buf[..len])?;
             remaining -= len;
         }
-        Ok(header.element_type)
+        Ok(header.payload_size)
     }
 
     fn read_bool(&mut self, header: Header) -> Result<bool> {
@@ -142,7 +678,7 @@ impl<R: Read> Deserializer<R> {
         match header.element_type {
             ElementType::True => Ok(true),
             ElementType::False => Ok(false),
-            t => Err(Error::UnexpectedType(t)),
+            t => Err(Error::expected("boolean", t)),
         }
     }
 
@@ -150,7 +686,7 @@ impl<R: Read> Deserializer<R> {
         self.drop_payload(header)?;
         match header.element_type {
             ElementType::Null => Ok(()),
-            t => Err(Error::UnexpectedType(t)),
+            t => Err(Error::expected("null", t)),
         }
     }
 
@@ -170,10 +706,37 @@ impl<R: Read> Deserializer<R> {
                 .map_err(Error::IntConversion)?;
             let smallbuf = &mut buf[..payload_size];
             self.reader.read_exact(smallbuf)?;
+            check_numeric_payload(header.element_type, smallbuf)?;
             Ok(crate::json::parse_json_slice(smallbuf)?)
         } else {
-            let mut reader = self.reader_with_limit(header);
-            Ok(crate::json::parse_json(&mut reader)?)
+            self.read_payload_to_scratch(header)?;
+            check_numeric_payload(header.element_type, &self.scratch)?;
+            Ok(crate::json::parse_json_slice(&self.scratch)?)
+        }
+    }
+
+    /// Read a `Float` element. `SQLite` stores infinities as `9e999`, which
+    /// JSON parsers reject as out of range: such floats become infinities.
+    fn read_json_float<T>(&mut self, header: Header) -> Result<T>
+    where
+        for<'a> T: Deserialize<'a>,
+    {
+        let text = self.read_numeric_payload(header)?;
+        match crate::json::parse_json_slice(text.as_bytes()) {
+            Ok(value) => Ok(value),
+            Err(e) => match text.parse::<f64>() {
+                Ok(f)
+                    if f.is_infinite()
+                        && text.bytes().all(|b| {
+                            b.is_ascii_digit() || b"+-.eE".contains(&b)
+                        }) =>
+                {
+                    let deserializer: serde::de::value::F64Deserializer<Error> =
+                        f.into_deserializer();
+                    Ok(T::deserialize(deserializer)?)
+                }
+                _ => Err(e.into()),
+            },
         }
     }
 
@@ -181,8 +744,15 @@ impl<R: Read> Deserializer<R> {
     where
         for<'a> T: Deserialize<'a>,
     {
-        let mut reader = self.reader_with_limit(header);
-        Ok(crate::json::parse_json5(&mut reader)?)
+        self.read_payload_to_scratch(header)?;
+        check_numeric_payload(header.element_type, &self.scratch)?;
+        // Without a json5 parser, the payloads that are also valid JSON,
+        // such as `1.5`, can still be read
+        #[cfg(not(feature = "serde_json5"))]
+        if let Ok(value) = crate::json::parse_json_slice(&self.scratch) {
+            return Ok(value);
+        }
+        Ok(crate::json::parse_json5(&self.scratch[..])?)
     }
 
     fn read_json_compatible_string(
@@ -212,19 +782,217 @@ impl<R: Read> Deserializer<R> {
             ElementType::Float | ElementType::Int => {
                 self.read_json_compatible(header)
             }
-            t => Err(Error::UnexpectedType(t)),
+            ElementType::Reserved14 => {
+                let deserializer: serde::de::value::I64Deserializer<Error> =
+                    self.read_binary_int(header)?.into_deserializer();
+                Ok(T::deserialize(deserializer)?)
+            }
+            t => Err(Error::expected("integer", t)),
         }
     }
 
-    fn read_string(&mut self, header: Header) -> Result<String> {
+    /// Read a binary integer element, written with [`Options::binary_int`]
+    fn read_binary_int(&mut self, header: Header) -> Result<i64> {
+        let payload_size = usize::try_from(header.payload_size)
+            .map_err(Error::IntConversion)?;
+        if !matches!(payload_size, 1 | 2 | 4 | 8) {
+            return Err(Error::Message(format!(
+                "invalid payload size {payload_size} for binary integer"
+            )));
+        }
+        let mut buf = [0u8; 8];
+        self.reader.read_exact(&mut buf[..payload_size])?;
+        // extend the sign bit of the last byte to the missing ones
+        if buf[payload_size - 1] & 0x80 != 0 {
+            buf[payload_size..].fill(0xff);
+        }
+        Ok(i64::from_le_bytes(buf))
+    }
+
+    /// Read an `Int` or `Int5` element without going through a float or a
+    /// 64-bit integer, so that no precision is lost.
+    fn read_exact_integer(&mut self, header: Header) -> Result<Integer> {
+        match header.element_type {
+            ElementType::Int | ElementType::Int5 => {}
+            ElementType::Reserved14 => {
+                let i = self.read_binary_int(header)?;
+                return Ok(Integer::Signed(i128::from(i)));
+            }
+            t => return Err(Error::expected("integer", t)),
+        }
+        let text = self.read_numeric_payload(header)?;
+        let (negative, digits) = match text.as_bytes() {
+            [b'-', ..] => (true, &text[1..]),
+            [b'+', ..] => (false, &text[1..]),
+            _ => (false, text),
+        };
+        // json5 integers can be hexadecimal
+        let parsed = match digits.get(..2) {
+            Some("0x" | "0X") => u128::from_str_radix(&digits[2..], 16),
+            _ => digits.parse::<u128>(),
+        };
+        Ok(match (parsed, negative) {
+            (Ok(u), false) => match i128::try_from(u) {
+                Ok(i) => Integer::Signed(i),
+                Err(_) => Integer::Unsigned(u),
+            },
+            (Ok(u), true) => match i128::try_from(u) {
+                Ok(i) => Integer::Signed(-i),
+                Err(_) if u == i128::MIN.unsigned_abs() => {
+                    Integer::Signed(i128::MIN)
+                }
+                Err(_) => Integer::Text(text.to_owned()),
+            },
+            (Err(_), _) => Integer::Text(text.to_owned()),
+        })
+    }
+
+    /// Visit an enum stored as a container holding its variant name followed
+    /// by its value, and nothing else.
+    fn deserialize_enum_container<V>(
+        &mut self,
+        header: Header,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let input = self.borrow_payload(header);
+        let reader = (&mut self.reader).take(header.payload_size);
+        Deserializer::with_scratch(
+            reader,
+            self.options,
+            input,
+            self.remaining.map(|_| header.payload_size),
+            &mut self.scratch,
+            &mut self.budget,
+            &mut self.on_key,
+            |de| {
+                let r = visitor.visit_enum(&mut *de);
+                if read_byte(&mut de.reader)?.is_none() {
+                    r
+                } else {
+                    Err(Error::TrailingCharacters)
+                }
+            },
+        )
+    }
+
+    /// Visit the text of a numeric element, without parsing it.
+    fn deserialize_raw_number<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let header = self.read_header()?;
+        match header.element_type {
+            ElementType::Int
+            | ElementType::Int5
+            | ElementType::Float
+            | ElementType::Float5 => {
+                visitor.visit_str(self.read_numeric_payload(header)?)
+            }
+            ElementType::BinaryFloat => {
+                let f: f64 = self.read_float(header)?;
+                visitor.visit_string(crate::number::float_text(f))
+            }
+            ElementType::Reserved14 => {
+                visitor.visit_string(self.read_binary_int(header)?.to_string())
+            }
+            t => Err(Error::expected("number", t)),
+        }
+    }
+
+    /// Visit a numeric element with its exact value: integers as `i128` or
+    /// `u128`, or as their text when they are too large, and floats as `f64`.
+    fn deserialize_number<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let header = self.read_header()?;
+        match header.element_type {
+            ElementType::Int | ElementType::Int5 | ElementType::Reserved14 => {
+                match self.read_exact_integer(header)? {
+                    Integer::Signed(i) => visitor.visit_i128(i),
+                    Integer::Unsigned(u) => visitor.visit_u128(u),
+                    Integer::Text(text) => visitor.visit_string(text),
+                }
+            }
+            ElementType::Float
+            | ElementType::Float5
+            | ElementType::BinaryFloat => {
+                visitor.visit_f64(self.read_finite_float(header)?)
+            }
+            t => Err(Error::expected("number", t)),
+        }
+    }
+
+    /// Read a string element. Strings without escapes are borrowed from the
+    /// scratch buffer, the others are unescaped into a new `String`.
+    fn read_str(&mut self, header: Header) -> Result<Cow<'_, str>> {
         match header.element_type {
             ElementType::Text | ElementType::TextRaw => {
-                self.read_payload_string(header)
+                self.read_payload_str(header).map(Cow::Borrowed)
+            }
+            ElementType::TextJ => {
+                self.read_json_compatible_string(header).map(Cow::Owned)
+            }
+            ElementType::Text5 => {
+                self.read_json5_compatible_string(header).map(Cow::Owned)
+            }
+            ElementType::Int
+            | ElementType::Int5
+            | ElementType::Float
+            | ElementType::Float5
+                if self.options.numbers_as_strings =>
+            {
+                self.read_numeric_payload(header).map(Cow::Borrowed)
             }
-            ElementType::TextJ => self.read_json_compatible_string(header),
-            ElementType::Text5 => self.read_json5_compatible_string(header),
-            t => Err(Error::UnexpectedType(t)),
+            t => Err(Error::expected("string", t)),
+        }
+    }
+
+    fn read_string(&mut self, header: Header) -> Result<String> {
+        self.read_str(header).map(Cow::into_owned)
+    }
+
+    /// Visit a string element, with `visit_borrowed_str` when it needs no
+    /// unescaping and the input is a byte slice, or `visit_str` when it needs
+    /// no unescaping
+    fn visit_string_element<V>(
+        &mut self,
+        header: Header,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if matches!(
+            header.element_type,
+            ElementType::Text | ElementType::TextRaw
+        ) {
+            if let Some(payload) = self.borrow_payload(header) {
+                self.drop_payload(header)?;
+                return visitor.visit_borrowed_str(payload_utf8(payload)?);
+            }
+        }
+        match self.read_str(header)? {
+            Cow::Borrowed(s) => visitor.visit_str(s),
+            Cow::Owned(s) => visitor.visit_string(s),
+        }
+    }
+
+    /// Read a float with [`Self::read_float`], then apply
+    /// [`Options::reject_non_finite`]
+    fn read_finite_float<T>(&mut self, header: Header) -> Result<T>
+    where
+        for<'a> T: Deserialize<'a> + Into<f64> + Copy,
+    {
+        let value: T = self.read_float(header)?;
+        let f: f64 = value.into();
+        if self.options.reject_non_finite && !f.is_finite() {
+            return Err(Error::NonFiniteFloat(f));
         }
+        Ok(value)
     }
 
     fn read_float<T>(&mut self, header: Header) -> Result<T>
@@ -232,9 +1000,9 @@ impl<R: Read> Deserializer<R> {
         for<'a> T: Deserialize<'a>,
     {
         match header.element_type {
-            ElementType::Int | ElementType::Float => {
-                self.read_json_compatible(header)
-            }
+            ElementType::Int => self.read_json_compatible(header),
+            ElementType::Reserved14 => self.read_integer(header),
+            ElementType::Float => self.read_json_float(header),
             ElementType::Int5 | ElementType::Float5 => {
                 self.read_json5_compatible(header)
             }
@@ -243,6 +1011,16 @@ impl<R: Read> Deserializer<R> {
                 let payload_size = usize::try_from(header.payload_size)
                     .map_err(Error::IntConversion)?;
                 match payload_size {
+                    #[cfg(feature = "half")]
+                    2 => {
+                        let mut buf = [0u8; 2];
+                        self.reader.read_exact(&mut buf)?;
+                        let f = half::f16::from_le_bytes(buf).to_f32();
+                        let deserializer: serde::de::value::F32Deserializer<
+                            Error,
+                        > = f.into_deserializer();
+                        Ok(T::deserialize(deserializer)?)
+                    }
                     4 => {
                         let mut buf = [0u8; 4];
                         self.reader.read_exact(&mut buf)?;
@@ -266,11 +1044,11 @@ impl<R: Read> Deserializer<R> {
                     ))),
                 }
             }
-            t => Err(Error::UnexpectedType(t)),
+            t => Err(Error::expected("number", t)),
         }
     }
 
-    fn deserialize_any_with_header<'de, V>(
+    fn deserialize_any_with_header<V>(
         &mut self,
         header: Header,
         visitor: V,
@@ -286,13 +1064,27 @@ impl<R: Read> Deserializer<R> {
             ElementType::True | ElementType::False => {
                 visitor.visit_bool(self.read_bool(header)?)
             }
+            // an f32 written with `binary_float`
+            ElementType::BinaryFloat if header.payload_size == 4 => {
+                visitor.visit_f32(self.read_finite_float(header)?)
+            }
             ElementType::Float
             | ElementType::Float5
             | ElementType::BinaryFloat => {
-                visitor.visit_f64(self.read_float(header)?)
+                visitor.visit_f64(self.read_finite_float(header)?)
             }
-            ElementType::Int | ElementType::Int5 => {
-                let i: i64 = self.read_integer(header)?;
+            ElementType::Int | ElementType::Int5 | ElementType::Reserved14 => {
+                let i = match self.read_exact_integer(header)? {
+                    Integer::Signed(i) => i,
+                    Integer::Unsigned(u) => return visitor.visit_u128(u),
+                    Integer::Text(text) => {
+                        // too large for any integer type
+                        let f = text.parse::<f64>().map_err(|_| {
+                            Error::Message(format!("invalid integer: {text}"))
+                        })?;
+                        return visitor.visit_f64(f);
+                    }
+                };
                 if let Ok(x) = u8::try_from(i) {
                     visitor.visit_u8(x)
                 } else if let Ok(x) = i8::try_from(i) {
@@ -307,44 +1099,150 @@ impl<R: Read> Deserializer<R> {
                     visitor.visit_i32(x)
                 } else if let Ok(x) = u64::try_from(i) {
                     visitor.visit_u64(x)
+                } else if let Ok(x) = i64::try_from(i) {
+                    visitor.visit_i64(x)
+                } else if let Ok(x) = u128::try_from(i) {
+                    visitor.visit_u128(x)
                 } else {
-                    visitor.visit_i64(i)
+                    visitor.visit_i128(i)
                 }
             }
-            ElementType::Array => visitor.visit_seq(self),
+            ElementType::Array => {
+                let limit = header.payload_size;
+                let input = self.borrow_payload(header);
+                // Avoids infinite type inference recursion by using dynamic dispatch
+                let reader = (&mut self.reader as &mut dyn Read).take(limit);
+                Deserializer::with_scratch(
+                    reader,
+                    self.options,
+                    input,
+                    self.remaining.map(|_| limit),
+                    &mut self.scratch,
+                    &mut self.budget,
+                    &mut self.on_key,
+                    |de| visitor.visit_seq(de),
+                )
+            }
             ElementType::Object => {
                 let limit = header.payload_size;
+                let input = self.borrow_payload(header);
                 // Avoids infinite type inference recursion by using dynamic dispatch
                 let reader = (&mut self.reader as &mut dyn Read).take(limit);
-                let mut de = Deserializer { reader };
-                visitor.visit_map(&mut de)
+                Deserializer::with_scratch(
+                    reader,
+                    self.options,
+                    input,
+                    self.remaining.map(|_| limit),
+                    &mut self.scratch,
+                    &mut self.budget,
+                    &mut self.on_key,
+                    |de| visitor.visit_map(de),
+                )
             }
             ElementType::Text
             | ElementType::TextJ
             | ElementType::Text5
             | ElementType::TextRaw => {
-                visitor.visit_string(self.read_string(header)?)
+                self.visit_string_element(header, visitor)
             }
-            ElementType::Reserved13 | ElementType::Reserved14 => {
-                Err(Error::UnexpectedType(header.element_type))
+            ElementType::Reserved13 => {
+                Err(Error::unexpected(header.element_type))
             }
         }
     }
 }
 
-fn read_with_quotes(r: impl Read) -> impl Read {
-    b"\"".chain(r).chain(&b"\""[..])
+fn payload_utf8(payload: &[u8]) -> Result<&str> {
+    std::str::from_utf8(payload).map_err(|e| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e).into()
+    })
 }
 
-impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
-    type Error = Error;
+/// Read a single byte, or `None` at the end of the input.
+/// Unlike a bare `read`, this retries reads that were interrupted.
+fn read_byte(reader: &mut impl Read) -> std::io::Result<Option<u8>> {
+    let mut buf = [0u8; 1];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(buf[0])),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
 
-    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: Visitor<'de>,
-    {
-        let header = self.read_header()?;
-        self.deserialize_any_with_header(header, visitor)
+/// Fill `buf` as much as possible, stopping early only at the end of the input.
+/// Returns the number of bytes read.
+pub(crate) fn read_up_to(
+    reader: &mut impl Read,
+    buf: &mut [u8],
+) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// The exact value of an integer element.
+enum Integer {
+    /// Any integer that fits in an `i128`
+    Signed(i128),
+    /// A positive integer larger than `i128::MAX`
+    Unsigned(u128),
+    /// An integer too large for 128 bits, as written in the blob
+    Text(String),
+}
+
+/// Reject numeric payloads with leading or trailing whitespace, which JSON
+/// parsers would otherwise silently accept, and `Int` and `Float` payloads
+/// with a leading `+`, which only json5 allows.
+fn check_numeric_payload(
+    element_type: ElementType,
+    payload: &[u8],
+) -> Result<()> {
+    let is_ws = |b: &u8| b.is_ascii_whitespace();
+    if payload.first().map_or(false, is_ws)
+        || payload.last().map_or(false, is_ws)
+    {
+        return Err(Error::Message(
+            "numeric payload contains whitespace".into(),
+        ));
+    }
+    if payload.first() == Some(&b'+') {
+        let json5_type = match element_type {
+            ElementType::Int => "Int5",
+            ElementType::Float => "Float5",
+            _ => return Ok(()),
+        };
+        return Err(Error::Message(format!(
+            "{element_type:?} payload {:?} starts with '+', which is not \
+             valid json: the element should be tagged {json5_type}",
+            String::from_utf8_lossy(payload)
+        )));
+    }
+    Ok(())
+}
+
+fn read_with_quotes(r: impl Read) -> impl Read {
+    b"\"".chain(r).chain(&b"\""[..])
+}
+
+impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<'de, R> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let header = self.read_header()?;
+        self.deserialize_any_with_header(header, visitor)
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
@@ -387,6 +1285,20 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
         visitor.visit_i64(self.read_integer(header)?)
     }
 
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let header = self.read_header()?;
+        let i = match self.read_exact_integer(header)? {
+            Integer::Signed(i) => Some(i),
+            Integer::Unsigned(_) | Integer::Text(_) => None,
+        };
+        visitor.visit_i128(i.ok_or_else(|| {
+            Error::Message("integer out of range for i128".into())
+        })?)
+    }
+
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
@@ -419,6 +1331,21 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
         visitor.visit_u64(self.read_integer(header)?)
     }
 
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let header = self.read_header()?;
+        let u = match self.read_exact_integer(header)? {
+            Integer::Signed(i) => u128::try_from(i).ok(),
+            Integer::Unsigned(u) => Some(u),
+            Integer::Text(_) => None,
+        };
+        visitor.visit_u128(u.ok_or_else(|| {
+            Error::Message("integer out of range for u128".into())
+        })?)
+    }
+
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
@@ -427,8 +1354,9 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
         if header.element_type == ElementType::Null {
             visitor.visit_none()
         } else {
-            let mut deser = self.with_header(header);
-            visitor.visit_some(&mut deser)
+            // "unread" the header, for the inner value
+            self.peeked = Some(header);
+            visitor.visit_some(self)
         }
     }
 
@@ -454,12 +1382,43 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
 
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if name == crate::number::NUMBER_TOKEN {
+            return self.deserialize_number(visitor);
+        }
+        if name == crate::number::RAW_NUMBER_TOKEN {
+            return self.deserialize_raw_number(visitor);
+        }
+        if name == crate::raw::RAW_ELEMENT_TOKEN {
+            let header = self.read_header()?;
+            if matches!(
+                header.element_type,
+                ElementType::Array | ElementType::Object
+            ) {
+                // the children are copied without being read one by one
+                self.spend_budget(header.payload_size)?;
+            }
+            let mut encoded = Vec::new();
+            write_minimal_header(
+                &mut encoded,
+                header.element_type,
+                header.payload_size,
+            );
+            let header_len = encoded.len();
+            self.reader_with_limit(header).read_to_end(&mut encoded)?;
+            if (encoded.len() - header_len) as u64 != header.payload_size {
+                return Err(std::io::Error::from(
+                    std::io::ErrorKind::UnexpectedEof,
+                )
+                .into());
+            }
+            return visitor.visit_byte_buf(encoded);
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -468,28 +1427,70 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
         V: Visitor<'de>,
     {
         let head = self.read_header()?;
-        let reader = self.reader_with_limit(head);
-        let mut seq_deser = Deserializer { reader };
-        visitor.visit_seq(&mut seq_deser)
-    }
-
-    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+        let input = self.borrow_payload(head);
+        let reader = (&mut self.reader).take(head.payload_size);
+        Deserializer::with_scratch(
+            reader,
+            self.options,
+            input,
+            self.remaining.map(|_| head.payload_size),
+            &mut self.scratch,
+            &mut self.budget,
+            &mut self.on_key,
+            |seq_deser| visitor.visit_seq(seq_deser),
+        )
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        let head = self.read_header()?;
+        let input = self.borrow_payload(head);
+        let reader = (&mut self.reader).take(head.payload_size);
+        Deserializer::with_scratch(
+            reader,
+            self.options,
+            input,
+            self.remaining.map(|_| head.payload_size),
+            &mut self.scratch,
+            &mut self.budget,
+            &mut self.on_key,
+            |seq_deser| {
+                let value = visitor.visit_seq(TupleAccess {
+                    de: &mut *seq_deser,
+                    len,
+                    found: 0,
+                })?;
+                // Count the extra elements, to report them
+                let mut found = len;
+                loop {
+                    match seq_deser.read_header() {
+                        Ok(header) => seq_deser.drop_payload(header)?,
+                        Err(Error::Empty) => break,
+                        Err(e) => return Err(e),
+                    };
+                    found += 1;
+                }
+                if found == len {
+                    Ok(value)
+                } else {
+                    Err(tuple_length_error(len, found))
+                }
+            },
+        )
     }
 
     fn deserialize_tuple_struct<V>(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_tuple(len, visitor)
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
@@ -497,9 +1498,18 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
         V: Visitor<'de>,
     {
         let head = self.read_header()?;
-        let reader = self.reader_with_limit(head);
-        let mut seq_deser = Deserializer { reader };
-        visitor.visit_map(&mut seq_deser)
+        let input = self.borrow_payload(head);
+        let reader = (&mut self.reader).take(head.payload_size);
+        Deserializer::with_scratch(
+            reader,
+            self.options,
+            input,
+            self.remaining.map(|_| head.payload_size),
+            &mut self.scratch,
+            &mut self.budget,
+            &mut self.on_key,
+            |map_deser| visitor.visit_map(map_deser),
+        )
     }
 
     fn deserialize_struct<V>(
@@ -532,17 +1542,17 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
                 let s = self.read_string(header)?;
                 visitor.visit_enum(s.into_deserializer())
             }
+            // {"variant": value}, or ["variant", value] with `enum_as_tuple`
             ElementType::Object => {
-                let reader = self.reader_with_limit(header);
-                let mut de = Deserializer { reader };
-                let r = visitor.visit_enum(&mut de);
-                if de.reader.read(&mut [0])? == 0 {
-                    r
-                } else {
-                    Err(Error::TrailingCharacters)
-                }
+                self.deserialize_enum_container(header, visitor)
+            }
+            ElementType::Array if self.options.enum_as_tuple => {
+                self.deserialize_enum_container(header, visitor)
+            }
+            other if self.options.enum_as_tuple => {
+                Err(Error::expected("string, object or array", other))
             }
-            other => Err(Error::UnexpectedType(other)),
+            other => Err(Error::expected("string or object", other)),
         }
     }
 
@@ -567,7 +1577,7 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
         V: Visitor<'de>,
     {
         let header = self.read_header()?;
-        visitor.visit_f32(self.read_float(header)?)
+        visitor.visit_f32(self.read_finite_float(header)?)
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
@@ -575,7 +1585,7 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
         V: Visitor<'de>,
     {
         let header = self.read_header()?;
-        visitor.visit_f64(self.read_float(header)?)
+        visitor.visit_f64(self.read_finite_float(header)?)
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
@@ -583,20 +1593,20 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
         V: Visitor<'de>,
     {
         let header = self.read_header()?;
-        let s = self.read_string(header)?;
-        if s.len() != 1 {
-            return Err(Error::Message(
-                "invalid string length for char".into(),
-            ));
+        let s = self.read_str(header)?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(Error::Message("invalid string length for char".into())),
         }
-        visitor.visit_char(s.chars().next().unwrap())
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        // Borrowed string deserialization is not supported
+        // Strings without escapes are borrowed from byte slices, and visited
+        // without allocating otherwise
         self.deserialize_string(visitor)
     }
 
@@ -605,25 +1615,44 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
         V: Visitor<'de>,
     {
         let header = self.read_header()?;
-        visitor.visit_string(self.read_string(header)?)
+        self.visit_string_element(header, visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.options.human_readable
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_byte_buf(visitor)
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        let encoding = self.options.bytes_encoding;
+        if encoding != BytesEncoding::Array {
+            let header = self.peek_header()?;
+            if matches!(
+                header.element_type,
+                ElementType::Text
+                    | ElementType::TextJ
+                    | ElementType::Text5
+                    | ElementType::TextRaw
+            ) {
+                let header = self.read_header()?;
+                let text = self.read_string(header)?;
+                return visitor.visit_byte_buf(encoding.decode(&text)?);
+            }
+        }
         self.deserialize_seq(visitor)
     }
 }
 
-impl<'de, R: Read> de::SeqAccess<'de> for &mut Deserializer<R> {
+impl<'de, R: Read> de::SeqAccess<'de> for &mut Deserializer<'de, R> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -638,26 +1667,165 @@ impl<'de, R: Read> de::SeqAccess<'de> for &mut Deserializer<R> {
     }
 }
 
-impl<'de, R: Read> de::MapAccess<'de> for &mut Deserializer<R> {
+/// Gives the elements of an array to a tuple visitor,
+/// failing when the array has fewer elements than the tuple.
+struct TupleAccess<'a, 'de, R: Read> {
+    de: &'a mut Deserializer<'de, R>,
+    len: usize,
+    found: usize,
+}
+
+impl<'de, R: Read> de::SeqAccess<'de> for TupleAccess<'_, 'de, R> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        if self.found == self.len {
+            return Ok(None);
+        }
+        match seed.deserialize(&mut *self.de) {
+            Ok(v) => {
+                self.found += 1;
+                Ok(Some(v))
+            }
+            Err(Error::Empty) => Err(tuple_length_error(self.len, self.found)),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.found)
+    }
+}
+
+fn tuple_length_error(len: usize, found: usize) -> Error {
+    de::Error::invalid_length(
+        found,
+        &format!("an array of {len} elements").as_str(),
+    )
+}
+
+impl<'de, R: Read> Deserializer<'de, R> {
+    /// Deserialize the next object key like [`de::MapAccess::next_key_seed`]
+    /// does, and also check it with [`Options::reject_duplicate_keys`] and
+    /// report it to the [`Deserializer::on_key`] hook. The payload of the
+    /// key is looked at on the side: borrowed from the input when it is a
+    /// byte slice, and copied otherwise.
+    fn next_recorded_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let header = self.read_header()?;
+        if let Some(payload) = self.borrow_payload(header) {
+            self.record_key(header, payload)?;
+            self.peeked = Some(header);
+            return seed
+                .deserialize(MapKeyDeserializer(self))
+                .map(Some)
+                .map_err(|e| key_error(e, header));
+        }
+        let payload_size = usize::try_from(header.payload_size)
+            .map_err(Error::IntConversion)?;
+        if self.read_payload_to_scratch(header)? != payload_size {
+            return Err(std::io::Error::from(
+                std::io::ErrorKind::UnexpectedEof,
+            )
+            .into());
+        }
+        let payload = std::mem::take(&mut self.scratch);
+        let result = self.record_key(header, &payload).and_then(|()| {
+            let mut de = Deserializer::new(&payload[..], self.options);
+            de.peeked = Some(header);
+            seed.deserialize(MapKeyDeserializer(&mut de))
+                .map_err(|e| key_error(e, header))
+        });
+        self.scratch = payload;
+        result.map(Some)
+    }
+
+    /// Report a key to the hook, and fail if it was already seen with
+    /// [`Options::reject_duplicate_keys`]. Only string and numeric keys have
+    /// a text to record; others are left to the deserialized type.
+    fn record_key(&mut self, header: Header, payload: &[u8]) -> Result<()> {
+        let key = match header.element_type {
+            ElementType::Text
+            | ElementType::TextRaw
+            | ElementType::Int
+            | ElementType::Int5
+            | ElementType::Float
+            | ElementType::Float5 => payload_utf8(payload)?.to_owned(),
+            ElementType::TextJ | ElementType::Text5 => {
+                Deserializer::new(payload, self.options).read_string(header)?
+            }
+            _ => return Ok(()),
+        };
+        if let Some(on_key) = &mut self.on_key {
+            on_key(&key);
+        }
+        if self.options.reject_duplicate_keys
+            && !self.seen_keys.insert(key.clone())
+        {
+            return Err(Error::DuplicateKey(key));
+        }
+        self.last_key = Some(key);
+        Ok(())
+    }
+}
+
+impl<'de, R: Read> de::MapAccess<'de> for &mut Deserializer<'de, R> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
     where
         K: de::DeserializeSeed<'de>,
     {
-        self.next_element_seed(seed)
+        let header = match self.peek_header() {
+            Ok(header) => header,
+            Err(Error::Empty) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if self.options.reject_duplicate_keys || self.on_key.is_some() {
+            return self.next_recorded_key_seed(seed);
+        }
+        seed.deserialize(MapKeyDeserializer(&mut **self))
+            .map(Some)
+            .map_err(|e| key_error(e, header))
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
     where
         V: de::DeserializeSeed<'de>,
     {
-        self.next_element_seed(seed)
-            .and_then(|opt| opt.ok_or(Error::Empty))
+        // an object with an odd number of elements ends with a lone key
+        self.next_element_seed(seed).and_then(|opt| {
+            opt.ok_or_else(|| Error::KeyWithoutValue(self.last_key.take()))
+        })
     }
 }
 
-impl<'de, R: Read> de::EnumAccess<'de> for &mut Deserializer<R> {
+/// Point out an object key that is not a string, rather than the type
+/// mismatch it causes, which would not tell that a key was being read.
+fn key_error(error: Error, key: Header) -> Error {
+    let is_text = matches!(
+        key.element_type,
+        ElementType::Text
+            | ElementType::TextJ
+            | ElementType::Text5
+            | ElementType::TextRaw
+    );
+    match error {
+        Error::UnexpectedType { found, .. }
+            if found == key.element_type && !is_text =>
+        {
+            Error::NonStringKey(found)
+        }
+        e => e,
+    }
+}
+
+impl<'de, R: Read> de::EnumAccess<'de> for &mut Deserializer<'de, R> {
     type Error = Error;
     type Variant = Self;
 
@@ -670,7 +1838,7 @@ impl<'de, R: Read> de::EnumAccess<'de> for &mut Deserializer<R> {
     }
 }
 
-impl<'de, R: Read> de::VariantAccess<'de> for &mut Deserializer<R> {
+impl<'de, R: Read> de::VariantAccess<'de> for &mut Deserializer<'de, R> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
@@ -703,11 +1871,24 @@ impl<'de, R: Read> de::VariantAccess<'de> for &mut Deserializer<R> {
     }
 }
 #[cfg(test)]
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_possible_wrap,
+    clippy::cast_sign_loss,
+    clippy::float_cmp,
+    clippy::items_after_statements,
+    clippy::manual_string_new,
+    clippy::redundant_closure_for_method_calls,
+    clippy::uninlined_format_args,
+    clippy::unreadable_literal,
+    clippy::zero_sized_map_values
+)]
 mod tests {
     use super::*;
 
     fn assert_header(bytes: &[u8], expected: Header) {
-        let mut de = Deserializer::from_bytes(bytes);
+        // not from_bytes, which would reject the missing payloads
+        let mut de = Deserializer::new(bytes, Options::default());
         let header = de.read_header().unwrap();
         assert_eq!(header, expected);
     }
@@ -758,6 +1939,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_drop_payload_returns_dropped_size() {
+        // "hello" followed by true
+        let mut de = Deserializer::from_bytes(b"\x57hello\x01");
+        let header = de.read_header().unwrap();
+        assert_eq!(de.drop_payload(header).unwrap(), header.payload_size);
+        assert_eq!(header.payload_size, 5);
+        assert_eq!(de.reader, b"\x01");
+    }
+
+    #[test]
+    fn test_peek_is_null() {
+        let mut de = Deserializer::from_bytes(b"\x00");
+        assert!(de.peek_is_null().unwrap());
+        assert!(de.peek_is_null().unwrap(), "peeking does not consume");
+        <()>::deserialize(&mut de).unwrap();
+        assert!(matches!(de.peek_is_null(), Err(Error::Empty)));
+
+        let mut de = Deserializer::from_bytes(b"\x2342");
+        assert!(!de.peek_is_null().unwrap());
+        assert_eq!(u8::deserialize(&mut de).unwrap(), 42);
+    }
+
     fn assert_all_int_types_eq(encoded: &[u8], expected: i64) {
         // unsigned
         assert_eq!(
@@ -796,12 +2000,12 @@ mod tests {
     #[test]
     fn test_decoding_large_int() {
         assert_eq!(
-            from_slice::<u64>(b"\xc3\xf418446744073709551615").unwrap(),
+            from_slice::<u64>(b"\xc3\x1418446744073709551615").unwrap(),
             18446744073709551615
         );
         // large negative i64
         assert_eq!(
-            from_slice::<i64>(b"\xc3\xf5-9223372036854775808").unwrap(),
+            from_slice::<i64>(b"\xc3\x14-9223372036854775808").unwrap(),
             -9223372036854775808
         );
     }
@@ -849,6 +2053,35 @@ mod tests {
         assert_eq!(from_slice::<String>(b"\x49\\x0A").unwrap(), "\n");
     }
 
+    #[test]
+    #[cfg(feature = "serde_json5")]
+    fn test_float5_forms() {
+        // the forms that SQLite stores as Float5, and other JSON5 floats
+        for (blob, expected) in [
+            (&b"\x26.5"[..], 0.5),
+            (b"\x265.", 5.0),
+            (b"\x66-.5e-2", -0.005),
+            (b"\x46+1.5", 1.5),
+            (b"\x66+.5E+1", 5.0),
+        ] {
+            assert_eq!(from_slice::<f64>(blob).unwrap(), expected, "{blob:?}");
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "serde_json5"))]
+    fn test_float5_without_json5() {
+        // payloads that are valid JSON are parsed as such
+        assert_eq!(from_slice::<f64>(b"\x361.5").unwrap(), 1.5);
+        assert_eq!(from_slice::<f64>(b"\x56-1e+5").unwrap(), -1e5);
+        for blob in [&b"\x26.5"[..], b"\x265.", b"\x46+1.5"] {
+            assert!(matches!(
+                from_slice::<f64>(blob),
+                Err(Error::Json5Error(_))
+            ));
+        }
+    }
+
     #[test]
     fn test_tuple() {
         assert_eq!(
@@ -857,6 +2090,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_tuple_length_mismatch() {
+        let err = from_slice::<(u8, u8, u8)>(b"\x8b\x131\x132\x133\x134")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid length 4, expected an array of 3 elements"
+        );
+        let err = from_slice::<(u8, u8, u8)>(b"\x4b\x131\x132").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid length 2, expected an array of 3 elements"
+        );
+    }
+
     #[test]
     fn test_tuple_struct() {
         #[derive(Debug, PartialEq, serde_derive::Deserialize)]
@@ -890,6 +2138,1000 @@ mod tests {
         assert_eq!(from_reader::<_, Vec<()>>(&b"\x0b"[..]).unwrap(), vec![]);
     }
 
+    /// A reader that returns at most one byte per call,
+    /// and is interrupted every other call
+    struct ChoppyReader<'a> {
+        data: &'a [u8],
+        interrupt: bool,
+    }
+
+    impl Read for ChoppyReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+            let n = buf.len().min(self.data.len()).min(1);
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_choppy_reader() {
+        #[derive(
+            Debug, PartialEq, serde_derive::Deserialize, serde_derive::Serialize,
+        )]
+        enum E {
+            X(String),
+        }
+        #[derive(
+            Debug, PartialEq, serde_derive::Deserialize, serde_derive::Serialize,
+        )]
+        struct Test {
+            id: u64,
+            names: Vec<String>,
+            e: E,
+        }
+        let expected = Test {
+            id: 123_456_789_012,
+            names: vec!["b".into(), "c".into()],
+            e: E::X("xy".into()),
+        };
+        let bytes = crate::to_vec(&expected).unwrap();
+        let reader = ChoppyReader {
+            data: &bytes,
+            interrupt: false,
+        };
+        assert_eq!(from_reader::<_, Test>(reader).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_number() {
+        use crate::Number;
+        assert_eq!(from_slice::<Number>(b"\x2342").unwrap(), Number::Int(42));
+        assert_eq!(from_slice::<Number>(b"\x33-42").unwrap(), Number::Int(-42));
+        assert_eq!(from_slice::<Number>(b"\x440x2A").unwrap(), Number::Int(42));
+        assert_eq!(
+            from_slice::<Number>(b"\x351.5").unwrap(),
+            Number::Float(1.5)
+        );
+        #[cfg(feature = "serde_json5")]
+        assert_eq!(
+            from_slice::<Number>(b"\x26.5").unwrap(),
+            Number::Float(0.5)
+        );
+        let big = "1234567890123456789012345678901234567890";
+        let mut blob = vec![0xc3, 40];
+        blob.extend_from_slice(big.as_bytes());
+        assert_eq!(
+            from_slice::<Number>(&blob).unwrap(),
+            Number::Text(big.to_string())
+        );
+        let max = u128::MAX.to_string();
+        let mut blob = vec![0xc3, max.len() as u8];
+        blob.extend_from_slice(max.as_bytes());
+        assert_eq!(
+            from_slice::<Number>(&blob).unwrap(),
+            Number::UInt(u128::MAX)
+        );
+        assert!(matches!(
+            from_slice::<Number>(b"\x1aa"),
+            Err(Error::UnexpectedType {
+                found: ElementType::TextRaw,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_128_bit_integers_in_deserialize_any() {
+        // serde's derived untagged enums cannot buffer 128-bit integers,
+        // so this mirrors one with a visitor over deserialize_any
+        #[derive(Debug, PartialEq)]
+        enum Wide {
+            Small(i64),
+            Unsigned(u128),
+            Signed(i128),
+        }
+        impl<'de> Deserialize<'de> for Wide {
+            fn deserialize<D: de::Deserializer<'de>>(
+                d: D,
+            ) -> std::result::Result<Self, D::Error> {
+                struct WideVisitor;
+                impl Visitor<'_> for WideVisitor {
+                    type Value = Wide;
+                    fn expecting(
+                        &self,
+                        f: &mut std::fmt::Formatter,
+                    ) -> std::fmt::Result {
+                        f.write_str("an integer")
+                    }
+                    fn visit_i64<E>(
+                        self,
+                        v: i64,
+                    ) -> std::result::Result<Wide, E> {
+                        Ok(Wide::Small(v))
+                    }
+                    fn visit_u64<E>(
+                        self,
+                        v: u64,
+                    ) -> std::result::Result<Wide, E> {
+                        Ok(Wide::Small(v as i64))
+                    }
+                    fn visit_u128<E>(
+                        self,
+                        v: u128,
+                    ) -> std::result::Result<Wide, E> {
+                        Ok(Wide::Unsigned(v))
+                    }
+                    fn visit_i128<E>(
+                        self,
+                        v: i128,
+                    ) -> std::result::Result<Wide, E> {
+                        Ok(Wide::Signed(v))
+                    }
+                }
+                d.deserialize_any(WideVisitor)
+            }
+        }
+        let int_blob = |text: String| {
+            let mut blob = vec![0xc3, text.len() as u8];
+            blob.extend_from_slice(text.as_bytes());
+            blob
+        };
+        let max = int_blob(u128::MAX.to_string());
+        assert_eq!(
+            from_slice::<Wide>(&max).unwrap(),
+            Wide::Unsigned(u128::MAX)
+        );
+        assert_eq!(from_slice::<u128>(&max).unwrap(), u128::MAX);
+        let min = int_blob(i128::MIN.to_string());
+        assert_eq!(from_slice::<Wide>(&min).unwrap(), Wide::Signed(i128::MIN));
+        assert_eq!(from_slice::<i128>(&min).unwrap(), i128::MIN);
+        assert_eq!(from_slice::<Wide>(b"\x33-42").unwrap(), Wide::Small(-42));
+    }
+
+    #[test]
+    fn test_raw_number_preserves_float_text() {
+        use crate::RawNumber;
+        let blob = b"\x451.50";
+        let raw: RawNumber = from_slice(blob).unwrap();
+        assert_eq!(raw.as_str(), "1.50");
+        assert_eq!(crate::to_vec(&raw).unwrap(), blob);
+        for (blob, text) in [
+            (&b"\x2342"[..], "42"),
+            (b"\x440x2A", "0x2A"),
+            (b"\x56-.5e1", "-.5e1"),
+        ] {
+            let raw: RawNumber = from_slice(blob).unwrap();
+            assert_eq!(raw.as_str(), text);
+            assert_eq!(crate::to_vec(&raw).unwrap(), blob);
+        }
+    }
+
+    #[test]
+    fn test_raw_number_rejects_invalid_text() {
+        use crate::RawNumber;
+        use serde::de::value::{Error as ValueError, StrDeserializer};
+        use serde::de::IntoDeserializer;
+        for text in ["+1", ".5", "5.", "-Infinity", "NaN", "0x1f", "1e+3"] {
+            let de: StrDeserializer<ValueError> = text.into_deserializer();
+            let raw = RawNumber::deserialize(de).unwrap();
+            assert_eq!(crate::to_vec(&raw).unwrap()[1..], *text.as_bytes());
+        }
+        for text in ["abc", "", "-", ".", "01", "1e", "0x", "1.5.", "+-1"] {
+            let de: StrDeserializer<ValueError> = text.into_deserializer();
+            assert!(RawNumber::deserialize(de).is_err(), "{text}");
+        }
+        // a numeric element whose text is not a number
+        assert!(from_slice::<RawNumber>(b"\x36abc").is_err());
+        let infinity: RawNumber =
+            from_slice(b"\x8f\x00\x00\x00\x00\x00\x00\xf0\x7f").unwrap();
+        assert_eq!(infinity.as_str(), "Infinity");
+    }
+
+    #[test]
+    fn test_require_minimal_headers() {
+        let encodings_of_1: [&[u8]; 5] = [
+            b"\x131",
+            b"\xc3\x01\x31",
+            b"\xd3\x00\x01\x31",
+            b"\xe3\x00\x00\x00\x01\x31",
+            b"\xf3\x00\x00\x00\x00\x00\x00\x00\x01\x31",
+        ];
+        let strict = Options {
+            require_minimal_headers: true,
+            ..Options::default()
+        };
+        for (i, blob) in encodings_of_1.iter().enumerate() {
+            assert_eq!(from_slice::<u8>(blob).unwrap(), 1);
+            let strict_result = from_slice_with_options::<u8>(blob, strict);
+            if i == 0 {
+                assert_eq!(strict_result.unwrap(), 1);
+            } else {
+                assert!(matches!(
+                    strict_result,
+                    Err(Error::NonMinimalHeader { minimal_len: 1, .. })
+                ));
+            }
+        }
+        // the header of an optional value is checked only once
+        assert_eq!(
+            from_slice_with_options::<Option<u8>>(b"\x131", strict).unwrap(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_raw_element() {
+        use crate::RawElement;
+        let raw = RawElement {
+            element_type: ElementType::Text,
+            payload: b"hello".to_vec(),
+        };
+        let blob = crate::to_vec(&raw).unwrap();
+        assert_eq!(blob, b"\x57hello");
+        assert_eq!(from_slice::<RawElement>(&blob).unwrap(), raw);
+        assert_eq!(from_slice::<String>(&blob).unwrap(), "hello");
+        // the bytes are copied as they are, whatever the bytes encoding
+        for bytes_encoding in [
+            crate::BytesEncoding::Array,
+            crate::BytesEncoding::Base64,
+            crate::BytesEncoding::Hex,
+        ] {
+            let options = Options {
+                bytes_encoding,
+                ..Options::default()
+            };
+            assert_eq!(
+                crate::to_vec_with_options(&raw, options).unwrap(),
+                blob
+            );
+            assert_eq!(
+                from_slice_with_options::<RawElement>(&blob, options).unwrap(),
+                raw
+            );
+        }
+        // nested in a container, with a non-minimal header
+        let elements: Vec<RawElement> =
+            from_slice(b"\x4b\x13\x31\xc7\x00").unwrap();
+        assert_eq!(
+            elements,
+            [
+                RawElement {
+                    element_type: ElementType::Int,
+                    payload: b"1".to_vec()
+                },
+                RawElement {
+                    element_type: ElementType::Text,
+                    payload: vec![]
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_raw_element_field_with_bytes_encoding() {
+        use crate::{BytesEncoding, RawElement};
+        #[derive(
+            Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+        )]
+        struct Envelope {
+            kind: u8,
+            body: RawElement,
+        }
+        let envelope = Envelope {
+            kind: 1,
+            body: RawElement {
+                element_type: ElementType::Array,
+                payload: b"\x131\x01".to_vec(),
+            },
+        };
+        // {"kind": 1, "body": [1, true]}
+        let blob = b"\xcc\x10\x47kind\x131\x47body\x3b\x131\x01";
+        for bytes_encoding in [BytesEncoding::Hex, BytesEncoding::Base64] {
+            let options = Options {
+                bytes_encoding,
+                ..Options::default()
+            };
+            assert_eq!(
+                crate::to_vec_with_options(&envelope, options).unwrap(),
+                blob
+            );
+            assert_eq!(
+                from_slice_with_options::<Envelope>(blob, options).unwrap(),
+                envelope
+            );
+        }
+    }
+
+    #[test]
+    fn test_raw_jsonb() {
+        use crate::RawJsonb;
+        let values: Vec<RawJsonb> =
+            from_slice(b"\x5b\x13\x31\x2b\x17a").unwrap();
+        assert_eq!(values[0].as_bytes(), b"\x131");
+        assert_eq!(values[1].as_bytes(), b"\x2b\x17a");
+        assert_eq!(crate::to_vec(&values).unwrap(), b"\x5b\x13\x31\x2b\x17a");
+        for bytes_encoding in [
+            crate::BytesEncoding::Array,
+            crate::BytesEncoding::Base64,
+            crate::BytesEncoding::Hex,
+        ] {
+            let options = Options {
+                bytes_encoding,
+                ..Options::default()
+            };
+            assert_eq!(
+                crate::to_vec_with_options(&values, options).unwrap(),
+                b"\x5b\x13\x31\x2b\x17a"
+            );
+        }
+        assert!(RawJsonb::try_from(b"\x131\x00".to_vec()).is_err());
+        assert!(RawJsonb::try_from(b"\x23".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_numbers_as_strings() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Test {
+            int: String,
+            float: String,
+        }
+        // {"int": 5, "float": 1.5}
+        let blob = b"\xcc\x10\x37int\x135\x57float\x351.5";
+        assert!(matches!(
+            from_slice::<Test>(blob),
+            Err(Error::UnexpectedType {
+                found: ElementType::Int,
+                ..
+            })
+        ));
+        let options = Options {
+            numbers_as_strings: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            from_slice_with_options::<Test>(blob, options).unwrap(),
+            Test {
+                int: "5".into(),
+                float: "1.5".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_ignored_nested_object_is_skipped_whole() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Test {
+            a: u8,
+            c: bool,
+        }
+        // Nest a reserved element, which cannot be decoded, 10000 objects
+        // deep: skipping it by recursing would fail or overflow the stack.
+        let mut nested = vec![u8::from(ElementType::Reserved13)];
+        for _ in 0..10_000 {
+            let mut object = Vec::new();
+            let payload_size = 2 + nested.len() as u64;
+            crate::write_minimal_header(
+                &mut object,
+                ElementType::Object,
+                payload_size,
+            );
+            object.extend_from_slice(b"\x17k");
+            object.extend_from_slice(&nested);
+            nested = object;
+        }
+        let mut entries = b"\x17a\x131\x17b".to_vec();
+        entries.extend_from_slice(&nested);
+        entries.extend_from_slice(b"\x17c\x01");
+        let mut blob = Vec::new();
+        crate::write_minimal_header(
+            &mut blob,
+            ElementType::Object,
+            entries.len() as u64,
+        );
+        blob.extend_from_slice(&entries);
+
+        assert_eq!(from_slice::<Test>(&blob).unwrap(), Test { a: 1, c: true });
+        // the innermost element does not decode when it is not ignored
+        assert!(from_slice::<()>(&[u8::from(ElementType::Reserved13)]).is_err());
+    }
+
+    #[test]
+    fn test_from_reader_with_remainder() {
+        // ["a", 1] followed by framed data that is not JSONB
+        let input = b"\x4b\x17a\x131\xff\x00rest";
+        let (value, mut rest): ((String, u8), _) =
+            from_reader_with_remainder(std::io::Cursor::new(&input[..]))
+                .unwrap();
+        assert_eq!(value, ("a".to_string(), 1));
+        let mut remainder = Vec::new();
+        rest.read_to_end(&mut remainder).unwrap();
+        assert_eq!(remainder, b"\xff\x00rest");
+        assert!(matches!(
+            from_reader::<_, (String, u8)>(&input[..]),
+            Err(Error::TrailingCharacters)
+        ));
+    }
+
+    #[test]
+    fn test_object_key_without_value() {
+        use std::collections::HashMap;
+        // {"a": 1, "b"}
+        let blob = b"\x6c\x17a\x131\x17b";
+        let err = from_slice::<HashMap<String, u8>>(blob).unwrap_err();
+        assert!(matches!(err, Error::KeyWithoutValue(None)), "{err:?}");
+        assert_eq!(err.to_string(), "object has a key with no value");
+        // the key is known when keys are tracked
+        let options = Options {
+            reject_duplicate_keys: true,
+            ..Options::default()
+        };
+        let err = from_slice_with_options::<HashMap<String, u8>>(blob, options)
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::KeyWithoutValue(Some(k)) if k == "b"),
+            "{err:?}"
+        );
+        assert_eq!(err.to_string(), "object has a key with no value: \"b\"");
+    }
+
+    #[test]
+    fn test_unexpected_type_messages() {
+        #[derive(Debug, serde_derive::Deserialize)]
+        enum Test {
+            A,
+        }
+        let message = |err: Error| err.to_string();
+        assert_eq!(
+            message(from_slice::<i32>(b"\x17a").unwrap_err()),
+            "expected integer, found string"
+        );
+        assert_eq!(
+            message(from_slice::<bool>(b"\x131").unwrap_err()),
+            "expected boolean, found integer"
+        );
+        assert_eq!(
+            message(from_slice::<f64>(b"\x28\\n").unwrap_err()),
+            "expected number, found string"
+        );
+        assert_eq!(
+            message(from_slice::<String>(b"\x0b").unwrap_err()),
+            "expected string, found array"
+        );
+        assert_eq!(
+            message(from_slice::<()>(b"\x02").unwrap_err()),
+            "expected null, found boolean"
+        );
+        assert_eq!(
+            message(from_slice::<Test>(b"\x351.5").unwrap_err()),
+            "expected string or object, found float"
+        );
+        let err = read_and_validate_header(&b"\x0d"[..], true).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::UnexpectedType {
+                found: ElementType::Reserved13,
+                expected: None
+            }
+        ));
+        assert_eq!(message(err), "unexpected reserved element type 13");
+    }
+
+    #[test]
+    fn test_object_value_in_key_slot() {
+        use std::collections::HashMap;
+        #[derive(Debug, serde_derive::Deserialize)]
+        struct Test {
+            #[allow(dead_code)]
+            a: String,
+        }
+        // an object holding 1, "a": its value comes before its key
+        let blob = b"\x4c\x131\x17a";
+        let err = from_slice::<HashMap<String, String>>(blob).unwrap_err();
+        assert!(
+            matches!(err, Error::NonStringKey(ElementType::Int)),
+            "{err:?}"
+        );
+        assert_eq!(
+            err.to_string(),
+            "expected a string object key, found integer: \
+             the object may have a value where a key should be"
+        );
+        let err = from_slice::<Test>(blob).unwrap_err();
+        assert!(
+            matches!(err, Error::NonStringKey(ElementType::Int)),
+            "{err:?}"
+        );
+        let options = Options {
+            reject_duplicate_keys: true,
+            ..Options::default()
+        };
+        let err =
+            from_slice_with_options::<HashMap<String, String>>(blob, options)
+                .unwrap_err();
+        assert!(
+            matches!(err, Error::NonStringKey(ElementType::Int)),
+            "{err:?}"
+        );
+        // maps with integer keys, as written by to_vec, still read them
+        let map: HashMap<u8, String> = from_slice(blob).unwrap();
+        assert_eq!(map, HashMap::from([(1, "a".to_string())]));
+        // a string key in a map with integer keys is a mere type mismatch
+        assert!(matches!(
+            from_slice::<HashMap<u8, u8>>(b"\x4c\x17a\x131"),
+            Err(Error::UnexpectedType {
+                found: ElementType::Text,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_tracked_keys_are_decoded_as_usual() {
+        use std::collections::HashMap;
+        let options = Options {
+            reject_duplicate_keys: true,
+            ..Options::default()
+        };
+        // {1: "a"}, with an integer key as written by to_vec
+        let blob = b"\x4c\x131\x17a";
+        let map: HashMap<u8, String> =
+            from_slice_with_options(blob, options).unwrap();
+        assert_eq!(map, HashMap::from([(1, "a".to_string())]));
+        // {"true": 1, "false": 0}, with stringified keys
+        let blob = b"\xcc\x0f\x47true\x131\x57false\x130";
+        let expected = HashMap::from([(true, 1), (false, 0)]);
+        assert_eq!(
+            from_slice_with_options::<HashMap<bool, u8>>(blob, options)
+                .unwrap(),
+            expected
+        );
+        assert_eq!(
+            from_reader_with_options::<_, HashMap<bool, u8>>(
+                &blob[..],
+                options
+            )
+            .unwrap(),
+            expected
+        );
+        // {"1": 1, 1: 2} repeats the key 1, whatever its type
+        let blob = b"\x8c\x171\x131\x131\x132";
+        assert!(matches!(
+            from_slice_with_options::<HashMap<u8, u8>>(blob, options),
+            Err(Error::DuplicateKey(k)) if k == "1"
+        ));
+        // escaped keys are compared by their text: {"a": 1, "\u0061": 2}
+        let blob = b"\xcc\x0d\x17a\x131\x68\\u0061\x132";
+        assert!(matches!(
+            from_slice_with_options::<HashMap<String, u8>>(blob, options),
+            Err(Error::DuplicateKey(k)) if k == "a"
+        ));
+        assert!(matches!(
+            from_reader_with_options::<_, HashMap<String, u8>>(
+                &blob[..],
+                options
+            ),
+            Err(Error::DuplicateKey(k)) if k == "a"
+        ));
+    }
+
+    #[test]
+    fn test_on_key() {
+        use std::collections::BTreeMap;
+
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Known {
+            id: u32,
+        }
+        // {"id": 7, "key 0": 0, ..., "key 999": 999}
+        let mut object: BTreeMap<String, u32> =
+            (0..1000).map(|i| (format!("key {i}"), i)).collect();
+        object.insert("id".to_string(), 7);
+        let blob = crate::to_vec(&object).unwrap();
+
+        // the hook may borrow local state
+        let mut count = 0;
+        let mut de = Deserializer::from_bytes(&blob);
+        de.on_key(|_| count += 1);
+        let known = Known::deserialize(&mut de).unwrap();
+        drop(de);
+        assert_eq!(known, Known { id: 7 });
+        assert_eq!(count, 1001);
+
+        // keys of nested objects are reported too, in order
+        // {"a": {"b": 1}, "c": [{"d": null}]}
+        let blob = b"\xcc\x0e\x17a\x4c\x17b\x131\x17c\x4b\x3c\x17d\x00";
+        let mut keys = Vec::new();
+        let mut de = Deserializer::from_bytes(blob);
+        de.on_key(|key| keys.push(key.to_owned()));
+        #[derive(serde_derive::Deserialize)]
+        struct Nested {
+            #[allow(dead_code)]
+            a: BTreeMap<String, u8>,
+            #[allow(dead_code)]
+            c: Vec<BTreeMap<String, ()>>,
+        }
+        Nested::deserialize(&mut de).unwrap();
+        drop(de);
+        assert_eq!(keys, ["a", "b", "c", "d"]);
+
+        // keys are decoded as without the hook: {"1": true}
+        let mut keys = Vec::new();
+        let mut de = Deserializer::from_bytes(b"\x3c\x171\x01");
+        de.on_key(|key| keys.push(key.to_owned()));
+        let map = BTreeMap::<u8, bool>::deserialize(&mut de).unwrap();
+        drop(de);
+        assert_eq!(map, BTreeMap::from([(1, true)]));
+        assert_eq!(keys, ["1"]);
+    }
+
+    #[test]
+    fn test_deny_unknown_fields() {
+        #[derive(Debug, serde_derive::Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Strict {
+            #[allow(dead_code)]
+            id: u32,
+        }
+        // {"id": 1}
+        assert!(from_slice::<Strict>(b"\x5c\x27id\x131").is_ok());
+        // {"id": 1, "x": 2}
+        let err =
+            from_slice::<Strict>(b"\x9c\x27id\x131\x17x\x132").unwrap_err();
+        assert_eq!(err.to_string(), "unknown field `x`, expected `id`");
+    }
+
+    #[test]
+    fn test_from_length_prefixed_reader() {
+        let mut stream = crate::to_length_prefixed_vec(&("a", 1)).unwrap();
+        stream.extend(crate::to_length_prefixed_vec(&true).unwrap());
+        let mut reader = &stream[..];
+        let first: (String, u8) =
+            from_length_prefixed_reader(&mut reader).unwrap();
+        assert_eq!(first, ("a".to_string(), 1));
+        assert!(from_length_prefixed_reader::<_, bool>(&mut reader).unwrap());
+        assert!(reader.is_empty());
+
+        // the prefix announces more bytes than there are
+        let truncated = b"\x00\x00\x00\x05\x131";
+        assert!(matches!(
+            from_length_prefixed_reader::<_, u8>(&truncated[..]),
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[test]
+    fn test_missing_optional_and_default_fields() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Test {
+            a: u8,
+            b: Option<String>,
+            #[serde(default)]
+            c: Vec<bool>,
+            #[serde(default = "default_d")]
+            d: u32,
+        }
+        fn default_d() -> u32 {
+            7
+        }
+        // {"a": 1}
+        assert_eq!(
+            from_slice::<Test>(b"\x4c\x17a\x131").unwrap(),
+            Test {
+                a: 1,
+                b: None,
+                c: vec![],
+                d: 7
+            }
+        );
+        // {} with a required field missing
+        let err = from_slice::<Test>(b"\x0c").unwrap_err();
+        assert_eq!(err.to_string(), "missing field `a`");
+    }
+
+    #[test]
+    fn test_numeric_payload_with_whitespace() {
+        for blob in [
+            &b"\x23 5"[..],
+            b"\x235 ",
+            b"\x24\n5",
+            b"\x45 1.5",
+            b"\xc3\x0d123456789012 ",
+        ] {
+            let err = from_slice::<f64>(blob).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "numeric payload contains whitespace",
+                "{blob:?}"
+            );
+            assert!(from_slice::<crate::Number>(blob).is_err(), "{blob:?}");
+        }
+        assert!(from_slice::<i64>(b"\x23 5").is_err());
+        assert_eq!(from_slice::<i64>(b"\x135").unwrap(), 5);
+    }
+
+    #[test]
+    fn test_int_payload_with_plus_sign() {
+        // SQLite drops the sign of +5, or keeps it in an Int5 element
+        let err = from_slice::<i32>(b"\x23+5").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Int payload \"+5\" starts with '+', which is not valid json: \
+             the element should be tagged Int5"
+        );
+        assert!(from_slice::<crate::Number>(b"\x23+5").is_err());
+        assert!(from_slice::<f64>(b"\x45+1.5")
+            .unwrap_err()
+            .to_string()
+            .ends_with("tagged Float5"));
+        assert_eq!(
+            from_slice::<crate::Number>(b"\x24+5").unwrap(),
+            crate::Number::Int(5)
+        );
+        assert_eq!(from_slice::<i32>(b"\x23-5").unwrap(), -5);
+    }
+
+    #[test]
+    fn test_from_slice_checked() {
+        let options = Options {
+            append_crc32: true,
+            ..Options::default()
+        };
+        let value = ("hello".to_string(), vec![1, 2, 3]);
+        let blob = crate::to_vec_with_options(&value, options).unwrap();
+        assert_eq!(blob.len(), crate::to_vec(&value).unwrap().len() + 4);
+        assert_eq!(
+            from_slice_checked::<(String, Vec<u8>)>(&blob).unwrap(),
+            value
+        );
+        assert!(matches!(
+            from_slice::<(String, Vec<u8>)>(&blob),
+            Err(Error::TrailingCharacters)
+        ));
+
+        // flip every bit of the value, one at a time
+        for i in 0..(blob.len() - 4) * 8 {
+            let mut corrupted = blob.clone();
+            corrupted[i / 8] ^= 1 << (i % 8);
+            assert!(
+                matches!(
+                    from_slice_checked::<(String, Vec<u8>)>(&corrupted),
+                    Err(Error::ChecksumMismatch { .. })
+                ),
+                "bit {i}"
+            );
+        }
+        assert!(from_slice_checked::<()>(b"\x00").is_err());
+    }
+
+    #[test]
+    fn test_nested_strings_share_scratch() {
+        #[derive(
+            Debug, PartialEq, serde_derive::Deserialize, serde_derive::Serialize,
+        )]
+        struct Inner {
+            text: String,
+            big: u128,
+            tags: (String, String),
+        }
+        #[derive(
+            Debug, PartialEq, serde_derive::Deserialize, serde_derive::Serialize,
+        )]
+        struct Outer {
+            before: String,
+            inner: Vec<Inner>,
+            after: String,
+        }
+        let value = Outer {
+            before: "a rather long string, read before the nested ones".into(),
+            inner: vec![
+                Inner {
+                    text: "x".into(),
+                    big: u128::MAX,
+                    tags: ("t".into(), "a second, longer tag".into()),
+                },
+                Inner {
+                    text: "".into(),
+                    big: 0,
+                    tags: ("".into(), "é".into()),
+                },
+            ],
+            after: "short".into(),
+        };
+        let blob = crate::to_vec(&value).unwrap();
+        assert_eq!(from_slice::<Outer>(&blob).unwrap(), value);
+
+        // escaped strings are unescaped outside of the scratch buffer
+        let blob = b"\xbb\x17a\x48a\\\"b\x17b\x18x";
+        let map: std::collections::BTreeMap<String, String> =
+            from_slice(blob).unwrap();
+        assert_eq!(map["a"], "a\"b");
+        assert_eq!(map["b"], "x");
+    }
+
+    #[test]
+    fn test_from_slice_in_place() {
+        let mut values: Vec<i64> = Vec::with_capacity(64);
+        let ptr = values.as_ptr();
+        for n in [3, 50, 0, 64, 1] {
+            let expected: Vec<i64> = (0..n).map(|i| i * 1000 - 7).collect();
+            let blob = crate::to_vec(&expected).unwrap();
+            from_slice_in_place(&blob, &mut values).unwrap();
+            assert_eq!(values, expected);
+            assert_eq!(values.capacity(), 64);
+            assert_eq!(values.as_ptr(), ptr);
+        }
+
+        // strings without escapes are copied into the existing buffer
+        let mut text = String::with_capacity(32);
+        let ptr = text.as_ptr();
+        from_slice_in_place(b"\x5ahello", &mut text).unwrap();
+        assert_eq!(text, "hello");
+        assert_eq!(text.as_ptr(), ptr);
+
+        assert!(matches!(
+            from_slice_in_place(b"\x2b\x131\x00", &mut values),
+            Err(Error::TrailingCharacters)
+        ));
+    }
+
+    #[test]
+    fn test_truncated_header() {
+        let err = from_slice::<String>(b"\xc3").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidHeaderLength {
+                expected: 2,
+                available: 1
+            }
+        ));
+        let err =
+            from_reader::<_, Vec<u8>>(&b"\x3b\xe3\x00\x00"[..]).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidHeaderLength {
+                expected: 5,
+                available: 3
+            }
+        ));
+    }
+
+    #[test]
+    fn test_truncated_slice_payload() {
+        // a 100-byte string in a 10-byte slice
+        let blob = b"\xc7\x6412345678";
+        assert!(matches!(
+            from_slice::<String>(blob),
+            Err(Error::Truncated {
+                payload_size: 100,
+                available: 8
+            })
+        ));
+        // inside a container
+        assert!(matches!(
+            from_slice::<Vec<String>>(b"\x4b\x57abc"),
+            Err(Error::Truncated {
+                payload_size: 5,
+                available: 3
+            })
+        ));
+        assert!(matches!(
+            from_slice::<Vec<String>>(b"\x5b\x17a"),
+            Err(Error::Truncated {
+                payload_size: 5,
+                available: 2
+            })
+        ));
+        // readers keep reporting the end of the input
+        assert!(matches!(
+            from_reader::<_, String>(&blob[..]),
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[test]
+    fn test_max_total_size() {
+        // 100 strings of 10 bytes: a 3-byte array header, and 11 bytes each
+        let strings = vec!["abcdefghij"; 100];
+        let blob = crate::to_vec(&strings).unwrap();
+        assert_eq!(blob.len(), 1103);
+        let options = |limit| Options {
+            max_total_size: Some(limit),
+            ..Options::default()
+        };
+        let decoded: Vec<String> =
+            from_slice_with_options(&blob, options(1103)).unwrap();
+        assert_eq!(decoded, strings);
+        assert!(matches!(
+            from_slice_with_options::<Vec<String>>(&blob, options(1102)),
+            Err(Error::SizeLimitExceeded { limit: 1102 })
+        ));
+        assert!(matches!(
+            from_reader_with_options::<_, Vec<String>>(&blob[..], options(500)),
+            Err(Error::SizeLimitExceeded { limit: 500 })
+        ));
+        // ignored values are skipped without being read
+        assert!(from_slice_with_options::<serde::de::IgnoredAny>(
+            &blob,
+            options(3)
+        )
+        .is_ok());
+        // the budget is shared by nested containers
+        let nested = vec![vec!["abcdefghij"; 10]; 10];
+        let blob = crate::to_vec(&nested).unwrap();
+        assert!(matches!(
+            from_slice_with_options::<Vec<Vec<String>>>(&blob, options(500)),
+            Err(Error::SizeLimitExceeded { limit: 500 })
+        ));
+        // numbers count their payload too: a 3-byte array header, and 10
+        // bytes each
+        let blob = crate::to_vec(&vec![123_456_789; 100]).unwrap();
+        assert_eq!(blob.len(), 1003);
+        let decoded: Vec<u32> =
+            from_slice_with_options(&blob, options(1003)).unwrap();
+        assert_eq!(decoded.len(), 100);
+        assert!(matches!(
+            from_slice_with_options::<Vec<u32>>(&blob, options(1002)),
+            Err(Error::SizeLimitExceeded { limit: 1002 })
+        ));
+        // a huge payload size is rejected before anything is allocated
+        let mut huge = b"\xf5".to_vec();
+        huge.extend_from_slice(&(1u64 << 40).to_be_bytes());
+        huge.extend_from_slice(b"1.5");
+        assert!(matches!(
+            from_reader_with_options::<_, f64>(&huge[..], options(1000)),
+            Err(Error::SizeLimitExceeded { limit: 1000 })
+        ));
+        // raw elements count the whole payload that they copy
+        let blob = crate::to_vec(&vec![vec![1u8; 100]]).unwrap();
+        assert!(from_slice_with_options::<crate::RawJsonb>(
+            &blob,
+            options(blob.len() as u64)
+        )
+        .is_ok());
+        assert!(matches!(
+            from_slice_with_options::<crate::RawJsonb>(&blob, options(100)),
+            Err(Error::SizeLimitExceeded { limit: 100 })
+        ));
+        assert!(matches!(
+            from_slice_with_options::<crate::RawElement>(&blob, options(100)),
+            Err(Error::SizeLimitExceeded { limit: 100 })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_string_payload() {
+        assert!(matches!(
+            from_reader::<_, String>(&b"\x5ahel"[..]),
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+        // a huge payload size is not allocated before the payload is read
+        let mut huge = b"\xfa".to_vec();
+        huge.extend_from_slice(&(1u64 << 40).to_be_bytes());
+        huge.extend_from_slice(b"hel");
+        assert!(matches!(
+            from_reader::<_, String>(&huge[..]),
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[test]
+    fn test_dyn_reader() {
+        let mut reader: Box<dyn Read> = Box::new(&b"\x4b\x131\x132"[..]);
+        assert_eq!(
+            from_reader::<_, Vec<u8>>(&mut reader as &mut dyn Read).unwrap(),
+            vec![1, 2]
+        );
+        let mut reader: Box<dyn Read> = Box::new(&b"\x5ahello"[..]);
+        assert_eq!(from_dyn_reader::<String>(&mut reader).unwrap(), "hello");
+        let reader: Box<dyn Read> = Box::new(&b"\x01"[..]);
+        assert!(from_reader::<_, bool>(reader).unwrap());
+    }
+
     #[test]
     fn test_vec_of_vecs() {
         assert_eq!(
@@ -952,6 +3194,84 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_serde_with_display_from_str() {
+        #[serde_with::serde_as]
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Test {
+            #[serde_as(as = "serde_with::DisplayFromStr")]
+            n: u64,
+        }
+        // {"n": "42"}
+        let actual: Test = from_slice(b"\x5c\x17n\x2742").unwrap();
+        assert_eq!(actual, Test { n: 42 });
+        // {"n": "4\u0032"}, with a json escape
+        let actual: Test = from_slice(b"\xac\x17n\x784\\u0032").unwrap();
+        assert_eq!(actual, Test { n: 42 });
+    }
+
+    #[test]
+    fn test_char() {
+        assert_eq!(from_slice::<char>(b"\x1aa").unwrap(), 'a');
+        // 2-byte and 4-byte UTF-8 characters
+        assert_eq!(from_slice::<char>(b"\x2a\xc3\xa9").unwrap(), '\u{e9}');
+        assert_eq!(
+            from_slice::<char>(b"\x4a\xf0\x9f\x98\x80").unwrap(),
+            '\u{1f600}'
+        );
+        // an escaped character
+        assert_eq!(from_slice::<char>(b"\x28\\n").unwrap(), '\n');
+        for blob in [&b"\x0a"[..], b"\x2aab", b"\x3ae\xcc\x81"] {
+            let err = from_slice::<char>(blob).unwrap_err();
+            assert_eq!(err.to_string(), "invalid string length for char");
+        }
+    }
+
+    #[test]
+    fn test_borrowed_str() {
+        use std::borrow::Cow;
+
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct S<'a> {
+            name: &'a str,
+            tags: Vec<&'a str>,
+            #[serde(borrow)]
+            note: Cow<'a, str>,
+        }
+        // {"name": "Ann", "tags": ["a", "bc"], "note": "x"}
+        let blob =
+            b"\xcc\x1b\x4aname\x3aAnn\x4atags\x5b\x1aa\x2abc\x4anote\x1ax";
+        let s: S = from_slice(blob).unwrap();
+        assert_eq!(s.name, "Ann");
+        assert_eq!(s.tags, ["a", "bc"]);
+        assert!(matches!(s.note, Cow::Borrowed("x")));
+        // the strings point into the blob
+        let name_start = blob.iter().position(|&b| b == b'A').unwrap();
+        assert_eq!(s.name.as_ptr(), blob[name_start..].as_ptr());
+
+        // escaped strings are unescaped into a new string
+        let note: Cow<str> = from_slice(b"\x28\\n").unwrap();
+        assert!(matches!(note, Cow::Owned(s) if s == "\n"));
+        let err = from_slice::<&str>(b"\x28\\n").unwrap_err();
+        assert!(err.to_string().contains("borrowed string"), "{err}");
+        assert!(from_slice::<&str>(b"\x2a\xff\xfe").is_err());
+
+        // keys are borrowed too when they are tracked: {"a": "b"}
+        use std::collections::HashMap;
+        let blob = b"\x4c\x1aa\x1ab";
+        let options = Options {
+            reject_duplicate_keys: true,
+            ..Options::default()
+        };
+        let map: HashMap<&str, &str> =
+            from_slice_with_options(blob, options).unwrap();
+        assert_eq!(map, HashMap::from([("a", "b")]));
+        let mut de = Deserializer::from_bytes(blob);
+        de.on_key(|_| {});
+        let map = HashMap::<&str, &str>::deserialize(&mut de).unwrap();
+        assert_eq!(map, HashMap::from([("a", "b")]));
+    }
+
     #[test]
     fn test_basic_enum() {
         #[derive(Debug, PartialEq, serde_derive::Deserialize)]
@@ -1026,6 +3346,139 @@ mod tests {
         assert_eq!(actual, expected, "{:x?} != {:x?}", actual, expected);
     }
 
+    #[test]
+    fn test_read_and_validate_header() {
+        let blobs: [(&[u8], ElementType, u64); 16] = [
+            (b"\x00", ElementType::Null, 0),
+            (b"\x01", ElementType::True, 0),
+            (b"\x02", ElementType::False, 0),
+            (b"\x131", ElementType::Int, 1),
+            (b"\x340x1", ElementType::Int5, 3),
+            (b"\x351.5", ElementType::Float, 3),
+            (b"\x26.5", ElementType::Float5, 2),
+            (b"\x17a", ElementType::Text, 1),
+            (b"\x28\\n", ElementType::TextJ, 2),
+            (b"\x29\\v", ElementType::Text5, 2),
+            (b"\x2a\xc3\xa9", ElementType::TextRaw, 2),
+            (b"\xcb\x02\x00\x00", ElementType::Array, 2),
+            (b"\xdc\x00\x03\x17a\x00", ElementType::Object, 3),
+            (b"\x1d\x00", ElementType::Reserved13, 1),
+            (b"\x1e\x05", ElementType::Reserved14, 1),
+            (
+                b"\xf5\x00\x00\x00\x00\x00\x00\x00\x01\x31",
+                ElementType::Float,
+                1,
+            ),
+        ];
+        for (blob, element_type, payload_size) in blobs {
+            let mut reader = blob;
+            let header = read_and_validate_header(&mut reader, false).unwrap();
+            assert_eq!(header.element_type, element_type, "{blob:x?}");
+            assert_eq!(header.payload_size, payload_size, "{blob:x?}");
+            assert_eq!(reader.len() as u64, payload_size, "{blob:x?}");
+            let validated = read_and_validate_header(blob, true);
+            if element_type == ElementType::Reserved13 {
+                assert!(matches!(
+                    validated,
+                    Err(Error::UnexpectedType {
+                        found: ElementType::Reserved13,
+                        ..
+                    })
+                ));
+            } else {
+                assert_eq!(validated.unwrap(), header);
+            }
+        }
+        // Reserved14 holds the integers of `binary_int`, and is accepted
+        let header = read_and_validate_header(&b"\x1e\x05"[..], true).unwrap();
+        assert_eq!(header.element_type, ElementType::Reserved14);
+        assert!(matches!(
+            read_and_validate_header(&b""[..], true),
+            Err(Error::Empty)
+        ));
+        assert!(matches!(
+            read_and_validate_header(&b"\xdb\x01"[..], true),
+            Err(Error::InvalidHeaderLength {
+                expected: 3,
+                available: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_copy_element() {
+        // {"a": {"b": [1, "x"]}, "c": null}, then [true]
+        let object = b"\xcc\x0d\x17a\x7c\x17b\x4b\x131\x17x\x17c\x00";
+        let mut input = object.to_vec();
+        input.extend_from_slice(b"\x1b\x01");
+        let mut reader = &input[..];
+        let mut copy = Vec::new();
+        assert_eq!(copy_element(&mut reader, &mut copy).unwrap(), object.len());
+        assert_eq!(copy, object);
+        assert_eq!(reader, b"\x1b\x01");
+        // the header is copied as is, even when it is larger than needed
+        let mut copy = Vec::new();
+        assert_eq!(copy_element(&b"\xc7\x01a"[..], &mut copy).unwrap(), 3);
+        assert_eq!(copy, b"\xc7\x01a");
+
+        assert!(matches!(
+            copy_element(&b""[..], Vec::new()),
+            Err(Error::Empty)
+        ));
+        assert!(matches!(
+            copy_element(&b"\xcc"[..], Vec::new()),
+            Err(Error::InvalidHeaderLength {
+                expected: 2,
+                available: 1
+            })
+        ));
+        assert!(matches!(
+            copy_element(&b"\x3b\x131"[..], Vec::new()),
+            Err(Error::Truncated {
+                payload_size: 3,
+                available: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_internally_tagged_enum_with_other_variant() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        #[serde(tag = "t")]
+        enum Shape {
+            Circle {
+                r: i64,
+            },
+            #[serde(other)]
+            Unknown,
+        }
+        // {"t": "Circle", "r": 2}
+        let actual: Shape =
+            from_slice(b"\xcc\x0d\x17t\x67Circle\x17r\x132").unwrap();
+        assert_eq!(actual, Shape::Circle { r: 2 });
+        // {"t": "Square", "side": 2}
+        let actual: Shape =
+            from_slice(b"\xcc\x10\x17t\x67Square\x47side\x132").unwrap();
+        assert_eq!(actual, Shape::Unknown);
+        // [{"t": "Square"}], with the tag stored as TextRaw
+        let actual: Vec<Shape> =
+            from_slice(b"\xab\x9c\x1at\x6aSquare").unwrap();
+        assert_eq!(actual, vec![Shape::Unknown]);
+    }
+
+    #[test]
+    fn test_unit_enum_with_other_variant() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        enum Color {
+            Red,
+            #[serde(other)]
+            Unknown,
+        }
+        // ["Red", "Blue"]
+        let actual: Vec<Color> = from_slice(b"\x9b\x37Red\x47Blue").unwrap();
+        assert_eq!(actual, vec![Color::Red, Color::Unknown]);
+    }
+
     #[cfg(feature = "serde_json")]
     #[test]
     fn test_array_of_maps_parsed_as_any() {
@@ -1041,6 +3494,64 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_json_text_input() {
+        for text in [&b"   {}"[..], b"{}", b"[1, 2]\n", b"\"hello\""] {
+            assert!(
+                matches!(
+                    from_slice::<serde::de::IgnoredAny>(text),
+                    Err(Error::LikelyJsonText)
+                ),
+                "{text:?}"
+            );
+        }
+        // valid jsonb that happens to be printable ascii is still accepted
+        assert_eq!(from_slice::<u8>(b"\x2342").unwrap(), 42);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_large_unsigned_parsed_as_any() {
+        let actual: serde_json::Value =
+            from_slice(b"\xc3\x1418446744073709551615").unwrap();
+        assert_eq!(actual, serde_json::json!(u64::MAX));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_nested_arrays_parsed_as_any() {
+        // [[false], true]
+        let actual: serde_json::Value =
+            from_slice(b"\x3b\x1b\x02\x01").unwrap();
+        assert_eq!(actual, serde_json::json!([[false], true]));
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[test]
+    fn test_object_key_order_preserved_in_value() {
+        // {"z": 1, "a": 2, "m": {"y": true, "b": null}}, with the keys stored
+        // as Text like the serializer does
+        let encoded =
+            b"\xcc\x11\x17z\x131\x17a\x132\x17m\x6c\x17y\x01\x17b\x00";
+        let actual: serde_json::Value = from_slice(encoded).unwrap();
+        let keys: Vec<&str> = actual
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        assert_eq!(keys, ["z", "a", "m"]);
+        let nested: Vec<&str> = actual["m"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        assert_eq!(nested, ["y", "b"]);
+        // re-serializing gives back the same blob
+        assert_eq!(crate::to_vec(&actual).unwrap(), encoded);
+    }
+
     #[test]
     fn test_externally_tagged_enum_too_large() {
         #[derive(Debug, PartialEq, serde_derive::Deserialize)]
@@ -1056,6 +3567,102 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_reject_non_finite() {
+        let reject = Options {
+            reject_non_finite: true,
+            ..Options::default()
+        };
+        // SQLite stores Infinity as 9e999
+        let blob = b"\x559e999";
+        assert_eq!(from_slice::<f64>(blob).unwrap(), f64::INFINITY);
+        assert!(matches!(
+            from_slice_with_options::<f64>(blob, reject),
+            Err(Error::NonFiniteFloat(f)) if f == f64::INFINITY
+        ));
+        assert!(matches!(
+            from_slice_with_options::<Vec<f32>>(b"\x7b\x65-9e999", reject),
+            Err(Error::NonFiniteFloat(f)) if f == f64::NEG_INFINITY
+        ));
+        let nan = [&b"\x8f"[..], &f64::NAN.to_le_bytes()].concat();
+        assert!(from_slice::<f64>(&nan).unwrap().is_nan());
+        assert!(matches!(
+            from_slice_with_options::<f64>(&nan, reject),
+            Err(Error::NonFiniteFloat(f)) if f.is_nan()
+        ));
+        // finite floats and integers are still accepted
+        assert_eq!(
+            from_slice_with_options::<Vec<f64>>(b"\x7b\x351.5\x2312", reject)
+                .unwrap(),
+            [1.5, 12.0]
+        );
+    }
+
+    #[cfg(feature = "serde_json5")]
+    #[test]
+    fn test_reject_non_finite_float5() {
+        let blob = b"\x86Infinity";
+        assert_eq!(from_slice::<f64>(blob).unwrap(), f64::INFINITY);
+        let reject = Options {
+            reject_non_finite: true,
+            ..Options::default()
+        };
+        assert!(matches!(
+            from_slice_with_options::<f64>(blob, reject),
+            Err(Error::NonFiniteFloat(f)) if f == f64::INFINITY
+        ));
+    }
+
+    #[test]
+    fn test_binary_float_roundtrip() {
+        let options = Options {
+            binary_float: true,
+            ..Options::default()
+        };
+        let values = vec![0.1f64, -2.5, 1e300, f64::MIN_POSITIVE];
+        let blob = crate::to_vec_with_options(&values, options).unwrap();
+        assert_eq!(from_slice::<Vec<f64>>(&blob).unwrap(), values);
+    }
+
+    #[test]
+    fn test_binary_float_width_in_deserialize_any() {
+        /// The name of the visitor method called by `deserialize_any`
+        struct VisitedAs(&'static str);
+        impl<'de> Deserialize<'de> for VisitedAs {
+            fn deserialize<D: de::Deserializer<'de>>(
+                deserializer: D,
+            ) -> std::result::Result<Self, D::Error> {
+                struct V;
+                impl Visitor<'_> for V {
+                    type Value = VisitedAs;
+                    fn expecting(
+                        &self,
+                        f: &mut std::fmt::Formatter,
+                    ) -> std::fmt::Result {
+                        f.write_str("a float")
+                    }
+                    fn visit_f32<E>(
+                        self,
+                        _: f32,
+                    ) -> std::result::Result<VisitedAs, E> {
+                        Ok(VisitedAs("f32"))
+                    }
+                    fn visit_f64<E>(
+                        self,
+                        _: f64,
+                    ) -> std::result::Result<VisitedAs, E> {
+                        Ok(VisitedAs("f64"))
+                    }
+                }
+                deserializer.deserialize_any(V)
+            }
+        }
+        let visited = |blob: &[u8]| from_slice::<VisitedAs>(blob).unwrap().0;
+        assert_eq!(visited(b"\x4f\x00\x00\x80\x3f"), "f32");
+        assert_eq!(visited(b"\x8f\x00\x00\x00\x00\x00\x00\xf0\x3f"), "f64");
+        assert_eq!(visited(b"\x351.0"), "f64");
+    }
+
     #[test]
     fn test_binary_float() {
         assert_eq!(
@@ -0,0 +1,143 @@
+//! Deserialization of object keys, which are strings even when the map has
+//! keys of another type, as written with
+//! [`Options::stringify_map_keys`](crate::Options::stringify_map_keys).
+
+use super::Deserializer;
+use crate::error::{Error, Result};
+use crate::header::ElementType;
+use serde::de::{self, Visitor};
+use std::io::Read;
+use std::str::FromStr;
+
+/// Deserializes an object key. A string key read as an integer, a float or
+/// a boolean is parsed from its text, as `serde_json` does; any other key is
+/// read like a value.
+pub(super) struct MapKeyDeserializer<'a, 'de, R: Read>(
+    pub(super) &'a mut Deserializer<'de, R>,
+);
+
+impl<R: Read> MapKeyDeserializer<'_, '_, R> {
+    /// Parse the next element if it is a string, or return `None` without
+    /// consuming it otherwise
+    fn parse_text<T: FromStr>(
+        &mut self,
+        expected: &'static str,
+    ) -> Result<Option<T>> {
+        let header = self.0.peek_header()?;
+        if !matches!(
+            header.element_type,
+            ElementType::Text
+                | ElementType::TextJ
+                | ElementType::Text5
+                | ElementType::TextRaw
+        ) {
+            return Ok(None);
+        }
+        let header = self.0.read_header()?;
+        let text = self.0.read_str(header)?;
+        match text.parse() {
+            Ok(v) => Ok(Some(v)),
+            Err(_) => Err(Error::expected(expected, header.element_type)),
+        }
+    }
+}
+
+macro_rules! parse_key {
+    ($($method:ident => $visit:ident($ty:ty, $expected:literal);)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+                match self.parse_text::<$ty>($expected)? {
+                    Some(v) => visitor.$visit(v),
+                    None => de::Deserializer::$method(self.0, visitor),
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! forward {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(
+                self,
+                $($arg: $ty,)*
+                visitor: V,
+            ) -> Result<V::Value> {
+                de::Deserializer::$method(self.0, $($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, R: Read> de::Deserializer<'de> for MapKeyDeserializer<'_, 'de, R> {
+    type Error = Error;
+
+    parse_key! {
+        deserialize_bool => visit_bool(bool, "boolean");
+        deserialize_i8 => visit_i8(i8, "integer");
+        deserialize_i16 => visit_i16(i16, "integer");
+        deserialize_i32 => visit_i32(i32, "integer");
+        deserialize_i64 => visit_i64(i64, "integer");
+        deserialize_i128 => visit_i128(i128, "integer");
+        deserialize_u8 => visit_u8(u8, "integer");
+        deserialize_u16 => visit_u16(u16, "integer");
+        deserialize_u32 => visit_u32(u32, "integer");
+        deserialize_u64 => visit_u64(u64, "integer");
+        deserialize_u128 => visit_u128(u128, "integer");
+        deserialize_f32 => visit_f32(f32, "float");
+        deserialize_f64 => visit_f64(f64, "float");
+    }
+
+    forward! {
+        deserialize_any();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_struct(
+            name: &'static str,
+            fields: &'static [&'static str]
+        );
+        deserialize_enum(
+            name: &'static str,
+            variants: &'static [&'static str]
+        );
+        deserialize_identifier();
+        deserialize_ignored_any();
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value> {
+        // a key is never null
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name == crate::number::NUMBER_TOKEN
+            || name == crate::number::RAW_NUMBER_TOKEN
+            || name == crate::raw::RAW_ELEMENT_TOKEN
+        {
+            return de::Deserializer::deserialize_newtype_struct(
+                self.0, name, visitor,
+            );
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.0.options.human_readable
+    }
+}
@@ -12,14 +12,93 @@ pub enum Error {
     JsonError(crate::json::JsonError),
     Json5Error(crate::json::Json5Error),
     InvalidElementType(u8),
-    UnexpectedType(ElementType),
+    /// An element is not of the type needed where it was found.
+    UnexpectedType {
+        /// The type of the element
+        found: ElementType,
+        /// The kind of value that was expected, when it is known
+        expected: Option<&'static str>,
+    },
     Io(std::io::Error),
     TrailingCharacters,
+    /// The input is not valid JSONB, but looks like JSON text.
+    LikelyJsonText,
+    /// An object contains the same key twice, and
+    /// [`Options::reject_duplicate_keys`](crate::Options::reject_duplicate_keys) is set.
+    DuplicateKey(String),
+    /// An object ends with a key that has no value. The key is known when
+    /// [`Options::reject_duplicate_keys`](crate::Options::reject_duplicate_keys)
+    /// or [`Deserializer::on_key`](crate::Deserializer::on_key) is set.
+    KeyWithoutValue(Option<String>),
+    /// A string key was expected in an object, but an element of another
+    /// type was found: the object is corrupt, or its keys and values are
+    /// out of step.
+    NonStringKey(ElementType),
+    /// A header uses more bytes than needed for its payload size, and
+    /// [`Options::require_minimal_headers`](crate::Options::require_minimal_headers) is set.
+    NonMinimalHeader {
+        /// The size of the header found in the input
+        header_len: usize,
+        /// The size of the smallest header for the same payload size
+        minimal_len: usize,
+    },
+    /// The input ends in the middle of an element header.
+    InvalidHeaderLength {
+        /// The size of the header, as announced by its first byte
+        expected: usize,
+        /// The number of header bytes that could be read
+        available: usize,
+    },
+    /// The input slice ends in the middle of the payload of an element.
+    Truncated {
+        /// The size of the payload, as announced by its header
+        payload_size: u64,
+        /// The number of bytes left in the input after the header
+        available: u64,
+    },
+    /// The checksum stored after a value does not match the value.
+    ChecksumMismatch {
+        /// The checksum stored in the input
+        stored: u32,
+        /// The checksum of the value found in the input
+        computed: u32,
+    },
+    /// A float is NaN or infinite, and
+    /// [`Options::non_finite_floats`](crate::Options::non_finite_floats) rejects
+    /// it when serializing, or
+    /// [`Options::reject_non_finite`](crate::Options::reject_non_finite) when
+    /// deserializing.
+    NonFiniteFloat(f64),
+    /// The value needs more than
+    /// [`Options::max_total_size`](crate::Options::max_total_size) bytes to decode.
+    SizeLimitExceeded {
+        /// The limit that was exceeded
+        limit: u64,
+    },
     Utf8(std::string::FromUtf8Error),
     Empty,
     IntConversion(std::num::TryFromIntError),
 }
 
+impl Error {
+    /// An [`Error::UnexpectedType`] for a `found` element where a value of
+    /// the `expected` kind was needed
+    pub(crate) fn expected(expected: &'static str, found: ElementType) -> Self {
+        Error::UnexpectedType {
+            found,
+            expected: Some(expected),
+        }
+    }
+
+    /// An [`Error::UnexpectedType`] for an element that is never valid
+    pub(crate) fn unexpected(found: ElementType) -> Self {
+        Error::UnexpectedType {
+            found,
+            expected: None,
+        }
+    }
+}
+
 impl ser::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
         Error::Message(msg.to_string())
@@ -41,14 +120,76 @@ impl Display for Error {
             Error::InvalidElementType(t) => {
                 write!(f, "{t} is not a valid jsonb element type code")
             }
-            Error::UnexpectedType(t) => write!(f, "unexpected type: {t:?}"),
+            Error::UnexpectedType {
+                found,
+                expected: Some(expected),
+            } => write!(f, "expected {expected}, found {found}"),
+            Error::UnexpectedType {
+                found,
+                expected: None,
+            } => write!(f, "unexpected {found}"),
             Error::Io(_) => write!(f, "io error"),
             Error::TrailingCharacters => {
                 write!(f, "trailing data after the end of the jsonb value")
             }
+            Error::LikelyJsonText => write!(
+                f,
+                "the input looks like json text, not a jsonb blob \
+                 (use sqlite's jsonb() function instead of json())"
+            ),
+            Error::DuplicateKey(k) => {
+                write!(f, "duplicate key {k:?} in object")
+            }
+            Error::KeyWithoutValue(None) => {
+                write!(f, "object has a key with no value")
+            }
+            Error::KeyWithoutValue(Some(k)) => {
+                write!(f, "object has a key with no value: {k:?}")
+            }
+            Error::NonStringKey(t) => write!(
+                f,
+                "expected a string object key, found {t}: \
+                 the object may have a value where a key should be"
+            ),
+            Error::NonMinimalHeader {
+                header_len,
+                minimal_len,
+            } => write!(
+                f,
+                "non-minimal jsonb header: {header_len} bytes instead of {minimal_len}"
+            ),
+            Error::InvalidHeaderLength {
+                expected,
+                available,
+            } => write!(
+                f,
+                "truncated jsonb header: expected {expected} bytes, \
+                 but only {available} are available"
+            ),
+            Error::Truncated {
+                payload_size,
+                available,
+            } => write!(
+                f,
+                "truncated jsonb payload: expected {payload_size} bytes, \
+                 but only {available} are available"
+            ),
+            Error::ChecksumMismatch { stored, computed } => write!(
+                f,
+                "checksum mismatch: stored {stored:08x}, computed {computed:08x}"
+            ),
+            Error::NonFiniteFloat(v) => {
+                write!(f, "{v} cannot be stored as a json number")
+            }
+            Error::SizeLimitExceeded { limit } => write!(
+                f,
+                "jsonb value exceeds the decoding limit of {limit} bytes"
+            ),
             Error::Utf8(_) => write!(f, "invalid utf8 in string"),
             Error::Empty => write!(f, "empty jsonb value"),
-            Error::IntConversion(e) => write!(f, "integer size conversion error: {e}"),
+            Error::IntConversion(e) => {
+                write!(f, "integer size conversion error: {e}")
+            }
         }
     }
 }
@@ -71,13 +212,25 @@ impl From<std::io::Error> for Error {
     }
 }
 
+/// Errors become [`std::io::ErrorKind::InvalidData`] errors that wrap them,
+/// except I/O errors, which are returned as they are.
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> std::io::Error {
+        match err {
+            Error::Io(e) => e,
+            e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
 impl From<std::string::FromUtf8Error> for Error {
     fn from(err: std::string::FromUtf8Error) -> Self {
         Error::Utf8(err)
     }
 }
 
-#[cfg(feature = "serde_json")]
+// With only serde_json5, `JsonError` and `Json5Error` are the same type
+#[cfg(any(feature = "serde_json", not(feature = "serde_json5")))]
 impl From<crate::json::JsonError> for Error {
     fn from(err: crate::json::JsonError) -> Error {
         Error::JsonError(err)
@@ -89,3 +242,27 @@ impl From<crate::json::Json5Error> for Error {
         Error::Json5Error(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_io_error() {
+        let err =
+            std::io::Error::from(Error::expected("object", ElementType::Array));
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "expected object, found array");
+        assert!(matches!(
+            err.into_inner().unwrap().downcast_ref::<Error>(),
+            Some(Error::UnexpectedType {
+                found: ElementType::Array,
+                expected: Some("object"),
+            })
+        ));
+
+        let eof = std::io::Error::from(std::io::ErrorKind::UnexpectedEof);
+        let err = std::io::Error::from(Error::Io(eof));
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
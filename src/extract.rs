@@ -0,0 +1,444 @@
+//! Helpers that read parts of a JSONB blob held in memory,
+//! skipping over the elements they do not need instead of decoding them.
+
+use crate::error::{Error, Result};
+use crate::header::{parse_header, ElementType, Header};
+use crate::raw::RawJsonb;
+use serde::de::{Deserialize, DeserializeOwned};
+
+/// An encoded element inside a blob held in memory
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Element<'a> {
+    pub(crate) header: Header,
+    /// The whole element, header included
+    pub(crate) bytes: &'a [u8],
+    pub(crate) payload: &'a [u8],
+}
+
+/// Split the first element off `data`, returning it and the bytes after it.
+pub(crate) fn split_element(data: &[u8]) -> Result<(Element<'_>, &[u8])> {
+    let (header, header_len) = parse_header(data)?;
+    let end = usize::try_from(header.payload_size)
+        .ok()
+        .and_then(|size| size.checked_add(header_len))
+        .filter(|&end| end <= data.len())
+        .ok_or_else(|| {
+            Error::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))
+        })?;
+    let (bytes, rest) = data.split_at(end);
+    let element = Element {
+        header,
+        bytes,
+        payload: &bytes[header_len..],
+    };
+    Ok((element, rest))
+}
+
+/// Whether the key element `key_element` holds `key`
+pub(crate) fn key_matches(key_element: &Element, key: &str) -> Result<bool> {
+    match key_element.header.element_type {
+        ElementType::Text | ElementType::TextRaw => {
+            Ok(key_element.payload == key.as_bytes())
+        }
+        // keys with escapes need to be decoded before comparing them
+        ElementType::TextJ | ElementType::Text5 => {
+            Ok(crate::from_slice::<String>(key_element.bytes)? == key)
+        }
+        t => Err(Error::expected("string", t)),
+    }
+}
+
+/// Deserialize the value of a single field of the JSONB object in `data`,
+/// without decoding the other fields. Returns `None` if there is no such key.
+/// If the key appears several times, the first occurrence is used.
+///
+/// ```
+/// // {"id": 1, "name": "John"}
+/// let blob = b"\xcc\x0f\x27id\x131\x47name\x47John";
+/// let name: Option<String> = serde_sqlite_jsonb::get_field(blob, "name")?;
+/// assert_eq!(name.as_deref(), Some("John"));
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `data` is not a JSONB object, if it is truncated,
+/// or if the value cannot be deserialized into `T`.
+pub fn get_field<T: DeserializeOwned>(
+    data: &[u8],
+    key: &str,
+) -> Result<Option<T>> {
+    let (object, _) = split_element(data)?;
+    if object.header.element_type != ElementType::Object {
+        return Err(Error::expected("object", object.header.element_type));
+    }
+    let mut entries = object.payload;
+    while !entries.is_empty() {
+        let (key_element, rest) = split_element(entries)?;
+        let (value, rest) = split_element(rest)?;
+        if key_matches(&key_element, key)? {
+            return crate::from_slice(value.bytes).map(Some);
+        }
+        entries = rest;
+    }
+    Ok(None)
+}
+
+/// Decode the entries of a JSONB object in the order in which they are stored,
+/// keeping every occurrence of duplicate keys. The values are not decoded.
+///
+/// ```
+/// // {"a": 1, "a": true}
+/// let blob = b"\x7c\x17a\x131\x17a\x01";
+/// let entries = serde_sqlite_jsonb::decode_object_entries(blob)?;
+/// assert_eq!(entries.len(), 2);
+/// assert_eq!(entries[1].0, "a");
+/// assert!(serde_sqlite_jsonb::from_slice::<bool>(entries[1].1.as_bytes())?);
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `data` is not a JSONB object, if it is truncated,
+/// or if one of its keys is not a string.
+pub fn decode_object_entries(data: &[u8]) -> Result<Vec<(String, RawJsonb)>> {
+    let (object, rest) = split_element(data)?;
+    if object.header.element_type != ElementType::Object {
+        return Err(Error::expected("object", object.header.element_type));
+    }
+    if !rest.is_empty() {
+        return Err(Error::TrailingCharacters);
+    }
+    let mut entries = Vec::new();
+    let mut remaining = object.payload;
+    while !remaining.is_empty() {
+        let (key_element, rest) = split_element(remaining)?;
+        let key: String = crate::from_slice(key_element.bytes)?;
+        if rest.is_empty() {
+            return Err(Error::KeyWithoutValue(Some(key)));
+        }
+        let (value, rest) = split_element(rest)?;
+        entries.push((key, RawJsonb::from_element_bytes(value.bytes)));
+        remaining = rest;
+    }
+    Ok(entries)
+}
+
+/// Deserialize the first `n` elements of the JSONB array in `data`, or all
+/// of them if there are fewer. The elements after them are not read at all,
+/// which makes previewing a large array cheap.
+///
+/// ```
+/// let blob = serde_sqlite_jsonb::to_vec(&(0..1000).collect::<Vec<u32>>())?;
+/// let first: Vec<u32> = serde_sqlite_jsonb::from_slice_take(&blob, 3)?;
+/// assert_eq!(first, [0, 1, 2]);
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `data` is not a JSONB array, if it is truncated,
+/// or if one of the first `n` elements cannot be deserialized into `T`.
+pub fn from_slice_take<T: DeserializeOwned>(
+    data: &[u8],
+    n: usize,
+) -> Result<Vec<T>> {
+    let (array, rest) = split_element(data)?;
+    if array.header.element_type != ElementType::Array {
+        return Err(Error::expected("array", array.header.element_type));
+    }
+    if !rest.is_empty() {
+        return Err(Error::TrailingCharacters);
+    }
+    // every element takes at least one byte
+    let mut values = Vec::with_capacity(n.min(array.payload.len()));
+    let mut items = array.payload;
+    while values.len() < n && !items.is_empty() {
+        let (item, rest) = split_element(items)?;
+        values.push(crate::from_slice(item.bytes)?);
+        items = rest;
+    }
+    Ok(values)
+}
+
+/// Deserialize the JSONB value that starts at `offset` in `data`, and return
+/// it together with the offset where it ends, which is where the next value
+/// starts when several values are stored one after the other.
+///
+/// ```
+/// let mut data = serde_sqlite_jsonb::to_vec(&"first")?;
+/// let offset = data.len();
+/// data.extend(serde_sqlite_jsonb::to_vec(&[1, 2])?);
+/// let (second, end): (Vec<u8>, usize) =
+///     serde_sqlite_jsonb::from_slice_at(&data, offset)?;
+/// assert_eq!(second, [1, 2]);
+/// assert_eq!(end, data.len());
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `offset` is past the end of `data`, if the value
+/// there is truncated, or if it cannot be deserialized into `T`.
+pub fn from_slice_at<'a, T: Deserialize<'a>>(
+    data: &'a [u8],
+    offset: usize,
+) -> Result<(T, usize)> {
+    let input = data.get(offset..).ok_or_else(|| {
+        Error::Message(format!(
+            "offset {offset} is past the end of the {} bytes of data",
+            data.len()
+        ))
+    })?;
+    let (element, _) = split_element(input)?;
+    let value = crate::from_slice(element.bytes)?;
+    Ok((value, offset + element.bytes.len()))
+}
+
+/// Decode a JSONB array of integers, such as `[1, -2, 3]`, into a `Vec<i64>`.
+///
+/// This gives the same result as `from_slice::<Vec<i64>>`, but reads the
+/// canonical `Int` elements directly instead of going through serde, which is
+/// much faster for large arrays.
+///
+/// ```
+/// let blob = b"\x7b\x131\x23-2\x133";
+/// assert_eq!(serde_sqlite_jsonb::decode_int_array(blob)?, [1, -2, 3]);
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `data` is not a JSONB array, if it is truncated,
+/// or if one of its elements is not an integer that fits in an `i64`.
+pub fn decode_int_array(data: &[u8]) -> Result<Vec<i64>> {
+    let (array, rest) = split_element(data)?;
+    if array.header.element_type != ElementType::Array {
+        return Err(Error::expected("array", array.header.element_type));
+    }
+    if !rest.is_empty() {
+        return Err(Error::TrailingCharacters);
+    }
+    // every element takes at least two bytes
+    let mut values = Vec::with_capacity(array.payload.len() / 2);
+    let mut items = array.payload;
+    while !items.is_empty() {
+        let (item, rest) = split_element(items)?;
+        let value = match item.header.element_type {
+            ElementType::Int => parse_canonical_int(item.payload),
+            _ => None,
+        };
+        // anything unusual is left to the deserializer, which also
+        // produces the right error
+        values.push(match value {
+            Some(value) => value,
+            None => crate::from_slice(item.bytes)?,
+        });
+        items = rest;
+    }
+    Ok(values)
+}
+
+/// Parse an integer made of an optional minus sign and decimal digits
+/// without leading zeros, or return `None` if it has any other form, such as
+/// `007` or `-0`, or does not fit in an `i64`.
+fn parse_canonical_int(text: &[u8]) -> Option<i64> {
+    let (negative, digits) = match text {
+        [b'-', digits @ ..] => (true, digits),
+        digits => (false, digits),
+    };
+    if digits.is_empty() || digits.len() > 19 {
+        return None;
+    }
+    if digits[0] == b'0' && (negative || digits.len() > 1) {
+        return None;
+    }
+    let mut value: i64 = 0;
+    for &d in digits {
+        if !d.is_ascii_digit() {
+            return None;
+        }
+        // accumulate negatively, so that i64::MIN does not overflow
+        value = value.checked_mul(10)?.checked_sub(i64::from(d - b'0'))?;
+    }
+    if negative {
+        Some(value)
+    } else {
+        value.checked_neg()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_field() {
+        // {"id": 1, "name": "John", "tags": ["a"]}
+        let blob = b"\xdc\x00\x17\x27id\x131\x47name\x47John\x47tags\x2b\x17a";
+        assert_eq!(get_field::<u8>(blob, "id").unwrap(), Some(1));
+        assert_eq!(
+            get_field::<String>(blob, "name").unwrap().as_deref(),
+            Some("John")
+        );
+        assert_eq!(
+            get_field::<Vec<String>>(blob, "tags").unwrap(),
+            Some(vec!["a".to_string()])
+        );
+        assert_eq!(get_field::<u8>(blob, "missing").unwrap(), None);
+        assert!(get_field::<u8>(blob, "name").is_err());
+    }
+
+    #[test]
+    fn test_decode_object_entries() {
+        // {"b": 1, "a\"": [true], "b": "x"}, with the escaped key as TextJ
+        let blob = b"\xcc\x0e\x17b\x131\x38a\\\"\x1b\x01\x17b\x18x";
+        let entries = decode_object_entries(blob).unwrap();
+        let keys: Vec<&str> = entries.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["b", "a\"", "b"]);
+        let values: Vec<&[u8]> =
+            entries.iter().map(|(_, v)| v.as_bytes()).collect();
+        assert_eq!(values, [&b"\x131"[..], b"\x1b\x01", b"\x18x"]);
+        assert_eq!(
+            crate::from_slice::<Vec<bool>>(entries[1].1.as_bytes()).unwrap(),
+            [true]
+        );
+
+        assert!(decode_object_entries(b"\x0c").unwrap().is_empty());
+        assert!(matches!(
+            decode_object_entries(b"\x2c\x17a"),
+            Err(Error::KeyWithoutValue(Some(key))) if key == "a"
+        ));
+        assert!(matches!(
+            decode_object_entries(b"\x0b"),
+            Err(Error::UnexpectedType {
+                found: ElementType::Array,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_get_field_escaped_key() {
+        // {"a\"b": 1}, with the key stored as TextJ
+        let blob = b"\x7c\x48a\\\"b\x131";
+        assert_eq!(get_field::<u8>(blob, "a\"b").unwrap(), Some(1));
+    }
+
+    #[test]
+    fn test_get_field_not_an_object() {
+        assert!(matches!(
+            get_field::<u8>(b"\x0b", "a"),
+            Err(Error::UnexpectedType {
+                found: ElementType::Array,
+                ..
+            })
+        ));
+        // the object claims more bytes than available
+        assert!(get_field::<u8>(b"\x5c\x17a\x131", "a").is_err());
+    }
+
+    #[test]
+    fn test_from_slice_take() {
+        // [0, 1, 2, ...] with 997 elements of a reserved type after the
+        // first three, which would fail to decode if they were read
+        let mut blob = vec![0xdb, 0x03, 0xeb];
+        blob.extend_from_slice(b"\x130\x131\x132");
+        blob.extend(std::iter::repeat(0x0d).take(997));
+        assert_eq!(blob.len(), 3 + 0x3eb);
+        assert_eq!(from_slice_take::<u8>(&blob, 3).unwrap(), [0, 1, 2]);
+        assert_eq!(from_slice_take::<u8>(&blob, 0).unwrap(), Vec::<u8>::new());
+        assert!(matches!(
+            from_slice_take::<u8>(&blob, 4),
+            Err(Error::UnexpectedType {
+                found: ElementType::Reserved13,
+                ..
+            })
+        ));
+
+        // fewer elements than requested
+        let blob = crate::to_vec(&["a", "b"]).unwrap();
+        assert_eq!(from_slice_take::<String>(&blob, 3).unwrap(), ["a", "b"]);
+        assert!(matches!(
+            from_slice_take::<u8>(b"\x0c", 1),
+            Err(Error::UnexpectedType {
+                found: ElementType::Object,
+                ..
+            })
+        ));
+        assert!(matches!(
+            from_slice_take::<u8>(b"\x0b\x00", 1),
+            Err(Error::TrailingCharacters)
+        ));
+    }
+
+    #[test]
+    fn test_from_slice_at() {
+        // "ab", then {"a": [true]}, then null
+        let data = b"\x2aab\x4c\x1aa\x1b\x01\x00";
+        let (first, end) = from_slice_at::<String>(data, 0).unwrap();
+        assert_eq!((first.as_str(), end), ("ab", 3));
+        let (second, end) = from_slice_at::<
+            std::collections::HashMap<String, Vec<bool>>,
+        >(data, 3)
+        .unwrap();
+        assert_eq!(second["a"], [true]);
+        assert_eq!(end, 8);
+        assert_eq!(from_slice_at::<()>(data, end).unwrap(), ((), 9));
+
+        assert!(matches!(from_slice_at::<()>(data, 9), Err(Error::Empty)));
+        let err = from_slice_at::<()>(data, 10).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "offset 10 is past the end of the 9 bytes of data"
+        );
+        // a value that would end past the end of the data
+        assert!(from_slice_at::<String>(&data[..7], 3).is_err());
+    }
+
+    #[test]
+    fn test_decode_int_array() {
+        let values = vec![0, 1, -1, 42, i64::MAX, i64::MIN];
+        let blob = crate::to_vec(&values).unwrap();
+        assert_eq!(decode_int_array(&blob).unwrap(), values);
+        assert_eq!(decode_int_array(b"\x0b").unwrap(), Vec::<i64>::new());
+        // a header that is longer than needed
+        assert_eq!(decode_int_array(b"\x3b\xc3\x015").unwrap(), [5]);
+    }
+
+    #[test]
+    fn test_decode_int_array_errors() {
+        assert!(matches!(
+            decode_int_array(b"\x0c"),
+            Err(Error::UnexpectedType {
+                found: ElementType::Object,
+                ..
+            })
+        ));
+        assert!(matches!(
+            decode_int_array(b"\x2b\x17a"),
+            Err(Error::UnexpectedType {
+                found: ElementType::Text,
+                ..
+            })
+        ));
+        // one more than i64::MAX
+        assert!(
+            decode_int_array(b"\xcb\x14\xc3\x139223372036854775808").is_err()
+        );
+        assert!(matches!(
+            decode_int_array(b"\x0b\x00"),
+            Err(Error::TrailingCharacters)
+        ));
+        assert!(decode_int_array(b"\x3b\x23").is_err());
+        // non-canonical integers are decoded as from_slice does
+        for blob in [&b"\x4b\x33007"[..], b"\x3b\x23-0", b"\x5b\x43-012"] {
+            assert_eq!(
+                decode_int_array(blob).ok(),
+                crate::from_slice::<Vec<i64>>(blob).ok(),
+                "{blob:?}"
+            );
+        }
+        assert!(decode_int_array(b"\x4b\x33007").is_err());
+    }
+}
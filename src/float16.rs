@@ -0,0 +1,96 @@
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+
+/// The name of the newtype struct through which [`F16`] writes its value.
+pub(crate) const F16_TOKEN: &str = "$serde_sqlite_jsonb::private::F16";
+
+/// A [`half::f16`](https://docs.rs/half) that is stored as a 2-byte
+/// `BinaryFloat` element with [`Options::binary_float`](crate::Options),
+/// which halves the storage needed for large vectors of low-precision floats.
+///
+/// Without `binary_float`, and with other serde formats, it is written as an
+/// `f32`. Deserializing one accepts any float, rounded to the nearest `f16`.
+///
+/// ```
+/// use half::f16;
+/// use serde_sqlite_jsonb::{Options, F16};
+///
+/// let options = Options { binary_float: true, ..Options::default() };
+/// let blob = serde_sqlite_jsonb::to_vec_with_options(&F16(f16::ONE), options)?;
+/// assert_eq!(blob, b"\x2f\x00\x3c");
+/// let value: F16 = serde_sqlite_jsonb::from_slice(&blob)?;
+/// assert_eq!(value.0, f16::ONE);
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct F16(pub half::f16);
+
+impl From<half::f16> for F16 {
+    fn from(v: half::f16) -> Self {
+        F16(v)
+    }
+}
+
+impl From<F16> for half::f16 {
+    fn from(v: F16) -> Self {
+        v.0
+    }
+}
+
+/// Serializes as the `f32` of the same value
+struct AsF32(half::f16);
+
+impl Serialize for AsF32 {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f32(self.0.to_f32())
+    }
+}
+
+impl Serialize for F16 {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(F16_TOKEN, &AsF32(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for F16 {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(F16_TOKEN, F16Visitor)
+    }
+}
+
+struct F16Visitor;
+
+impl<'de> Visitor<'de> for F16Visitor {
+    type Value = F16;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a float")
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<F16, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // 2-byte binary floats are decoded exactly as f32
+        let v = f32::deserialize(deserializer)?;
+        Ok(F16(half::f16::from_f32(v)))
+    }
+
+    fn visit_f32<E: de::Error>(self, v: f32) -> Result<F16, E> {
+        Ok(F16(half::f16::from_f32(v)))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<F16, E> {
+        Ok(F16(half::f16::from_f64(v)))
+    }
+}
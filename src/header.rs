@@ -32,12 +32,23 @@ pub enum ElementType {
     Object = 0xC,
     /// Reserved for future expansion.
     Reserved13 = 0xD,
-    /// Reserved for future expansion.
+    /// Reserved for future expansion. Used by this crate for little-endian
+    /// two's complement integers of 1, 2, 4 or 8 bytes, written with
+    /// [`Options::binary_int`](crate::Options::binary_int).
     Reserved14 = 0xE,
     /// Binary Float of IEEE 754 in little-endian
     BinaryFloat = 0xF,
 }
 
+/// The JSONB encoding of `null`
+pub const NULL: &[u8] = b"\x00";
+
+/// The JSONB encoding of an empty array, `[]`
+pub const EMPTY_ARRAY: &[u8] = b"\x0b";
+
+/// The JSONB encoding of an empty object, `{}`
+pub const EMPTY_OBJECT: &[u8] = b"\x0c";
+
 /// Represents the header of a JSONB element (size and type).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Header {
@@ -47,6 +58,7 @@ pub struct Header {
 
 impl Header {
     /// Serialize the header into a byte array.
+    #[must_use]
     pub fn serialize(self) -> [u8; 9] {
         let mut s = [0u8; 9];
         s[0] = u8::from(self.element_type) | 0xF0;
@@ -81,14 +93,132 @@ impl std::convert::From<u8> for ElementType {
     }
 }
 
+impl ElementType {
+    /// The kind of JSON value held by elements of this type
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ElementType::Null => "null",
+            ElementType::True | ElementType::False => "boolean",
+            ElementType::Int | ElementType::Int5 | ElementType::Reserved14 => {
+                "integer"
+            }
+            ElementType::Float
+            | ElementType::Float5
+            | ElementType::BinaryFloat => "float",
+            ElementType::Text
+            | ElementType::TextJ
+            | ElementType::Text5
+            | ElementType::TextRaw => "string",
+            ElementType::Array => "array",
+            ElementType::Object => "object",
+            ElementType::Reserved13 => "reserved element type 13",
+        }
+    }
+}
+
+/// Shows the kind of JSON value, such as `integer` for both `Int` and
+/// `Int5`. The `Debug` form tells the exact element type.
+impl std::fmt::Display for ElementType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 impl std::convert::From<ElementType> for u8 {
     fn from(value: ElementType) -> Self {
         value as u8
     }
 }
 
+/// Append the smallest possible header for an element with the given type
+/// and payload size to `buffer`. This is the inverse of reading a header.
+pub fn write_minimal_header(
+    buffer: &mut Vec<u8>,
+    element_type: ElementType,
+    payload_size: u64,
+) {
+    let (header, header_len) =
+        encode_minimal_header(element_type, payload_size);
+    buffer.extend_from_slice(&header[..header_len]);
+}
+
+/// Encode the smallest possible header for an element.
+/// Returns the header bytes, and how many of them are used.
+pub(crate) fn encode_minimal_header(
+    element_type: ElementType,
+    payload_size: u64,
+) -> ([u8; 9], usize) {
+    let mut header = [0u8; 9];
+    header[0] = u8::from(element_type);
+    let header_len = if payload_size <= 11 {
+        header[0] |= (u8::try_from(payload_size).unwrap()) << 4;
+        1
+    } else if let Ok(size) = u8::try_from(payload_size) {
+        header[0] |= 0xc0;
+        header[1] = size;
+        2
+    } else if let Ok(size) = u16::try_from(payload_size) {
+        header[0] |= 0xd0;
+        header[1..3].copy_from_slice(&size.to_be_bytes());
+        3
+    } else if let Ok(size) = u32::try_from(payload_size) {
+        header[0] |= 0xe0;
+        header[1..5].copy_from_slice(&size.to_be_bytes());
+        5
+    } else {
+        header[0] |= 0xf0;
+        header[1..9].copy_from_slice(&payload_size.to_be_bytes());
+        9
+    };
+    (header, header_len)
+}
+
+/// Check that `data` holds exactly one JSONB element, and return its header.
+///
+/// # Errors
+///
+/// Returns an error if the data is empty, if the header is truncated,
+/// or if the data length does not match the header payload size.
 pub fn is_jsonb(data: &[u8]) -> Result<Header, Error> {
-    if data.len() == 0 {
+    let (header, header_len) = parse_header(data)?;
+
+    // then check length of rest bytes instead of checking recursively
+    // which means we just do a naive checking here
+    if u64::try_from(data.len() - header_len) != Ok(header.payload_size) {
+        return Err(Error::Message(
+            "data length does not match header payload size".to_string(),
+        ));
+    }
+
+    Ok(header)
+}
+
+/// The type of the JSONB element at the start of `data`.
+///
+/// Only the header is read: unlike [`is_jsonb`], the payload size is not
+/// checked against the length of `data`, so this is enough to tell an
+/// object from an array or a scalar before deciding how to read it.
+///
+/// ```
+/// use serde_sqlite_jsonb::{top_level_type, ElementType};
+///
+/// // {"a": 1}, without its last byte
+/// assert_eq!(top_level_type(b"\x4c\x17a\x13")?, ElementType::Object);
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the data is empty or if the header is truncated.
+pub fn top_level_type(data: &[u8]) -> Result<ElementType, Error> {
+    let (header, _) = parse_header(data)?;
+    Ok(header.element_type)
+}
+
+/// Parse the header at the start of `data`.
+/// Returns the header and the number of bytes it occupies.
+pub(crate) fn parse_header(data: &[u8]) -> Result<(Header, usize), Error> {
+    if data.is_empty() {
         return Err(Error::Empty);
     }
 
@@ -113,28 +243,61 @@ pub fn is_jsonb(data: &[u8]) -> Result<Header, Error> {
 
         let mut buf = [0u8; 8];
         let start = 8 - bytes_to_read;
-        buf[start..].copy_from_slice(&data[1..1 + bytes_to_read]);
+        buf[start..].copy_from_slice(&data[1..=bytes_to_read]);
         u64::from_be_bytes(buf)
     };
 
-    // then check length of rest bytes instead of checking recursively
-    // which means we just do a naive checking here
-    if data.len() != 1 + bytes_to_read + payload_size as usize {
-        return Err(Error::Message(
-            "data length does not match header payload size".to_string(),
-        ));
-    }
-
-    Ok(Header {
+    let header = Header {
         element_type: ElementType::from(first_byte),
         payload_size,
-    })
+    };
+    Ok((header, 1 + bytes_to_read))
 }
 
 #[cfg(test)]
+#[allow(clippy::cast_lossless, clippy::identity_op)]
 mod tests {
     use super::*;
 
+    fn minimal_header(element_type: ElementType, payload_size: u64) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        write_minimal_header(&mut buffer, element_type, payload_size);
+        let (header, header_len) = parse_header(&buffer).unwrap();
+        assert_eq!(header_len, buffer.len());
+        assert_eq!(header.element_type, element_type);
+        assert_eq!(header.payload_size, payload_size);
+        buffer
+    }
+
+    #[test]
+    fn test_element_type_display() {
+        assert_eq!(ElementType::Int5.to_string(), "integer");
+        assert_eq!(ElementType::BinaryFloat.to_string(), "float");
+        assert_eq!(ElementType::TextRaw.to_string(), "string");
+        assert_eq!(ElementType::False.to_string(), "boolean");
+        assert_eq!(format!("{:?}", ElementType::Int5), "Int5");
+    }
+
+    #[test]
+    fn test_write_minimal_header_size_boundaries() {
+        use ElementType::{Array, Object, Text};
+        assert_eq!(minimal_header(Text, 0), b"\x07");
+        assert_eq!(minimal_header(Text, 11), b"\xb7");
+        assert_eq!(minimal_header(Text, 12), b"\xc7\x0c");
+        assert_eq!(minimal_header(Array, 0xff), b"\xcb\xff");
+        assert_eq!(minimal_header(Array, 0x100), b"\xdb\x01\x00");
+        assert_eq!(minimal_header(Object, 0xffff), b"\xdc\xff\xff");
+        assert_eq!(minimal_header(Object, 0x1_0000), b"\xec\x00\x01\x00\x00");
+        assert_eq!(
+            minimal_header(Object, 0xffff_ffff),
+            b"\xec\xff\xff\xff\xff"
+        );
+        assert_eq!(
+            minimal_header(Object, 0x1_0000_0000),
+            b"\xfc\x00\x00\x00\x01\x00\x00\x00\x00"
+        );
+    }
+
     #[test]
     fn test_is_jsonb_empty_data() {
         let data = &[];
@@ -399,4 +562,24 @@ mod tests {
             assert_eq!(result.payload_size, 0);
         }
     }
+
+    #[test]
+    fn test_top_level_type() {
+        // {"a": 1}
+        assert_eq!(
+            top_level_type(b"\x4c\x17a\x131").unwrap(),
+            ElementType::Object
+        );
+        // [1, 2], with only the start of its payload
+        assert_eq!(top_level_type(b"\x4b\x13").unwrap(), ElementType::Array);
+        // a 300-byte array, whose size is read but not checked
+        assert_eq!(
+            top_level_type(b"\xdb\x01\x2c").unwrap(),
+            ElementType::Array
+        );
+        assert_eq!(top_level_type(b"\x2342").unwrap(), ElementType::Int);
+        assert!(matches!(top_level_type(b""), Err(Error::Empty)));
+        // the size bytes of the header are missing
+        assert!(top_level_type(b"\xdb\x01").is_err());
+    }
 }
@@ -5,13 +5,20 @@ pub(crate) use serde_json::from_slice as parse_json_slice;
 #[cfg(feature = "serde_json")]
 pub(crate) type JsonError = serde_json::Error;
 
-#[cfg(not(feature = "serde_json"))]
+#[cfg(all(not(feature = "serde_json"), feature = "serde_json5"))]
 pub(crate) use serde_json5::from_reader as parse_json;
-#[cfg(not(feature = "serde_json"))]
+#[cfg(all(not(feature = "serde_json"), feature = "serde_json5"))]
 pub(crate) use serde_json5::from_slice as parse_json_slice;
-#[cfg(not(feature = "serde_json"))]
+#[cfg(all(not(feature = "serde_json"), feature = "serde_json5"))]
 pub(crate) type JsonError = serde_json5::Error;
 
+// Without any json parser, fall back to a minimal built-in one that handles
+// the numbers and strings of Int, Float and TextJ elements.
+#[cfg(any(test, not(any(feature = "serde_json", feature = "serde_json5"))))]
+mod builtin;
+#[cfg(not(any(feature = "serde_json", feature = "serde_json5")))]
+pub(crate) use builtin::{parse_json, parse_json_slice, JsonError};
+
 #[cfg(feature = "serde_json5")]
 pub(crate) use serde_json5::from_reader as parse_json5;
 
@@ -0,0 +1,287 @@
+//! A minimal JSON parser for the numbers and strings found in JSONB payloads,
+//! used when neither `serde_json` nor `serde_json5` is enabled.
+//!
+//! It only understands a single number or string, which is all the JSONB
+//! `Int`, `Float` and `TextJ` elements ever contain.
+
+use serde::de::{self, DeserializeOwned, Visitor};
+use std::fmt::{self, Display};
+use std::io::Read;
+
+/// An error produced by the built-in parser.
+#[derive(Debug)]
+pub struct JsonError(String);
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl de::Error for JsonError {
+    fn custom<T: Display>(msg: T) -> Self {
+        JsonError(msg.to_string())
+    }
+}
+
+impl From<std::io::Error> for JsonError {
+    fn from(err: std::io::Error) -> Self {
+        JsonError(err.to_string())
+    }
+}
+
+pub(crate) fn parse_json<R: Read, T: DeserializeOwned>(
+    mut reader: R,
+) -> Result<T, JsonError> {
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input)?;
+    parse_json_slice(&input)
+}
+
+pub(crate) fn parse_json_slice<T: DeserializeOwned>(
+    input: &[u8],
+) -> Result<T, JsonError> {
+    T::deserialize(ValueDeserializer {
+        input: trim_whitespace(input),
+    })
+}
+
+fn trim_whitespace(mut input: &[u8]) -> &[u8] {
+    let is_ws = |b: &u8| matches!(b, b' ' | b'\t' | b'\n' | b'\r');
+    while input.first().map_or(false, is_ws) {
+        input = &input[1..];
+    }
+    while input.last().map_or(false, is_ws) {
+        input = &input[..input.len() - 1];
+    }
+    input
+}
+
+struct ValueDeserializer<'a> {
+    input: &'a [u8],
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'_> {
+    type Error = JsonError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, JsonError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.input.first() {
+            Some(b'"') => visitor.visit_string(parse_string(self.input)?),
+            Some(b'-' | b'0'..=b'9') => parse_number(self.input, visitor),
+            _ => Err(invalid(self.input, "a number or a string")),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+fn invalid(input: &[u8], expected: &str) -> JsonError {
+    JsonError(format!(
+        "invalid json: expected {expected}, found {:?}",
+        String::from_utf8_lossy(input)
+    ))
+}
+
+/// Parse a number following the JSON grammar. Integers are visited as `u64`
+/// or `i64` when they fit, and as `f64` otherwise, like `serde_json` does.
+fn parse_number<'de, V: Visitor<'de>>(
+    input: &[u8],
+    visitor: V,
+) -> Result<V::Value, JsonError> {
+    let digits = |s: &[u8]| s.iter().take_while(|b| b.is_ascii_digit()).count();
+    let negative = input.first() == Some(&b'-');
+    let mut pos = usize::from(negative);
+    let int_len = digits(&input[pos..]);
+    if int_len == 0 || (int_len > 1 && input[pos] == b'0') {
+        return Err(invalid(input, "a number"));
+    }
+    pos += int_len;
+    let mut is_float = false;
+    if input.get(pos) == Some(&b'.') {
+        let frac_len = digits(&input[pos + 1..]);
+        if frac_len == 0 {
+            return Err(invalid(input, "a number"));
+        }
+        pos += 1 + frac_len;
+        is_float = true;
+    }
+    if matches!(input.get(pos), Some(b'e' | b'E')) {
+        pos += 1;
+        if matches!(input.get(pos), Some(b'+' | b'-')) {
+            pos += 1;
+        }
+        let exp_len = digits(&input[pos..]);
+        if exp_len == 0 {
+            return Err(invalid(input, "a number"));
+        }
+        pos += exp_len;
+        is_float = true;
+    }
+    if pos != input.len() {
+        return Err(invalid(input, "a number"));
+    }
+    // The input was validated to be ASCII
+    let text =
+        std::str::from_utf8(input).map_err(|e| JsonError(e.to_string()))?;
+    if !is_float {
+        if negative {
+            // like serde_json, -0 is a float
+            match text.parse::<i64>() {
+                Ok(i) if i != 0 => return visitor.visit_i64(i),
+                _ => {}
+            }
+        } else if let Ok(u) = text.parse::<u64>() {
+            return visitor.visit_u64(u);
+        }
+    }
+    let f = text.parse::<f64>().map_err(|e| JsonError(e.to_string()))?;
+    if f.is_infinite() {
+        return Err(JsonError(format!("number out of range: {text}")));
+    }
+    visitor.visit_f64(f)
+}
+
+/// Parse a double-quoted JSON string, decoding its escape sequences.
+fn parse_string(input: &[u8]) -> Result<String, JsonError> {
+    let inner = match input {
+        [b'"', inner @ .., b'"'] => inner,
+        _ => return Err(invalid(input, "a string")),
+    };
+    let mut out = Vec::with_capacity(inner.len());
+    let mut bytes = inner.iter().copied();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'\\' => {
+                let c = match bytes.next() {
+                    Some(b'"') => '"',
+                    Some(b'\\') => '\\',
+                    Some(b'/') => '/',
+                    Some(b'b') => '\x08',
+                    Some(b'f') => '\x0c',
+                    Some(b'n') => '\n',
+                    Some(b'r') => '\r',
+                    Some(b't') => '\t',
+                    Some(b'u') => parse_unicode_escape(&mut bytes)?,
+                    _ => return Err(invalid(input, "a valid escape sequence")),
+                };
+                let mut buf = [0u8; 4];
+                out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+            b'"' | 0..=0x1f => {
+                return Err(invalid(input, "a valid string character"))
+            }
+            b => out.push(b),
+        }
+    }
+    String::from_utf8(out).map_err(|e| JsonError(e.to_string()))
+}
+
+/// Decode the `XXXX` of a `\uXXXX` escape, combining surrogate pairs.
+fn parse_unicode_escape(
+    bytes: &mut impl Iterator<Item = u8>,
+) -> Result<char, JsonError> {
+    let hex4 = |bytes: &mut dyn Iterator<Item = u8>| {
+        let mut code = 0u32;
+        for _ in 0..4 {
+            let digit = bytes
+                .next()
+                .and_then(|b| char::from(b).to_digit(16))
+                .ok_or_else(|| {
+                JsonError("invalid unicode escape".into())
+            })?;
+            code = code * 16 + digit;
+        }
+        Ok::<u32, JsonError>(code)
+    };
+    let first = hex4(bytes)?;
+    let code = if (0xD800..0xDC00).contains(&first) {
+        if bytes.next() != Some(b'\\') || bytes.next() != Some(b'u') {
+            return Err(JsonError("unpaired surrogate in string".into()));
+        }
+        let second = hex4(bytes)?;
+        if !(0xDC00..0xE000).contains(&second) {
+            return Err(JsonError("unpaired surrogate in string".into()));
+        }
+        0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)
+    } else {
+        first
+    };
+    char::from_u32(code)
+        .ok_or_else(|| JsonError("invalid unicode escape".into()))
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(parse_json_slice::<u64>(b"42").unwrap(), 42);
+        assert_eq!(parse_json_slice::<i64>(b"-42").unwrap(), -42);
+        assert_eq!(parse_json_slice::<f64>(b"1.5e3").unwrap(), 1500.0);
+        assert_eq!(parse_json_slice::<f64>(b" 2 ").unwrap(), 2.0);
+        assert_eq!(parse_json::<_, u8>(&b"7\n"[..]).unwrap(), 7);
+        for bad in [&b""[..], b"-", b"01", b"1.", b".5", b"1e", b"1x", b"1e400"]
+        {
+            assert!(parse_json_slice::<f64>(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(
+            parse_json_slice::<String>(r#""a\"b\\c\/\né😀""#.as_bytes())
+                .unwrap(),
+            "a\"b\\c/\n\u{e9}\u{1f600}"
+        );
+        for bad in [&br#""\x""#[..], br#""\ud83d""#, b"\"a\nb\"", b"\"a"] {
+            assert!(parse_json_slice::<String>(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_matches_serde_json() {
+        let numbers: &[&[u8]] = &[
+            b"0",
+            b"-0",
+            b"123",
+            b"-9223372036854775808",
+            b"18446744073709551615",
+            b"18446744073709551616",
+            b"0.1",
+            b"-1.25e-7",
+            b"3.141592653589793",
+            b"9.745231549739238e+199",
+        ];
+        for &n in numbers {
+            let expected: serde_json::Value =
+                serde_json::from_slice(n).unwrap();
+            let actual: serde_json::Value = parse_json_slice(n).unwrap();
+            assert_eq!(actual, expected, "{}", String::from_utf8_lossy(n));
+        }
+        let strings: &[&[u8]] = &[
+            br#""""#,
+            br#""plain""#,
+            br#""tab\tquote\"slash\\""#,
+            r#""\u0000\u001f€""#.as_bytes(),
+            r#""𝄞""#.as_bytes(),
+            "\"caf\u{e9}\"".as_bytes(),
+        ];
+        for &s in strings {
+            let expected: String = serde_json::from_slice(s).unwrap();
+            let actual: String = parse_json_slice(s).unwrap();
+            assert_eq!(actual, expected);
+        }
+    }
+}
@@ -1,12 +1,58 @@
 #![warn(clippy::pedantic)]
 
+mod bytes;
+mod checksum;
+mod compare;
 mod de;
 mod error;
+mod extract;
+#[cfg(feature = "half")]
+mod float16;
 mod header;
 mod json;
+mod log;
+mod merge;
+mod number;
+mod options;
+mod raw;
 mod ser;
+#[cfg(feature = "rusqlite")]
+mod sqlite;
+#[cfg(feature = "serde_json")]
+mod value;
+mod view;
 
-pub use crate::de::{from_reader, from_slice, Deserializer};
+pub use crate::bytes::{as_base64_bytes, as_hex_bytes, BytesEncoding};
+pub use crate::compare::jsonb_equal;
+pub use crate::de::{
+    copy_element, from_dyn_reader, from_length_prefixed_reader, from_reader,
+    from_reader_with_options, from_reader_with_remainder, from_slice,
+    from_slice_checked, from_slice_in_place, from_slice_with_options,
+    read_and_validate_header, Deserializer,
+};
 pub use crate::error::{Error, Result};
-pub use crate::header::{is_jsonb, Header};
-pub use crate::ser::{to_vec, to_vec_with_options, Options, Serializer};
+pub use crate::extract::{
+    decode_int_array, decode_object_entries, from_slice_at, from_slice_take,
+    get_field,
+};
+#[cfg(feature = "half")]
+pub use crate::float16::F16;
+pub use crate::header::{
+    is_jsonb, top_level_type, write_minimal_header, ElementType, Header,
+    EMPTY_ARRAY, EMPTY_OBJECT, NULL,
+};
+pub use crate::log::{JsonbLogReader, JsonbLogWriter};
+pub use crate::merge::merge;
+pub use crate::number::{Number, RawNumber};
+pub use crate::options::{NonFiniteFloats, Options};
+pub use crate::raw::{RawElement, RawJsonb};
+pub use crate::ser::{
+    to_length_prefixed_vec, to_length_prefixed_vec_with_options,
+    to_seekable_writer, to_seekable_writer_with_options, to_vec,
+    to_vec_with_options, to_writer, to_writer_with_options, Serializer,
+};
+#[cfg(feature = "rusqlite")]
+pub use crate::sqlite::RowJsonbExt;
+#[cfg(feature = "serde_json")]
+pub use crate::value::to_json_value;
+pub use crate::view::JsonbView;
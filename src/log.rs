@@ -0,0 +1,254 @@
+//! An append-only log of JSONB records, each prefixed by its length.
+
+use crate::de::read_up_to;
+use crate::error::{Error, Result};
+use crate::options::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+
+/// The size of the big-endian length that precedes each record, as written
+/// by [`to_length_prefixed_vec`](crate::to_length_prefixed_vec).
+pub(crate) const LENGTH_PREFIX_SIZE: usize = 4;
+
+/// Appends records to a writer, each as a JSONB value prefixed by its
+/// length, to be read back in order with [`JsonbLogReader`].
+///
+/// ```
+/// use serde_sqlite_jsonb::{JsonbLogReader, JsonbLogWriter};
+///
+/// let mut log = JsonbLogWriter::new(Vec::new());
+/// log.append(&("start", 1))?;
+/// log.append(&("stop", 2))?;
+/// let file = log.into_inner();
+///
+/// let records: Vec<(String, u8)> =
+///     JsonbLogReader::new(&file[..]).collect::<Result<_, _>>()?;
+/// assert_eq!(records, [("start".into(), 1), ("stop".into(), 2)]);
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+pub struct JsonbLogWriter<W: Write> {
+    writer: W,
+    options: Options,
+}
+
+impl<W: Write> JsonbLogWriter<W> {
+    /// Append records at the current position of `writer`.
+    /// To extend an existing log file, open it in append mode.
+    pub fn new(writer: W) -> Self {
+        Self::with_options(writer, Options::default())
+    }
+
+    /// Like [`JsonbLogWriter::new`], but serializes each record with the
+    /// given options, as [`to_length_prefixed_vec_with_options`] does.
+    ///
+    /// [`to_length_prefixed_vec_with_options`]: crate::to_length_prefixed_vec_with_options
+    pub fn with_options(writer: W, options: Options) -> Self {
+        JsonbLogWriter { writer, options }
+    }
+
+    /// Write `value` as the next record. Each record is written with a
+    /// single `write_all`, so a failing writer leaves at worst a truncated
+    /// last record, which [`JsonbLogReader`] reports as an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization fails, if the JSONB value is
+    /// larger than 4 GiB, or if the writer fails.
+    pub fn append<T: Serialize>(&mut self, value: &T) -> Result<()> {
+        let record =
+            crate::to_length_prefixed_vec_with_options(value, self.options)?;
+        self.writer.write_all(&record)?;
+        Ok(())
+    }
+
+    /// Flush the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer fails.
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    /// The underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads the records written by [`JsonbLogWriter`], in order, as an
+/// iterator of decoded values.
+///
+/// The iterator ends at the end of the reader. A last record that was cut
+/// short, for instance by a crash while it was being written, gives an
+/// [`Error::Io`] of kind `UnexpectedEof`, after which the iterator ends.
+/// A record that fails to decode gives its error, and the next records
+/// are still read.
+pub struct JsonbLogReader<R: Read, T> {
+    reader: R,
+    /// A buffer reused for the payload of each record
+    buffer: Vec<u8>,
+    done: bool,
+    record: PhantomData<fn() -> T>,
+}
+
+impl<R: Read, T: DeserializeOwned> JsonbLogReader<R, T> {
+    /// Read records from the current position of `reader`.
+    pub fn new(reader: R) -> Self {
+        JsonbLogReader {
+            reader,
+            buffer: Vec::new(),
+            done: false,
+            record: PhantomData,
+        }
+    }
+
+    /// The underlying reader, positioned after the last record read
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read the next record. The reader cannot be resynchronized after an
+    /// incomplete record, so reading stops there, as at the end of the input.
+    fn read_record(&mut self) -> Result<Option<T>> {
+        match self.read_frame() {
+            Ok(true) => crate::from_slice(&self.buffer).map(Some),
+            Ok(false) => {
+                self.done = true;
+                Ok(None)
+            }
+            Err(e) => {
+                self.done = true;
+                Err(e)
+            }
+        }
+    }
+
+    /// Read the payload of the next record into the buffer, and return
+    /// `false` at the end of the input.
+    fn read_frame(&mut self) -> Result<bool> {
+        let mut prefix = [0u8; LENGTH_PREFIX_SIZE];
+        match read_up_to(&mut self.reader, &mut prefix)? {
+            0 => return Ok(false),
+            LENGTH_PREFIX_SIZE => {}
+            _ => return Err(truncated()),
+        }
+        let len = u32::from_be_bytes(prefix);
+        self.buffer.clear();
+        // Do not allocate the announced length before the data is actually there
+        (&mut self.reader)
+            .take(u64::from(len))
+            .read_to_end(&mut self.buffer)?;
+        if self.buffer.len() == len as usize {
+            Ok(true)
+        } else {
+            Err(truncated())
+        }
+    }
+}
+
+fn truncated() -> Error {
+    Error::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))
+}
+
+impl<R: Read, T: DeserializeOwned> Iterator for JsonbLogReader<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(
+        Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+    )]
+    struct Event {
+        id: u32,
+        name: String,
+    }
+
+    fn event(id: u32) -> Event {
+        Event {
+            id,
+            name: format!("event {id}"),
+        }
+    }
+
+    #[test]
+    fn test_write_and_read_100_records() {
+        let mut log = JsonbLogWriter::new(Vec::new());
+        for id in 0..100 {
+            log.append(&event(id)).unwrap();
+        }
+        let file = log.into_inner();
+        let events: Vec<Event> = JsonbLogReader::new(&file[..])
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(events, (0..100).map(event).collect::<Vec<_>>());
+        assert_eq!(JsonbLogReader::<_, Event>::new(&b""[..]).count(), 0);
+    }
+
+    #[test]
+    fn test_truncated_last_record() {
+        let mut log = JsonbLogWriter::new(Vec::new());
+        for id in 0..3 {
+            log.append(&event(id)).unwrap();
+        }
+        let file = log.into_inner();
+        // cut in the payload, then in the length prefix of the last record
+        let last_start = file.len()
+            - crate::to_length_prefixed_vec(&event(2)).unwrap().len();
+        for cut in [file.len() - 1, last_start + 2] {
+            let mut records = JsonbLogReader::<_, Event>::new(&file[..cut]);
+            assert_eq!(records.next().unwrap().unwrap(), event(0));
+            assert_eq!(records.next().unwrap().unwrap(), event(1));
+            assert!(matches!(
+                records.next(),
+                Some(Err(Error::Io(e))) if e.kind() == std::io::ErrorKind::UnexpectedEof
+            ));
+            assert!(records.next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_undecodable_record_is_skipped() {
+        let mut log = JsonbLogWriter::new(Vec::new());
+        log.append(&event(0)).unwrap();
+        log.append(&"not an event").unwrap();
+        log.append(&event(2)).unwrap();
+        let file = log.into_inner();
+        let mut records = JsonbLogReader::<_, Event>::new(&file[..]);
+        assert_eq!(records.next().unwrap().unwrap(), event(0));
+        assert!(records.next().unwrap().is_err());
+        assert_eq!(records.next().unwrap().unwrap(), event(2));
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn test_write_with_options() {
+        let options = Options {
+            binary_int: true,
+            ..Options::default()
+        };
+        let mut log = JsonbLogWriter::with_options(Vec::new(), options);
+        log.append(&event(300)).unwrap();
+        let file = log.into_inner();
+        let record =
+            crate::to_length_prefixed_vec_with_options(&event(300), options)
+                .unwrap();
+        assert_eq!(file, record);
+        assert_ne!(file, crate::to_length_prefixed_vec(&event(300)).unwrap());
+        let mut records = JsonbLogReader::<_, Event>::new(&file[..]);
+        assert_eq!(records.next().unwrap().unwrap(), event(300));
+        assert!(records.next().is_none());
+    }
+}
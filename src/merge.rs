@@ -0,0 +1,207 @@
+//! Merging of JSONB objects, following the JSON merge patch of RFC 7386.
+
+use crate::error::{Error, Result};
+use crate::extract::{split_element, Element};
+use crate::header::{write_minimal_header, ElementType};
+
+/// Apply the JSONB `patch` to the JSONB value `base`, as `SQLite`'s
+/// `json_patch()` does, following the JSON merge patch of RFC 7386.
+///
+/// When `patch` is an object, each of its keys replaces the value of the
+/// same key in `base`, or is added after the keys of `base` if it is new.
+/// A `null` value removes the key instead, and an object value is itself
+/// merged into the value it replaces. When `patch` is not an object, it
+/// replaces `base` entirely. Keys and values are copied without being
+/// re-encoded.
+///
+/// ```
+/// // {"a": 1, "b": 2} patched with {"b": null, "c": 3}
+/// let base = b"\x8c\x17a\x131\x17b\x132";
+/// let patch = b"\x7c\x17b\x00\x17c\x133";
+/// let merged = serde_sqlite_jsonb::merge(base, patch)?;
+/// // {"a": 1, "c": 3}
+/// assert_eq!(merged, b"\x8c\x17a\x131\x17c\x133");
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if either blob is not a single valid JSONB element,
+/// or if the objects to merge hold keys that are not strings.
+pub fn merge(base: &[u8], patch: &[u8]) -> Result<Vec<u8>> {
+    let base = single_element(base)?;
+    let patch = single_element(patch)?;
+    let mut merged = Vec::with_capacity(base.bytes.len() + patch.bytes.len());
+    merge_into(&mut merged, Some(&base), &patch)?;
+    Ok(merged)
+}
+
+fn single_element(data: &[u8]) -> Result<Element<'_>> {
+    let (element, rest) = split_element(data)?;
+    if rest.is_empty() {
+        Ok(element)
+    } else {
+        Err(Error::TrailingCharacters)
+    }
+}
+
+/// An object entry, with its key decoded
+struct Entry<'a> {
+    key: String,
+    key_element: Element<'a>,
+    value: Element<'a>,
+}
+
+/// The entries of an object. With `last_wins`, a repeated key keeps a single
+/// entry, at the place of its first occurrence, with its last value.
+fn entries(mut payload: &[u8], last_wins: bool) -> Result<Vec<Entry<'_>>> {
+    let mut entries: Vec<Entry> = Vec::new();
+    while !payload.is_empty() {
+        let (key_element, rest) = split_element(payload)?;
+        let key = match key_element.header.element_type {
+            ElementType::Text
+            | ElementType::TextJ
+            | ElementType::Text5
+            | ElementType::TextRaw => crate::from_slice(key_element.bytes)?,
+            t => return Err(Error::NonStringKey(t)),
+        };
+        if rest.is_empty() {
+            return Err(Error::KeyWithoutValue(Some(key)));
+        }
+        let (value, rest) = split_element(rest)?;
+        payload = rest;
+        if last_wins {
+            if let Some(entry) = entries.iter_mut().find(|e| e.key == key) {
+                entry.value = value;
+                continue;
+            }
+        }
+        entries.push(Entry {
+            key,
+            key_element,
+            value,
+        });
+    }
+    Ok(entries)
+}
+
+/// Append to `out` the result of applying `patch` to `target`, where a
+/// missing target is merged like an empty object.
+fn merge_into(
+    out: &mut Vec<u8>,
+    target: Option<&Element>,
+    patch: &Element,
+) -> Result<()> {
+    if patch.header.element_type != ElementType::Object {
+        out.extend_from_slice(patch.bytes);
+        return Ok(());
+    }
+    let patch_entries = entries(patch.payload, true)?;
+    let target_entries = match target {
+        Some(t) if t.header.element_type == ElementType::Object => {
+            entries(t.payload, false)?
+        }
+        _ => Vec::new(),
+    };
+    let mut payload = Vec::new();
+    for entry in &target_entries {
+        match patch_entries.iter().find(|p| p.key == entry.key) {
+            None => {
+                payload.extend_from_slice(entry.key_element.bytes);
+                payload.extend_from_slice(entry.value.bytes);
+            }
+            Some(p) if p.value.header.element_type == ElementType::Null => {}
+            Some(p) => {
+                payload.extend_from_slice(entry.key_element.bytes);
+                merge_into(&mut payload, Some(&entry.value), &p.value)?;
+            }
+        }
+    }
+    for p in &patch_entries {
+        let is_new = !target_entries.iter().any(|e| e.key == p.key);
+        if is_new && p.value.header.element_type != ElementType::Null {
+            payload.extend_from_slice(p.key_element.bytes);
+            merge_into(&mut payload, None, &p.value)?;
+        }
+    }
+    write_minimal_header(out, ElementType::Object, payload.len() as u64);
+    out.extend_from_slice(&payload);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_override_add_and_remove_keys() {
+        // {"a": 1, "b": 2}
+        let base = b"\x8c\x17a\x131\x17b\x132";
+        // override: {"a": 3} gives {"a": 3, "b": 2}
+        assert_eq!(
+            merge(base, b"\x4c\x17a\x133").unwrap(),
+            b"\x8c\x17a\x133\x17b\x132"
+        );
+        // add: {"c": true} gives {"a": 1, "b": 2, "c": true}
+        assert_eq!(
+            merge(base, b"\x3c\x17c\x01").unwrap(),
+            b"\xbc\x17a\x131\x17b\x132\x17c\x01"
+        );
+        // remove: {"a": null, "z": null} gives {"b": 2}
+        assert_eq!(
+            merge(base, b"\x6c\x17a\x00\x17z\x00").unwrap(),
+            b"\x4c\x17b\x132"
+        );
+        // an empty patch changes nothing
+        assert_eq!(merge(base, b"\x0c").unwrap(), base);
+    }
+
+    #[test]
+    fn test_nested_objects_are_merged() {
+        // {"o": {"x": 1, "y": 2}} patched with {"o": {"y": null, "z": 3}}
+        let base = b"\xbc\x17o\x8c\x17x\x131\x17y\x132";
+        let patch = b"\xac\x17o\x7c\x17y\x00\x17z\x133";
+        // {"o": {"x": 1, "z": 3}}
+        assert_eq!(
+            merge(base, patch).unwrap(),
+            b"\xbc\x17o\x8c\x17x\x131\x17z\x133"
+        );
+        // a new object loses its null members: {"n": {"a": null, "b": 1}}
+        // patched into {} gives {"n": {"b": 1}}
+        assert_eq!(
+            merge(b"\x0c", b"\xac\x17n\x7c\x17a\x00\x17b\x131").unwrap(),
+            b"\x7c\x17n\x4c\x17b\x131"
+        );
+    }
+
+    #[test]
+    fn test_non_object_patch_or_base() {
+        // a patch that is not an object replaces the base
+        assert_eq!(
+            merge(b"\x3c\x17a\x01", b"\x2b\x131").unwrap(),
+            b"\x2b\x131"
+        );
+        assert_eq!(merge(b"\x131", b"\x00").unwrap(), b"\x00");
+        // a base that is not an object is replaced by the patched object
+        assert_eq!(
+            merge(b"\x2b\x131", b"\x6c\x17a\x01\x17b\x00").unwrap(),
+            b"\x3c\x17a\x01"
+        );
+    }
+
+    #[test]
+    fn test_merge_errors() {
+        assert!(matches!(
+            merge(b"\x0c\x00", b"\x0c"),
+            Err(Error::TrailingCharacters)
+        ));
+        assert!(matches!(
+            merge(b"\x0c", b"\x3c\x131\x01"),
+            Err(Error::NonStringKey(ElementType::Int))
+        ));
+        assert!(matches!(
+            merge(b"\x2c\x17a", b"\x0c"),
+            Err(Error::KeyWithoutValue(Some(k))) if k == "a"
+        ));
+    }
+}
@@ -0,0 +1,272 @@
+use crate::header::ElementType;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+
+/// The name of the newtype struct through which [`Number`] asks the
+/// deserializer for the original representation of a numeric element.
+pub(crate) const NUMBER_TOKEN: &str = "$serde_sqlite_jsonb::private::Number";
+
+/// The name of the newtype struct through which [`RawNumber`] reads and writes
+/// the text of a numeric element.
+pub(crate) const RAW_NUMBER_TOKEN: &str =
+    "$serde_sqlite_jsonb::private::RawNumber";
+
+/// Any numeric JSONB element, decoded without coercing it to a single Rust type.
+///
+/// Integers are kept exact: `Int` holds every integer that fits in an `i128`,
+/// `UInt` the larger ones that still fit in a `u128`, and `Text` the digits of
+/// integers that do not fit in either.
+/// Floats (`Float`, `Float5` and `BinaryFloat` elements) are decoded as `f64`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Int(i128),
+    UInt(u128),
+    Float(f64),
+    Text(String),
+}
+
+impl<'de> Deserialize<'de> for Number {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Other deserializers simply forward this to the inner number
+        deserializer.deserialize_newtype_struct(NUMBER_TOKEN, NumberVisitor)
+    }
+}
+
+struct NumberVisitor;
+
+impl<'de> Visitor<'de> for NumberVisitor {
+    type Value = Number;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number")
+    }
+
+    fn visit_newtype_struct<D>(
+        self,
+        deserializer: D,
+    ) -> Result<Number, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Number, E> {
+        Ok(Number::Int(i128::from(v)))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Number, E> {
+        Ok(Number::Int(i128::from(v)))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Number, E> {
+        Ok(Number::Int(v))
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Number, E> {
+        Ok(i128::try_from(v).map_or(Number::UInt(v), Number::Int))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Number, E> {
+        Ok(Number::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Number, E> {
+        Ok(Number::Text(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Number, E> {
+        Ok(Number::Text(v))
+    }
+}
+
+/// The text of a numeric JSONB element, exactly as it is stored in the blob.
+///
+/// Unlike [`Number`], nothing is parsed: `1.50` stays `1.50` instead of being
+/// normalized to `1.5`, and serializing a `RawNumber` writes back the same
+/// payload. `BinaryFloat` elements and the binary integers of
+/// [`Options::binary_int`](crate::Options::binary_int), which have no text,
+/// are formatted with the shortest representation that round-trips.
+///
+/// With other serializers, a `RawNumber` is written as a string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawNumber(String);
+
+impl RawNumber {
+    /// The text of the number
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The type of element this number is stored as:
+    /// `Int` or `Float` for JSON numbers, `Int5` or `Float5` for the other
+    /// JSON5 numbers, and `None` if `text` is not a number.
+    pub(crate) fn element_type(text: &str) -> Option<ElementType> {
+        let (plus, unsigned) = match text.strip_prefix('+') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('-').unwrap_or(text)),
+        };
+        if unsigned == "Infinity" || unsigned == "NaN" {
+            return Some(ElementType::Float5);
+        }
+        if let Some(hex) = unsigned
+            .strip_prefix("0x")
+            .or_else(|| unsigned.strip_prefix("0X"))
+        {
+            let valid =
+                !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit());
+            return if valid { Some(ElementType::Int5) } else { None };
+        }
+        let int_len = digit_count(unsigned);
+        let rest = &unsigned[int_len..];
+        if int_len > 1 && unsigned.starts_with('0') {
+            return None;
+        }
+        if int_len > 0 && !plus {
+            if rest.is_empty() {
+                return Some(ElementType::Int);
+            }
+            if is_json_float_suffix(rest) {
+                return Some(ElementType::Float);
+            }
+        }
+        // JSON5 also allows a leading '+', and a leading or trailing '.'
+        let (frac_len, rest) = match rest.strip_prefix('.') {
+            Some(frac) => (digit_count(frac), &frac[digit_count(frac)..]),
+            None => (0, rest),
+        };
+        let valid = int_len + frac_len > 0
+            && (rest.is_empty() || is_json_float_suffix(rest));
+        if valid {
+            Some(ElementType::Float5)
+        } else {
+            None
+        }
+    }
+}
+
+/// The number of ASCII digits at the start of `s`
+fn digit_count(s: &str) -> usize {
+    s.bytes().take_while(u8::is_ascii_digit).count()
+}
+
+/// Whether `s` is a valid JSON fraction and/or exponent, such as `.5e-3`
+fn is_json_float_suffix(s: &str) -> bool {
+    let mut rest = s;
+    if let Some(frac) = rest.strip_prefix('.') {
+        let n = digit_count(frac);
+        if n == 0 {
+            return false;
+        }
+        rest = &frac[n..];
+    }
+    if let Some(exp) = rest.strip_prefix(|c| c == 'e' || c == 'E') {
+        let exp = exp.strip_prefix(|c| c == '+' || c == '-').unwrap_or(exp);
+        let n = digit_count(exp);
+        if n == 0 {
+            return false;
+        }
+        rest = &exp[n..];
+    }
+    rest.is_empty() && !s.is_empty()
+}
+
+/// The text of a float, with infinities written as JSON5 `Infinity`
+pub(crate) fn float_text(v: f64) -> String {
+    if v.is_infinite() {
+        let sign = if v < 0.0 { "-" } else { "" };
+        format!("{sign}Infinity")
+    } else {
+        v.to_string()
+    }
+}
+
+impl fmt::Display for RawNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<RawNumber> for String {
+    fn from(n: RawNumber) -> String {
+        n.0
+    }
+}
+
+impl Serialize for RawNumber {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(RAW_NUMBER_TOKEN, &self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawNumber {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer
+            .deserialize_newtype_struct(RAW_NUMBER_TOKEN, RawNumberVisitor)
+    }
+}
+
+struct RawNumberVisitor;
+
+impl<'de> Visitor<'de> for RawNumberVisitor {
+    type Value = RawNumber;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number")
+    }
+
+    fn visit_newtype_struct<D>(
+        self,
+        deserializer: D,
+    ) -> Result<RawNumber, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<RawNumber, E> {
+        Ok(RawNumber(v.to_string()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<RawNumber, E> {
+        Ok(RawNumber(v.to_string()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<RawNumber, E> {
+        Ok(RawNumber(float_text(v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<RawNumber, E> {
+        self.visit_string(v.to_owned())
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<RawNumber, E> {
+        if RawNumber::element_type(&v).is_none() {
+            return Err(E::invalid_value(de::Unexpected::Str(&v), &self));
+        }
+        Ok(RawNumber(v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_invalid_raw_number() {
+        assert!(crate::to_vec(&RawNumber("abc".into())).is_err());
+        assert!(crate::to_vec(&RawNumber("1.".into())).is_ok());
+    }
+}
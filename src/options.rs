@@ -0,0 +1,177 @@
+use crate::bytes::BytesEncoding;
+use crate::header::ElementType;
+
+/// Options that control how values are serialized and deserialized.
+///
+/// `Options` is `Copy`, and is passed by value to every nested serializer
+/// and deserializer. Adding a field that is not `Copy` means revisiting this.
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Options {
+    /// Store floats as little-endian IEEE 754 `BinaryFloat` elements
+    /// instead of text. This is not understood by `SQLite`.
+    ///
+    /// An `f32` takes 4 bytes and an `f64` takes 8: the size of the payload
+    /// tells them apart when decoding, so both read back exactly.
+    pub binary_float: bool,
+    /// Store integers that fit in an `i64` as little-endian binary integers
+    /// of 1, 2, 4 or 8 bytes instead of text. Larger integers are still
+    /// stored as text.
+    ///
+    /// Binary integers use the `Reserved14` type code, which `SQLite`
+    /// reserves for future expansion: `SQLite` rejects such blobs as
+    /// malformed, and could give the code another meaning in a later
+    /// version. Only use this for data that is never read by `SQLite`.
+    /// No other option asks for `SQLite` compatibility, so none of them
+    /// conflicts with this one: nothing checks that the output stays
+    /// readable by `SQLite`.
+    pub binary_int: bool,
+    /// Sort the entries of every object by key, so that maps with the same
+    /// contents always serialize to the same bytes, whatever their iteration order.
+    pub sort_keys: bool,
+    /// Whether types that have both a textual and a compact representation
+    /// (such as `std::net::IpAddr`) should use the textual one.
+    /// Defaults to `true`.
+    pub human_readable: bool,
+    /// Fail deserialization with [`Error::DuplicateKey`](crate::Error::DuplicateKey)
+    /// when an object contains the same key twice, instead of
+    /// letting the deserialized type decide (maps usually keep the last value).
+    ///
+    /// This does not apply to arrays: an array decoded into a set, such as a
+    /// `BTreeSet`, silently keeps one copy of each repeated element, since
+    /// serde reads sets like any other sequence. To reject repeated
+    /// elements, decode the array into a `Vec` and compare its length with
+    /// the length of the set.
+    pub reject_duplicate_keys: bool,
+    /// Fail deserialization with [`Error::NonMinimalHeader`](crate::Error::NonMinimalHeader)
+    /// when an element header is longer than needed for its payload size.
+    /// `SQLite` accepts such headers, but never produces them.
+    pub require_minimal_headers: bool,
+    /// Accept numeric elements (`Int`, `Float`, `Int5` and `Float5`) when
+    /// deserializing a string, which then holds the number as written in the blob.
+    pub numbers_as_strings: bool,
+    /// Store enum variants that hold data as a two-element array
+    /// `["Variant", value]` instead of an object `{"Variant": value}`,
+    /// and read them back from that form. Unit variants are still plain strings.
+    pub enum_as_tuple: bool,
+    /// Chooses the element type of each serialized string. The string is
+    /// escaped when `TextJ` or `Text5` is chosen, and stored as it is for
+    /// `Text` and `TextRaw`; any other type is an error. `Text` must only be
+    /// chosen for strings that need no escaping in JSON.
+    ///
+    /// Defaults to [`Options::unescaped_strings`].
+    /// [`Options::sqlite_strings`] makes the same choice as `SQLite`.
+    pub escape_policy: fn(&str) -> ElementType,
+    /// How values that serialize as bytes are stored. With a text encoding,
+    /// such values are also read back from strings, as well as from arrays.
+    pub bytes_encoding: BytesEncoding,
+    /// Make [`to_vec_with_options`](crate::to_vec_with_options) append a
+    /// big-endian CRC-32 of the JSONB value after it. Such data must be read
+    /// with [`from_slice_checked`](crate::from_slice_checked), which verifies
+    /// and strips the checksum: for other functions, it is trailing data.
+    pub append_crc32: bool,
+    /// How NaN and infinite floats are serialized. Defaults to
+    /// [`NonFiniteFloats::Sqlite`].
+    pub non_finite_floats: NonFiniteFloats,
+    /// Fail deserialization with
+    /// [`Error::NonFiniteFloat`](crate::Error::NonFiniteFloat) when a float
+    /// decodes to NaN or an infinity, such as the JSON5 `Infinity` or the
+    /// `9e999` that `SQLite` stores for it, instead of returning it.
+    pub reject_non_finite: bool,
+    /// Serialize map keys that are not strings, such as integers, floats
+    /// and booleans, as the string of their value, like `serde_json` does.
+    /// Otherwise they are stored as they are, which makes an object that
+    /// `SQLite` rejects.
+    pub stringify_map_keys: bool,
+    /// Fail deserialization with
+    /// [`Error::SizeLimitExceeded`](crate::Error::SizeLimitExceeded) when
+    /// the value would take more than this many bytes to decode, to bound
+    /// the memory used by untrusted input, even when it is streamed.
+    ///
+    /// Every element read counts the size of its header and of its payload,
+    /// except arrays and objects, whose elements count on their own.
+    /// The limit applies to the whole value, nested elements included.
+    /// Ignored arrays and objects only count their header, since their
+    /// payload is skipped without being read.
+    pub max_total_size: Option<u64>,
+}
+
+/// What to do with NaN and infinite floats, which JSON cannot represent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonFiniteFloats {
+    /// Store them as `SQLite` does when it reads JSON5 `NaN` and `Infinity`:
+    /// NaN as `null`, and infinities as the `Float` elements `9e999` and
+    /// `-9e999`, which overflow back to infinities. This is the default.
+    /// NaN map keys are rejected, since `null` cannot be a key.
+    #[default]
+    Sqlite,
+    /// Fail with [`Error::NonFiniteFloat`](crate::Error::NonFiniteFloat)
+    Reject,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            binary_float: false,
+            binary_int: false,
+            sort_keys: false,
+            human_readable: true,
+            reject_duplicate_keys: false,
+            require_minimal_headers: false,
+            numbers_as_strings: false,
+            enum_as_tuple: false,
+            escape_policy: Options::unescaped_strings,
+            bytes_encoding: BytesEncoding::Array,
+            append_crc32: false,
+            non_finite_floats: NonFiniteFloats::Sqlite,
+            reject_non_finite: false,
+            stringify_map_keys: false,
+            max_total_size: None,
+        }
+    }
+}
+
+impl Options {
+    /// An [`escape_policy`](Options::escape_policy) that stores every string
+    /// as `TextRaw`, with its characters as they are.
+    #[must_use]
+    pub fn raw_strings(_: &str) -> ElementType {
+        ElementType::TextRaw
+    }
+
+    /// An [`escape_policy`](Options::escape_policy) that stores strings with
+    /// their characters as they are: as `Text`, like `SQLite` does, when they
+    /// need no escaping, and as `TextRaw` otherwise.
+    #[must_use]
+    pub fn unescaped_strings(s: &str) -> ElementType {
+        if needs_escaping(s) {
+            ElementType::TextRaw
+        } else {
+            ElementType::Text
+        }
+    }
+
+    /// An [`escape_policy`](Options::escape_policy) that stores strings the
+    /// way `SQLite`'s JSON functions do: as `Text` when they need no escaping,
+    /// and as escaped `TextJ` otherwise.
+    #[must_use]
+    pub fn sqlite_strings(s: &str) -> ElementType {
+        if needs_escaping(s) {
+            ElementType::TextJ
+        } else {
+            ElementType::Text
+        }
+    }
+}
+
+/// Whether a string holds characters that JSON requires to be escaped:
+/// quotes, backslashes and control characters
+fn needs_escaping(s: &str) -> bool {
+    s.bytes().any(|b| b == b'"' || b == b'\\' || b < 0x20)
+}
+
+// Options are copied into every nested (de)serializer, so they must stay cheap
+const _: fn() = || {
+    fn assert_copy<T: Copy>() {}
+    assert_copy::<Options>();
+};
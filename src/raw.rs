@@ -0,0 +1,200 @@
+use crate::error::Error;
+use crate::extract::split_element;
+use crate::header::{parse_header, write_minimal_header, ElementType};
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+
+/// The name of the newtype struct through which [`RawElement`] reads and
+/// writes a complete encoded element.
+pub(crate) const RAW_ELEMENT_TOKEN: &str =
+    "$serde_sqlite_jsonb::private::RawElement";
+
+/// A single JSONB element, as its type and undecoded payload.
+///
+/// Serializing a `RawElement` writes a minimal header followed by the payload,
+/// without checking that the payload is valid for the element type.
+/// Deserializing one reads the next element header and its payload as-is.
+/// For arrays and objects, the payload holds the encoded children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawElement {
+    pub element_type: ElementType,
+    pub payload: Vec<u8>,
+}
+
+impl RawElement {
+    /// The element encoded with a minimal header
+    fn encode(&self) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(9 + self.payload.len());
+        write_minimal_header(
+            &mut encoded,
+            self.element_type,
+            self.payload.len() as u64,
+        );
+        encoded.extend_from_slice(&self.payload);
+        encoded
+    }
+}
+
+/// Serializes as bytes, as `serde_bytes` would.
+struct EncodedElement<'a>(&'a [u8]);
+
+impl Serialize for EncodedElement<'_> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl Serialize for RawElement {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(
+            RAW_ELEMENT_TOKEN,
+            &EncodedElement(&self.encode()),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for RawElement {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer
+            .deserialize_newtype_struct(RAW_ELEMENT_TOKEN, RawElementVisitor)
+    }
+}
+
+struct RawElementVisitor;
+
+impl<'de> Visitor<'de> for RawElementVisitor {
+    type Value = RawElement;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an encoded jsonb element")
+    }
+
+    fn visit_newtype_struct<D>(
+        self,
+        deserializer: D,
+    ) -> Result<RawElement, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_byte_buf(self)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<RawElement, E> {
+        let (header, header_len) = parse_header(v).map_err(E::custom)?;
+        let payload = &v[header_len..];
+        if payload.len() as u64 != header.payload_size {
+            return Err(E::invalid_length(
+                payload.len(),
+                &"a payload of the size announced by the header",
+            ));
+        }
+        Ok(RawElement {
+            element_type: header.element_type,
+            payload: payload.to_vec(),
+        })
+    }
+}
+
+/// A complete JSONB value, header included, kept encoded.
+///
+/// Unlike [`RawElement`], the bytes can be decoded later with
+/// [`from_slice`](crate::from_slice). Serializing a `RawJsonb` writes them
+/// as they are, whatever the [`Options`](crate::Options), and deserializing
+/// one captures the next element.
+/// As a struct field, it defers decoding part of a value, for instance until
+/// another field tells which type it holds.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawJsonb(Vec<u8>);
+
+impl RawJsonb {
+    /// The encoded value
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The encoded value
+    #[must_use]
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Wrap bytes that are known to hold exactly one element
+    pub(crate) fn from_element_bytes(bytes: &[u8]) -> Self {
+        RawJsonb(bytes.to_vec())
+    }
+}
+
+/// Checks that the bytes hold exactly one element, whose payload has the
+/// size announced by its header.
+impl TryFrom<Vec<u8>> for RawJsonb {
+    type Error = Error;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Error> {
+        let (_, rest) = split_element(&bytes)?;
+        if !rest.is_empty() {
+            return Err(Error::TrailingCharacters);
+        }
+        Ok(RawJsonb(bytes))
+    }
+}
+
+impl Serialize for RawJsonb {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(
+            RAW_ELEMENT_TOKEN,
+            &EncodedElement(&self.0),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for RawJsonb {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer
+            .deserialize_newtype_struct(RAW_ELEMENT_TOKEN, RawJsonbVisitor)
+    }
+}
+
+struct RawJsonbVisitor;
+
+impl<'de> Visitor<'de> for RawJsonbVisitor {
+    type Value = RawJsonb;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an encoded jsonb element")
+    }
+
+    fn visit_newtype_struct<D>(
+        self,
+        deserializer: D,
+    ) -> Result<RawJsonb, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_byte_buf(self)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<RawJsonb, E> {
+        self.visit_byte_buf(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<RawJsonb, E> {
+        RawJsonb::try_from(v).map_err(E::custom)
+    }
+}
@@ -1,15 +1,30 @@
 use crate::{
     error::{Error, Result},
-    header::ElementType,
+    header::{encode_minimal_header, parse_header, ElementType},
+    log::LENGTH_PREFIX_SIZE,
+    options::{NonFiniteFloats, Options},
 };
 use serde::ser::{self, Serialize};
+use std::borrow::Cow;
 use std::io::Write;
 
-#[derive(Debug, Default, Clone)]
-pub struct Options {
-    pub binary_float: bool,
-}
+#[cfg(feature = "half")]
+mod float16;
+mod map_key;
+mod raw_bytes;
+mod seekable;
+
+#[cfg(feature = "half")]
+use float16::F16Serializer;
+use map_key::MapKeySerializer;
+use raw_bytes::RawBytesSerializer;
+pub use seekable::{to_seekable_writer, to_seekable_writer_with_options};
 
+/// A structure that serializes Rust values into `SQLite` JSONB data.
+///
+/// Serializing several values into the same serializer appends them one after
+/// the other, producing a stream of concatenated JSONB elements that can be read
+/// back one by one with a single [`Deserializer`](crate::Deserializer).
 #[derive(Debug)]
 pub struct Serializer {
     buffer: Vec<u8>,
@@ -17,12 +32,25 @@ pub struct Serializer {
 }
 
 impl Serializer {
+    #[must_use]
     pub fn from_options(options: Options) -> Self {
         Self {
             buffer: Vec::new(),
             options,
         }
     }
+
+    /// Consume the serializer, returning the JSONB bytes written so far
+    #[must_use]
+    pub fn into_vec(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    /// The JSONB bytes written so far
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer
+    }
 }
 
 /// Serialize a value into a JSONB byte array
@@ -34,25 +62,125 @@ pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
 where
     T: Serialize,
 {
-    let mut serializer = Serializer::from_options(Default::default());
+    let mut serializer = Serializer::from_options(Options::default());
     value.serialize(&mut serializer)?;
-    Ok(serializer.buffer)
+    Ok(serializer.into_vec())
 }
 
+/// Serialize a value into a JSONB byte array, using the given options
+///
+/// # Errors
+///
+/// Returns an error if serialization fails.
 pub fn to_vec_with_options<T>(value: &T, options: Options) -> Result<Vec<u8>>
 where
     T: Serialize,
 {
     let mut serializer = Serializer::from_options(options);
-    value.serialize(&mut serializer).unwrap();
-    Ok(serializer.buffer)
+    value.serialize(&mut serializer)?;
+    let mut blob = serializer.into_vec();
+    if options.append_crc32 {
+        let checksum = crate::checksum::crc32(&blob);
+        blob.extend_from_slice(&checksum.to_be_bytes());
+    }
+    Ok(blob)
+}
+
+/// Serialize a value as JSONB into a writer, such as a file or a socket.
+///
+/// The header of an array or object holds the size of its payload, so the
+/// whole value is built in memory first, then written with a single
+/// `write_all`: this saves a copy, not memory. To write a large value
+/// without holding it all in memory, use
+/// [`to_seekable_writer`](crate::to_seekable_writer) with a writer that can
+/// seek back to the headers.
+///
+/// # Errors
+///
+/// Returns an error if serialization fails, or if the writer fails.
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: Write,
+    T: Serialize,
+{
+    to_writer_with_options(writer, value, Options::default())
+}
+
+/// Serialize a value as JSONB into a writer, using the given options.
+/// See [`to_writer`] for how the value is buffered.
+///
+/// # Errors
+///
+/// Returns an error if serialization fails, or if the writer fails.
+pub fn to_writer_with_options<W, T>(
+    mut writer: W,
+    value: &T,
+    options: Options,
+) -> Result<()>
+where
+    W: Write,
+    T: Serialize,
+{
+    let blob = to_vec_with_options(value, options)?;
+    writer.write_all(&blob)?;
+    Ok(())
+}
+
+/// Serialize a value into a JSONB byte array prefixed by its length, as a
+/// 4-byte big-endian integer. Such records can be concatenated, and read back
+/// one by one with [`from_length_prefixed_reader`](crate::from_length_prefixed_reader).
+///
+/// # Errors
+///
+/// Returns an error if serialization fails, or if the JSONB value is
+/// larger than 4 GiB.
+pub fn to_length_prefixed_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: Serialize,
+{
+    to_length_prefixed_vec_with_options(value, Options::default())
+}
+
+/// Serialize a value into a JSONB byte array prefixed by its length, using
+/// the given options. See [`to_length_prefixed_vec`] for the framing.
+///
+/// No checksum is appended with [`Options::append_crc32`], since the
+/// length prefix already delimits the value.
+///
+/// # Errors
+///
+/// Returns an error if serialization fails, or if the JSONB value is
+/// larger than 4 GiB.
+pub fn to_length_prefixed_vec_with_options<T>(
+    value: &T,
+    options: Options,
+) -> Result<Vec<u8>>
+where
+    T: Serialize,
+{
+    let mut serializer = Serializer::from_options(options);
+    serializer
+        .buffer
+        .extend_from_slice(&[0; LENGTH_PREFIX_SIZE]);
+    value.serialize(&mut serializer)?;
+    let mut framed = serializer.into_vec();
+    let len = u32::try_from(framed.len() - LENGTH_PREFIX_SIZE)
+        .map_err(Error::IntConversion)?;
+    framed[..LENGTH_PREFIX_SIZE].copy_from_slice(&len.to_be_bytes());
+    Ok(framed)
 }
 
+/// A rough guess of the size of an array element (header and payload),
+/// used to reserve space when the number of elements is known in advance.
+const ESTIMATED_ELEMENT_SIZE: usize = 4;
+
 /// Helper struct to write JSONB data, then finalize the header to its minimal size
 pub struct JsonbWriter<'a> {
     buffer: &'a mut Vec<u8>,
     header_start: u64,
     options: Options,
+    /// Where each object entry starts, when keys need to be sorted
+    entry_starts: Vec<usize>,
 }
 
 impl<'a> JsonbWriter<'a> {
@@ -67,45 +195,95 @@ impl<'a> JsonbWriter<'a> {
             buffer,
             header_start,
             options,
+            entry_starts: Vec::new(),
         }
     }
+    /// Like [`JsonbWriter::new`], but reserves room for `len` elements
+    /// of `estimated_element_size` bytes each, when the length is known.
+    fn with_len_hint(
+        buffer: &'a mut Vec<u8>,
+        element_type: ElementType,
+        options: Options,
+        len: Option<usize>,
+        estimated_element_size: usize,
+    ) -> Self {
+        // Do not trust huge hints blindly
+        const MAX_RESERVED: usize = 1 << 20;
+        if let Some(len) = len {
+            let additional =
+                len.saturating_mul(estimated_element_size).min(MAX_RESERVED);
+            buffer.reserve(9 + additional);
+        }
+        Self::new(buffer, element_type, options)
+    }
+
     fn finalize(self) {
         let header_start = usize::try_from(self.header_start)
             .expect("header start out of range");
         let data_start = header_start + 9;
         let data_end = self.buffer.len();
-        let payload_size = data_end - data_start;
-        let header = &mut self.buffer[header_start..header_start + 9];
-        let head_len = if payload_size <= 11 {
-            header[0] |= (u8::try_from(payload_size).unwrap()) << 4;
-            1
-        } else if payload_size <= 0xff {
-            header[0] |= 0xc0;
-            header[1] = u8::try_from(payload_size).unwrap();
-            2
-        } else if payload_size <= 0xffff {
-            header[0] |= 0xd0;
-            header[1..3].copy_from_slice(
-                &(u16::try_from(payload_size).unwrap()).to_be_bytes(),
-            );
-            3
-        } else if payload_size <= 0xffff_ffff {
-            header[0] |= 0xe0;
-            header[1..5].copy_from_slice(
-                &(u32::try_from(payload_size).unwrap()).to_be_bytes(),
-            );
-            5
-        } else {
-            header[0] |= 0xf0;
-            header[1..9].copy_from_slice(&payload_size.to_be_bytes());
-            9
-        };
-        if head_len < 9 {
-            self.buffer
-                .copy_within(data_start..data_end, header_start + head_len);
-            self.buffer.truncate(header_start + head_len + payload_size);
+        if !self.entry_starts.is_empty() {
+            sort_entries(self.buffer, &self.entry_starts);
+        }
+        write_final_header(
+            self.buffer,
+            header_start,
+            (data_end - data_start) as u64,
+        );
+    }
+}
+
+/// Replace the 9-byte placeholder header at `header_start` by the smallest
+/// header for `payload_size`, moving the payload that follows it if needed.
+fn write_final_header(
+    buffer: &mut Vec<u8>,
+    header_start: usize,
+    payload_size: u64,
+) {
+    let element_type = ElementType::from(buffer[header_start]);
+    let (header, head_len) = encode_minimal_header(element_type, payload_size);
+    if head_len < 9 {
+        let data_start = header_start + 9;
+        let payload_size =
+            usize::try_from(payload_size).expect("the payload is in memory");
+        buffer.copy_within(
+            data_start..data_start + payload_size,
+            header_start + head_len,
+        );
+        buffer.truncate(header_start + head_len + payload_size);
+    }
+    buffer[header_start..header_start + head_len]
+        .copy_from_slice(&header[..head_len]);
+}
+
+/// Reorder the object entries starting at each of `entry_starts` (and ending
+/// at the end of the buffer) by key. Each key stays paired with its value.
+fn sort_entries(buffer: &mut [u8], entry_starts: &[usize]) {
+    let entries_start = entry_starts[0];
+    let mut entries: Vec<&[u8]> = entry_starts
+        .iter()
+        .zip(entry_starts[1..].iter().chain([&buffer.len()]))
+        .map(|(&start, &end)| &buffer[start..end])
+        .collect();
+    entries.sort_by_cached_key(|entry| entry_key(entry));
+    let sorted = entries.concat();
+    buffer[entries_start..].copy_from_slice(&sorted);
+}
+
+/// The key at the start of an object entry, as the UTF-8 bytes of the
+/// string it holds: escaped keys are compared by their decoded text.
+fn entry_key(entry: &[u8]) -> Cow<'_, [u8]> {
+    let (header, header_len) =
+        parse_header(entry).expect("we serialized a valid key header");
+    let payload_size = usize::try_from(header.payload_size)
+        .expect("the key was serialized in memory");
+    if matches!(header.element_type, ElementType::TextJ | ElementType::Text5) {
+        let key = &entry[..header_len + payload_size];
+        if let Ok(decoded) = crate::from_slice::<String>(key) {
+            return Cow::Owned(decoded.into_bytes());
         }
     }
+    Cow::Borrowed(&entry[header_len..header_len + payload_size])
 }
 
 impl Serializer {
@@ -118,11 +296,8 @@ impl Serializer {
         element_type: ElementType,
         data: impl std::fmt::Display,
     ) -> Result<()> {
-        let mut w = JsonbWriter::new(
-            &mut self.buffer,
-            element_type,
-            self.options.clone(),
-        );
+        let mut w =
+            JsonbWriter::new(&mut self.buffer, element_type, self.options);
         write!(&mut w.buffer, "{data}")?;
         w.finalize();
         Ok(())
@@ -133,15 +308,103 @@ impl Serializer {
         element_type: ElementType,
         data: impl AsRef<[u8]>,
     ) -> Result<()> {
-        let w = JsonbWriter::new(
-            &mut self.buffer,
-            element_type,
-            self.options.clone(),
-        );
+        let w = JsonbWriter::new(&mut self.buffer, element_type, self.options);
         w.buffer.write_all(data.as_ref())?;
         w.finalize();
         Ok(())
     }
+
+    /// Write an integer as a binary `Reserved14` element with `binary_int`
+    /// when it fits in an `i64`, using the smallest of 1, 2, 4 or 8 bytes,
+    /// and as text otherwise
+    fn write_int<I>(&mut self, v: I) -> Result<()>
+    where
+        I: std::fmt::Display + TryInto<i64> + Copy,
+    {
+        match v.try_into() {
+            Ok(i) if self.options.binary_int => {
+                if let Ok(i) = i8::try_from(i) {
+                    self.write_binary(ElementType::Reserved14, i.to_le_bytes())
+                } else if let Ok(i) = i16::try_from(i) {
+                    self.write_binary(ElementType::Reserved14, i.to_le_bytes())
+                } else if let Ok(i) = i32::try_from(i) {
+                    self.write_binary(ElementType::Reserved14, i.to_le_bytes())
+                } else {
+                    self.write_binary(ElementType::Reserved14, i.to_le_bytes())
+                }
+            }
+            _ => self.write_displayable(ElementType::Int, v),
+        }
+    }
+
+    /// Write a float as a `BinaryFloat` with `binary_float`, and as text otherwise
+    fn write_float<F>(&mut self, v: F, binary: impl AsRef<[u8]>) -> Result<()>
+    where
+        F: std::fmt::Display + Into<f64> + Copy,
+    {
+        check_non_finite(v.into(), self.options)?;
+        if self.options.binary_float {
+            self.write_binary(ElementType::BinaryFloat, binary)
+        } else if v.into().is_nan() {
+            self.write_header_nodata(ElementType::Null);
+            Ok(())
+        } else {
+            self.write_displayable(ElementType::Float, FloatText(v))
+        }
+    }
+}
+
+/// Fail if `v` is NaN or infinite and [`Options::non_finite_floats`] rejects it
+fn check_non_finite(v: f64, options: Options) -> Result<()> {
+    match options.non_finite_floats {
+        NonFiniteFloats::Reject if !v.is_finite() => {
+            Err(Error::NonFiniteFloat(v))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Displays a float that is not NaN as JSON, with infinities written as
+/// `9e999` and `-9e999` like `SQLite` does
+struct FloatText<F>(F);
+
+impl<F> std::fmt::Display for FloatText<F>
+where
+    F: std::fmt::Display + Into<f64> + Copy,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let v: f64 = self.0.into();
+        if v.is_infinite() {
+            f.write_str(if v > 0.0 { "9e999" } else { "-9e999" })
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+/// Displays a string with the escapes it needs inside a JSON string
+struct JsonEscaped<'a>(&'a str);
+
+impl std::fmt::Display for JsonEscaped<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use std::fmt::Write;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                '\x08' => f.write_str("\\b")?,
+                '\x0c' => f.write_str("\\f")?,
+                c if u32::from(c) < 0x20 => {
+                    write!(f, "\\u{:04x}", u32::from(c))?;
+                }
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<'a> ser::Serializer for &'a mut Serializer {
@@ -163,6 +426,10 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 
     type SerializeStructVariant = EnumVariantSerializer<'a>;
 
+    fn is_human_readable(&self) -> bool {
+        self.options.human_readable
+    }
+
     fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
         self.write_header_nodata(if v {
             ElementType::True
@@ -173,63 +440,77 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
-        self.write_displayable(ElementType::Int, v)
+        self.write_int(v)
     }
 
     fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
-        self.write_displayable(ElementType::Int, v)
+        self.write_int(v)
     }
 
     fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
-        self.write_displayable(ElementType::Int, v)
+        self.write_int(v)
     }
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
-        self.write_displayable(ElementType::Int, v)
+        self.write_int(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok> {
+        self.write_int(v)
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
-        self.write_displayable(ElementType::Int, v)
+        self.write_int(v)
     }
 
     fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
-        self.write_displayable(ElementType::Int, v)
+        self.write_int(v)
     }
 
     fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
-        self.write_displayable(ElementType::Int, v)
+        self.write_int(v)
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
-        self.write_displayable(ElementType::Int, v)
+        self.write_int(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok> {
+        self.write_int(v)
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
-        if !self.options.binary_float {
-            self.write_displayable(ElementType::Float, v)
-        } else {
-            self.write_binary(ElementType::BinaryFloat, v.to_le_bytes())
-        }
+        self.write_float(v, v.to_le_bytes())
     }
 
     fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
-        if !self.options.binary_float {
-            self.write_displayable(ElementType::Float, v)
-        } else {
-            self.write_binary(ElementType::BinaryFloat, v.to_le_bytes())
-        }
+        self.write_float(v, v.to_le_bytes())
     }
 
     fn serialize_char(self, v: char) -> Result<Self::Ok> {
-        self.write_displayable(ElementType::TextRaw, v)
+        // With the default escape policy, characters such as '\n' are stored
+        // unescaped as TextRaw, which SQLite escapes when it outputs JSON
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok> {
-        self.write_displayable(ElementType::TextRaw, v)
+        match (self.options.escape_policy)(v) {
+            t @ (ElementType::Text | ElementType::TextRaw) => {
+                self.write_displayable(t, v)
+            }
+            t @ (ElementType::TextJ | ElementType::Text5) => {
+                self.write_displayable(t, JsonEscaped(v))
+            }
+            t => Err(Error::expected("string", t)),
+        }
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
         use serde::ser::SerializeSeq;
+        if let Some(text) = self.options.bytes_encoding.encode(v) {
+            // base64 and hex digits never need escaping
+            return self.write_displayable(ElementType::Text, text);
+        }
         let mut s = self.serialize_seq(Some(v.len()))?;
         for byte in v {
             s.serialize_element(byte)?;
@@ -268,10 +549,27 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 
     fn serialize_newtype_struct<T: ?Sized + Serialize>(
         self,
-        _name: &'static str,
-        _value: &T,
+        name: &'static str,
+        value: &T,
     ) -> Result<Self::Ok> {
-        self.serialize_unit()
+        #[cfg(feature = "half")]
+        if name == crate::float16::F16_TOKEN {
+            return value.serialize(F16Serializer(self));
+        }
+        if name == crate::raw::RAW_ELEMENT_TOKEN {
+            return value.serialize(RawBytesSerializer(&mut self.buffer));
+        }
+        if name == crate::number::RAW_NUMBER_TOKEN {
+            let mut inner = Serializer::from_options(self.options);
+            value.serialize(&mut inner)?;
+            let text: String = crate::from_slice(&inner.buffer)?;
+            let element_type = crate::number::RawNumber::element_type(&text)
+                .ok_or_else(|| {
+                    Error::Message(format!("invalid number: {text:?}"))
+                })?;
+            return self.write_displayable(element_type, text);
+        }
+        value.serialize(self)
     }
 
     fn serialize_newtype_variant<T: ?Sized + Serialize>(
@@ -281,26 +579,30 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         variant: &'static str,
         value: &T,
     ) -> Result<Self::Ok> {
+        if self.options.enum_as_tuple {
+            let mut seq = self.serialize_seq(Some(2))?;
+            serde::ser::SerializeSeq::serialize_element(&mut seq, variant)?;
+            serde::ser::SerializeSeq::serialize_element(&mut seq, value)?;
+            return serde::ser::SerializeSeq::end(seq);
+        }
         let mut map = self.serialize_map(Some(1))?;
         serde::ser::SerializeMap::serialize_key(&mut map, variant)?;
         serde::ser::SerializeMap::serialize_value(&mut map, value)?;
         serde::ser::SerializeMap::end(map)
     }
 
-    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        Ok(JsonbWriter::new(
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(JsonbWriter::with_len_hint(
             &mut self.buffer,
             ElementType::Array,
-            self.options.clone(),
+            self.options,
+            len,
+            ESTIMATED_ELEMENT_SIZE,
         ))
     }
 
-    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        Ok(JsonbWriter::new(
-            &mut self.buffer,
-            ElementType::Array,
-            self.options.clone(),
-        ))
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
     }
 
     fn serialize_tuple_struct(
@@ -318,19 +620,21 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        Ok(EnumVariantSerializer::new(
+        EnumVariantSerializer::new(
             &mut self.buffer,
             variant,
             ElementType::Array,
-            self.options.clone(),
-        ))
+            self.options,
+        )
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        Ok(JsonbWriter::new(
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(JsonbWriter::with_len_hint(
             &mut self.buffer,
             ElementType::Object,
-            self.options.clone(),
+            self.options,
+            len,
+            2 * ESTIMATED_ELEMENT_SIZE,
         ))
     }
 
@@ -349,12 +653,12 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        Ok(EnumVariantSerializer::new(
+        EnumVariantSerializer::new(
             &mut self.buffer,
             variant,
             ElementType::Object,
-            self.options.clone(),
-        ))
+            self.options,
+        )
     }
 }
 
@@ -366,7 +670,7 @@ impl ser::SerializeSeq for JsonbWriter<'_> {
         &mut self,
         value: &T,
     ) -> Result<()> {
-        let mut serializer = Serializer::from_options(self.options.clone());
+        let mut serializer = Serializer::from_options(self.options);
         std::mem::swap(self.buffer, &mut serializer.buffer);
         let r = value.serialize(&mut serializer);
         std::mem::swap(self.buffer, &mut serializer.buffer);
@@ -428,19 +732,24 @@ impl<'a> EnumVariantSerializer<'a> {
         variant: &'static str,
         inner_element_type: ElementType,
         options: Options,
-    ) -> Self {
+    ) -> Result<Self> {
+        // {"variant": inner}, or ["variant", inner] with `enum_as_tuple`
+        let outer_element_type = if options.enum_as_tuple {
+            ElementType::Array
+        } else {
+            ElementType::Object
+        };
         let mut map_jsonb_writer =
-            JsonbWriter::new(buffer, ElementType::Object, options.clone());
-        ser::SerializeMap::serialize_key(&mut map_jsonb_writer, variant)
-            .unwrap();
+            JsonbWriter::new(buffer, outer_element_type, options);
+        ser::SerializeSeq::serialize_element(&mut map_jsonb_writer, variant)?;
         let map_header_start = map_jsonb_writer.header_start;
         let inner_jsonb_writer =
-            JsonbWriter::new(buffer, inner_element_type, options.clone());
-        Self {
+            JsonbWriter::new(buffer, inner_element_type, options);
+        Ok(Self {
             map_header_start,
             inner_jsonb_writer,
             options,
-        }
+        })
     }
 }
 
@@ -462,12 +771,14 @@ impl ser::SerializeTupleVariant for EnumVariantSerializer<'_> {
         ser::SerializeSeq::end(JsonbWriter {
             buffer: self.inner_jsonb_writer.buffer,
             header_start: self.inner_jsonb_writer.header_start,
-            options: self.options.clone(),
+            options: self.options,
+            entry_starts: self.inner_jsonb_writer.entry_starts,
         })?;
         ser::SerializeMap::end(JsonbWriter {
             buffer: self.inner_jsonb_writer.buffer,
             header_start: self.map_header_start,
-            options: self.options.clone(),
+            options: self.options,
+            entry_starts: Vec::new(),
         })
     }
 }
@@ -477,7 +788,17 @@ impl ser::SerializeMap for JsonbWriter<'_> {
     type Error = Error;
 
     fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
-        <Self as ser::SerializeSeq>::serialize_element(self, key)
+        if self.options.sort_keys {
+            self.entry_starts.push(self.buffer.len());
+        }
+        if !self.options.stringify_map_keys {
+            return <Self as ser::SerializeSeq>::serialize_element(self, key);
+        }
+        let mut serializer = Serializer::from_options(self.options);
+        std::mem::swap(self.buffer, &mut serializer.buffer);
+        let r = key.serialize(MapKeySerializer(&mut serializer));
+        std::mem::swap(self.buffer, &mut serializer.buffer);
+        r
     }
 
     fn serialize_value<T: ?Sized + Serialize>(
@@ -487,6 +808,28 @@ impl ser::SerializeMap for JsonbWriter<'_> {
         <Self as ser::SerializeSeq>::serialize_element(self, value)
     }
 
+    /// Serialize the key and the value with a single inner serializer,
+    /// instead of swapping the buffer in and out once for each of them.
+    fn serialize_entry<K: ?Sized + Serialize, V: ?Sized + Serialize>(
+        &mut self,
+        key: &K,
+        value: &V,
+    ) -> Result<()> {
+        if self.options.sort_keys {
+            self.entry_starts.push(self.buffer.len());
+        }
+        let mut serializer = Serializer::from_options(self.options);
+        std::mem::swap(self.buffer, &mut serializer.buffer);
+        let r = if self.options.stringify_map_keys {
+            key.serialize(MapKeySerializer(&mut serializer))
+        } else {
+            key.serialize(&mut serializer)
+        };
+        let r = r.and_then(|()| value.serialize(&mut serializer));
+        std::mem::swap(self.buffer, &mut serializer.buffer);
+        r
+    }
+
     fn end(self) -> Result<Self::Ok> {
         self.finalize();
         Ok(())
@@ -502,8 +845,7 @@ impl ser::SerializeStruct for JsonbWriter<'_> {
         key: &'static str,
         value: &T,
     ) -> Result<()> {
-        <Self as ser::SerializeMap>::serialize_key(self, key)?;
-        <Self as ser::SerializeMap>::serialize_value(self, value)
+        <Self as ser::SerializeMap>::serialize_entry(self, key, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
@@ -521,8 +863,11 @@ impl ser::SerializeStructVariant for EnumVariantSerializer<'_> {
         key: &'static str,
         value: &T,
     ) -> Result<()> {
-        ser::SerializeTupleVariant::serialize_field(self, key)?;
-        ser::SerializeTupleVariant::serialize_field(self, value)
+        ser::SerializeStruct::serialize_field(
+            &mut self.inner_jsonb_writer,
+            key,
+            value,
+        )
     }
 
     fn end(self) -> Result<Self::Ok> {
@@ -531,8 +876,21 @@ impl ser::SerializeStructVariant for EnumVariantSerializer<'_> {
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::approx_constant,
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::float_cmp,
+    clippy::ip_constant,
+    clippy::items_after_statements,
+    clippy::needless_pass_by_value,
+    clippy::unreadable_literal,
+    clippy::zero_sized_map_values
+)]
 mod tests {
     use super::*;
+    use crate::BytesEncoding;
+    use serde::Deserialize;
 
     #[test]
     fn test_serialize_u8() {
@@ -547,6 +905,262 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_serialize_128_bit_integers() {
+        let blob = to_vec(&u128::MAX).unwrap();
+        assert_eq!(blob, b"\xc3\x27340282366920938463463374607431768211455");
+        assert_eq!(crate::from_slice::<u128>(&blob).unwrap(), u128::MAX);
+        let blob = to_vec(&i128::MIN).unwrap();
+        assert_eq!(crate::from_slice::<i128>(&blob).unwrap(), i128::MIN);
+    }
+
+    #[test]
+    fn test_to_length_prefixed_vec() {
+        assert_eq!(to_length_prefixed_vec(&()).unwrap(), b"\0\0\0\x01\x00");
+        assert_eq!(
+            to_length_prefixed_vec(&"abc").unwrap(),
+            b"\0\0\0\x04\x37abc"
+        );
+        let mut stream = to_length_prefixed_vec(&vec![1, 2]).unwrap();
+        stream.extend(to_length_prefixed_vec(&()).unwrap());
+        stream.extend(to_length_prefixed_vec(&Some("x")).unwrap());
+        let mut reader = &stream[..];
+        let read = |reader: &mut &[u8]| {
+            crate::from_length_prefixed_reader::<_, Option<Vec<u8>>>(reader)
+        };
+        assert_eq!(read(&mut reader).unwrap(), Some(vec![1, 2]));
+        assert_eq!(read(&mut reader).unwrap(), None);
+        assert_eq!(
+            crate::from_length_prefixed_reader::<_, String>(&mut reader)
+                .unwrap(),
+            "x"
+        );
+        assert!(reader.is_empty());
+
+        // the options apply to the value, but no checksum is appended
+        let options = Options {
+            binary_float: true,
+            append_crc32: true,
+            ..Options::default()
+        };
+        let framed =
+            to_length_prefixed_vec_with_options(&1.5, options).unwrap();
+        assert_eq!(framed, b"\0\0\0\x09\x8f\0\0\0\0\0\0\xf8\x3f");
+        assert_eq!(
+            crate::from_length_prefixed_reader::<_, f64>(&framed[..]).unwrap(),
+            1.5
+        );
+    }
+
+    #[test]
+    fn test_serialize_escape_policy() {
+        // a variant name is a string too
+        #[derive(serde_derive::Serialize)]
+        enum E {
+            T(u8, u8),
+            S { a: u8 },
+        }
+        let always_text = Options {
+            escape_policy: |_| ElementType::Text,
+            ..Options::default()
+        };
+        assert_eq!(
+            to_vec_with_options(&["ab", "é"], always_text).unwrap(),
+            b"\x6b\x27ab\x27\xc3\xa9"
+        );
+        // the default stores strings as they are, as Text like SQLite when
+        // they need no escaping, and as TextRaw otherwise
+        assert_eq!(to_vec(&"hello").unwrap(), b"\x57hello");
+        assert_eq!(to_vec(&"a\"b").unwrap(), b"\x3aa\"b");
+        assert_eq!(to_vec(&"a\\b").unwrap(), b"\x3aa\\b");
+        assert_eq!(to_vec(&"\t").unwrap(), b"\x1a\t");
+        assert_eq!(to_vec(&"\u{e9}\u{7f}").unwrap(), b"\x37\xc3\xa9\x7f");
+        let raw = Options {
+            escape_policy: Options::raw_strings,
+            ..Options::default()
+        };
+        assert_eq!(to_vec_with_options(&"hello", raw).unwrap(), b"\x5ahello");
+
+        let sqlite = Options {
+            escape_policy: Options::sqlite_strings,
+            ..Options::default()
+        };
+        let blob =
+            to_vec_with_options(&("a", "b\"c", "\n\u{1}"), sqlite).unwrap();
+        assert_eq!(blob, b"\xcb\x10\x17a\x48b\\\"c\x88\\n\\u0001");
+        assert_eq!(
+            crate::from_slice::<(String, String, String)>(&blob).unwrap(),
+            ("a".into(), "b\"c".into(), "\n\u{1}".into())
+        );
+
+        let invalid = Options {
+            escape_policy: |_| ElementType::Int,
+            ..Options::default()
+        };
+        assert!(matches!(
+            to_vec_with_options(&"a", invalid),
+            Err(Error::UnexpectedType {
+                found: ElementType::Int,
+                ..
+            })
+        ));
+        // including the names of variants that hold data
+        assert!(to_vec_with_options(&E::T(1, 2), invalid).is_err());
+        assert!(to_vec_with_options(&E::S { a: 1 }, invalid).is_err());
+    }
+
+    #[test]
+    fn test_serialize_bytes_encoding() {
+        #[serde_with::serde_as]
+        #[derive(Debug, PartialEq, serde_derive::Serialize, Deserialize)]
+        struct Test {
+            #[serde_as(as = "serde_with::Bytes")]
+            bytes: Vec<u8>,
+        }
+        let value = Test {
+            bytes: b"foo".to_vec(),
+        };
+        for (bytes_encoding, expected) in [
+            (
+                BytesEncoding::Array,
+                &b"\xcc\x14\x57bytes\xcb\x0c\x33102\x33111\x33111"[..],
+            ),
+            (BytesEncoding::Base64, b"\xbc\x57bytes\x47Zm9v"),
+            (BytesEncoding::Hex, b"\xcc\x0d\x57bytes\x67666f6f"),
+        ] {
+            let options = Options {
+                bytes_encoding,
+                ..Options::default()
+            };
+            let blob = to_vec_with_options(&value, options).unwrap();
+            assert_eq!(blob, expected, "{bytes_encoding:?}");
+            let decoded: Test =
+                crate::from_slice_with_options(&blob, options).unwrap();
+            assert_eq!(decoded, value);
+        }
+        // arrays are still accepted with a text encoding
+        let options = Options {
+            bytes_encoding: BytesEncoding::Hex,
+            ..Options::default()
+        };
+        let array = to_vec(&value).unwrap();
+        assert_eq!(
+            crate::from_slice_with_options::<Test>(&array, options).unwrap(),
+            value
+        );
+        assert!(crate::from_slice_with_options::<Test>(
+            b"\x8c\x57bytes\x17x",
+            options
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_serialize_cell_and_refcell() {
+        use std::cell::{Cell, RefCell};
+
+        #[derive(Debug, PartialEq, serde_derive::Serialize, Deserialize)]
+        struct Config {
+            retries: Cell<u8>,
+            name: RefCell<String>,
+        }
+
+        let blob = to_vec(&RefCell::new(5)).unwrap();
+        assert_eq!(blob, b"\x135");
+        assert_eq!(
+            crate::from_slice::<RefCell<i32>>(&blob).unwrap(),
+            RefCell::new(5)
+        );
+        let blob = to_vec(&Cell::new(true)).unwrap();
+        assert_eq!(blob, b"\x01");
+        assert_eq!(
+            crate::from_slice::<Cell<bool>>(&blob).unwrap(),
+            Cell::new(true)
+        );
+
+        let config = Config {
+            retries: Cell::new(3),
+            name: RefCell::new("main".to_string()),
+        };
+        config.retries.set(4);
+        config.name.borrow_mut().push('!');
+        let blob = to_vec(&RefCell::new(&config)).unwrap();
+        assert_eq!(blob, to_vec(&config).unwrap());
+        let decoded: RefCell<Config> = crate::from_slice(&blob).unwrap();
+        assert_eq!(decoded.into_inner(), config);
+    }
+
+    #[test]
+    fn test_serializer_into_vec() {
+        let mut serializer = Serializer::from_options(Options::default());
+        42u8.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.as_bytes(), b"\x2342");
+        "x".serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_vec(), b"\x2342\x17x");
+    }
+
+    #[test]
+    fn test_serialize_multiple_top_level_values() {
+        let mut serializer = Serializer::from_options(Options::default());
+        42u8.serialize(&mut serializer).unwrap();
+        vec![1u8, 2].serialize(&mut serializer).unwrap();
+        let map: std::collections::BTreeMap<&str, &str> =
+            [("a", "b")].into_iter().collect();
+        map.serialize(&mut serializer).unwrap();
+        let bytes = serializer.into_vec();
+        assert_eq!(bytes, b"\x2342\x4b\x131\x132\x4c\x17a\x17b");
+
+        let mut de = crate::Deserializer::from_bytes(&bytes);
+        assert_eq!(u8::deserialize(&mut de).unwrap(), 42);
+        assert_eq!(Vec::<u8>::deserialize(&mut de).unwrap(), vec![1, 2]);
+        assert_eq!(
+            std::collections::BTreeMap::<String, String>::deserialize(&mut de)
+                .unwrap(),
+            [("a".to_string(), "b".to_string())].into_iter().collect()
+        );
+        assert!(matches!(de.peek_header(), Err(Error::Empty)));
+    }
+
+    #[test]
+    fn test_serialize_duplicate_keys() {
+        struct TwiceA;
+        impl Serialize for TwiceA {
+            fn serialize<S: ser::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                use ser::SerializeMap;
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("a", &1)?;
+                map.serialize_entry("a", &2)?;
+                map.end()
+            }
+        }
+        let bytes = to_vec(&TwiceA).unwrap();
+        assert_eq!(bytes, b"\x8c\x17a\x131\x17a\x132");
+
+        type Map = std::collections::BTreeMap<String, u8>;
+        let map: Map = crate::from_slice(&bytes).unwrap();
+        assert_eq!(map, [("a".to_string(), 2)].into_iter().collect());
+
+        let options = Options {
+            reject_duplicate_keys: true,
+            ..Options::default()
+        };
+        let err =
+            crate::from_slice_with_options::<Map>(&bytes, options).unwrap_err();
+        assert!(matches!(err, Error::DuplicateKey(k) if k == "a"));
+    }
+
+    #[test]
+    fn test_serialize_floats_ignore_locale() {
+        // Rust never reads LC_NUMERIC or LANG when formatting numbers
+        assert_eq!(to_vec(&1.5f64).unwrap(), b"\x351.5");
+        assert_eq!(to_vec(&-0.25f32).unwrap(), b"\x55-0.25");
+        assert_eq!(to_vec(&1234567.5f64).unwrap(), b"\x951234567.5");
+        assert_eq!(to_vec(&1234567u32).unwrap(), b"\x731234567");
+    }
+
     #[test]
     fn test_serialize_bool() {
         assert_eq!(to_vec(&true).unwrap(), b"\x01");
@@ -555,7 +1169,7 @@ mod tests {
 
     #[test]
     fn test_serialize_sring() {
-        assert_eq!(to_vec(&"hello").unwrap(), b"\x5ahello");
+        assert_eq!(to_vec(&"hello").unwrap(), b"\x57hello");
     }
 
     fn assert_long_str(repeats: u64, expected_header: &[u8]) {
@@ -568,16 +1182,31 @@ mod tests {
 
     #[test]
     fn test_serialize_various_string_lengths() {
-        assert_long_str(0x0, b"\x0a");
-        assert_long_str(0x1, b"\x1a");
-        assert_long_str(0xb, b"\xba");
-        assert_long_str(0xc, b"\xca\x0c");
-        assert_long_str(0xf, b"\xca\x0f");
-        assert_long_str(0x100, b"\xda\x01\x00");
-        assert_long_str(0xffff, b"\xda\xff\xff");
-        assert_long_str(0x01_23_45_67, b"\xea\x01\x23\x45\x67");
+        assert_long_str(0x0, b"\x07");
+        assert_long_str(0x1, b"\x17");
+        assert_long_str(0xb, b"\xb7");
+        assert_long_str(0xc, b"\xc7\x0c");
+        assert_long_str(0xf, b"\xc7\x0f");
+        assert_long_str(0x100, b"\xd7\x01\x00");
+        assert_long_str(0xffff, b"\xd7\xff\xff");
+        assert_long_str(0x01_23_45_67, b"\xe7\x01\x23\x45\x67");
         // disabled for test performance:
-        // assert_long_str(0x01_0000_0000, b"\xfa\x00\x00\x00\x01\x00\x00\x00\x00");
+        // assert_long_str(0x01_0000_0000, b"\xf7\x00\x00\x00\x01\x00\x00\x00\x00");
+    }
+
+    #[test]
+    fn test_final_header_of_9_bytes() {
+        // A 4 GiB payload keeps its placeholder header, which is only
+        // filled in: pretend that the few bytes after it are that payload.
+        let mut buffer = b"-\x0a\x0a\x0a\x0a\x0a\x0a\x0a\x0a\x0aabc".to_vec();
+        write_final_header(&mut buffer, 1, 0x01_0000_0000);
+        assert_eq!(buffer, b"-\xfa\x00\x00\x00\x01\x00\x00\x00\x00abc");
+        write_final_header(&mut buffer, 1, u64::MAX);
+        assert_eq!(buffer, b"-\xfa\xff\xff\xff\xff\xff\xff\xff\xffabc");
+        // smaller payloads get a smaller header
+        let mut buffer = b"-\x0a\x0a\x0a\x0a\x0a\x0a\x0a\x0a\x0aabc".to_vec();
+        write_final_header(&mut buffer, 1, 3);
+        assert_eq!(buffer, b"-\x3aabc");
     }
 
     #[test]
@@ -590,11 +1219,118 @@ mod tests {
         assert_eq!(to_vec(&vec![true, false]).unwrap(), b"\x2b\x01\x02");
     }
 
+    #[test]
+    fn test_empty_document_constants() {
+        use crate::{EMPTY_ARRAY, EMPTY_OBJECT, NULL};
+        assert_eq!(to_vec(&()).unwrap(), NULL);
+        assert_eq!(to_vec(&None::<u8>).unwrap(), NULL);
+        assert_eq!(to_vec(&Vec::<u8>::new()).unwrap(), EMPTY_ARRAY);
+        assert_eq!(
+            to_vec(&std::collections::HashMap::<String, u8>::new()).unwrap(),
+            EMPTY_OBJECT
+        );
+    }
+
+    #[test]
+    fn test_serialize_binary_int() {
+        let options = Options {
+            binary_int: true,
+            ..Options::default()
+        };
+        fn binary<T: Serialize>(v: T) -> Vec<u8> {
+            let options = Options {
+                binary_int: true,
+                ..Options::default()
+            };
+            to_vec_with_options(&v, options).unwrap()
+        }
+        assert_eq!(binary(5u8), b"\x1e\x05");
+        assert_eq!(binary(-2i32), b"\x1e\xfe");
+        assert_eq!(binary(300u16), b"\x2e\x2c\x01");
+        assert_eq!(binary(-70_000i64), b"\x4e\x90\xee\xfe\xff");
+        assert_eq!(binary(i64::MIN), b"\x8e\x00\x00\x00\x00\x00\x00\x00\x80");
+        // integers that do not fit in an i64 are still text
+        assert_eq!(binary(u64::MAX), b"\xc3\x1418446744073709551615");
+        assert_eq!(binary(-1i128), b"\x1e\xff");
+        for v in [
+            0,
+            1,
+            -1,
+            127,
+            -128,
+            128,
+            40_000,
+            -40_000,
+            i64::MAX,
+            i64::MIN,
+        ] {
+            let blob = to_vec_with_options(&v, options).unwrap();
+            assert_eq!(crate::from_slice::<i64>(&blob).unwrap(), v);
+            assert_eq!(crate::from_slice::<f64>(&blob).unwrap(), v as f64);
+            assert_eq!(
+                crate::from_slice::<crate::Number>(&blob).unwrap(),
+                crate::Number::Int(i128::from(v))
+            );
+            assert_eq!(
+                crate::from_slice::<crate::RawNumber>(&blob)
+                    .unwrap()
+                    .as_str(),
+                v.to_string()
+            );
+        }
+        let blob = to_vec_with_options(&300, options).unwrap();
+        assert!(crate::from_slice::<u8>(&blob).is_err());
+        assert!(crate::from_slice::<u16>(b"\x3e\x00\x00\x00").is_err());
+        let values: Vec<u32> = (0..1000).map(|i| i * 1000).collect();
+        let blob = to_vec_with_options(&values, options).unwrap();
+        assert!(blob.len() < to_vec(&values).unwrap().len());
+        assert_eq!(crate::from_slice::<Vec<u32>>(&blob).unwrap(), values);
+    }
+
     #[test]
     fn test_serialize_tuple() {
         assert_eq!(to_vec(&(true, 1, 2)).unwrap(), b"\x5b\x01\x131\x132");
     }
 
+    #[test]
+    fn test_large_tuple_roundtrip() {
+        type Mixed = (
+            u8,
+            i16,
+            String,
+            bool,
+            f64,
+            u64,
+            String,
+            bool,
+            f32,
+            i128,
+            char,
+            Option<u32>,
+        );
+        let value: Mixed = (
+            1,
+            -2,
+            "three".to_string(),
+            true,
+            4.5,
+            6,
+            "seven".to_string(),
+            false,
+            -8.25,
+            9,
+            'x',
+            None,
+        );
+        let encoded = to_vec(&value).unwrap();
+        assert_eq!(
+            encoded,
+            b"\xcb\x24\x131\x23-2\x57three\x01\x354.5\x136\x57seven\x02\x55-8.25\x139\x17x\x00"
+        );
+        let decoded: Mixed = crate::from_slice(&encoded).unwrap();
+        assert_eq!(decoded, value);
+    }
+
     #[test]
     fn test_serialize_tuple_struct() {
         #[derive(serde_derive::Serialize)]
@@ -602,7 +1338,7 @@ mod tests {
 
         assert_eq!(
             to_vec(&TupleStruct("hello".to_string(), 3.14)).unwrap(),
-            b"\xbb\x5ahello\x453.14"
+            b"\xbb\x57hello\x453.14"
         );
     }
 
@@ -619,15 +1355,183 @@ mod tests {
         };
         assert_eq!(
             to_vec(&test_struct).unwrap(),
-            b"\xcc\x1f\x4asmol\x1aX\xca\x13long_long_long_long\x2342"
+            b"\xcc\x1f\x47smol\x17X\xc7\x13long_long_long_long\x2342"
         );
     }
 
+    #[test]
+    fn test_serialize_serde_with_display_from_str() {
+        #[serde_with::serde_as]
+        #[derive(serde_derive::Serialize)]
+        struct Test {
+            #[serde_as(as = "serde_with::DisplayFromStr")]
+            n: u64,
+        }
+        assert_eq!(to_vec(&Test { n: 42 }).unwrap(), b"\x5c\x17n\x2742");
+    }
+
+    #[test]
+    fn test_serialize_std_net_types() {
+        use std::fmt::Debug;
+        use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+        fn roundtrip<T>(value: T, human_readable: bool) -> Vec<u8>
+        where
+            T: Serialize + serde::de::DeserializeOwned + PartialEq + Debug,
+        {
+            let options = Options {
+                human_readable,
+                ..Options::default()
+            };
+            let blob = to_vec_with_options(&value, options).unwrap();
+            let decoded: T =
+                crate::from_slice_with_options(&blob, options).unwrap();
+            assert_eq!(decoded, value);
+            blob
+        }
+        let v4 = Ipv4Addr::new(127, 0, 0, 1);
+        let v6 = Ipv6Addr::LOCALHOST;
+        let socket: SocketAddr = "[::1]:8080".parse().unwrap();
+        assert_eq!(roundtrip(v4, true), b"\x97127.0.0.1");
+        assert_eq!(roundtrip(IpAddr::V4(v4), true), b"\x97127.0.0.1");
+        assert_eq!(roundtrip(v6, true), b"\x37::1");
+        assert_eq!(roundtrip(IpAddr::V6(v6), true), b"\x37::1");
+        assert_eq!(roundtrip(socket, true), b"\xa7[::1]:8080");
+        // compact forms
+        assert_eq!(roundtrip(v4, false), b"\xab\x33127\x130\x130\x131");
+        assert_eq!(
+            roundtrip(IpAddr::V4(v4), false),
+            b"\xcc\x0e\x27V4\xab\x33127\x130\x130\x131"
+        );
+        roundtrip(v6, false);
+        roundtrip(IpAddr::V6(v6), false);
+        roundtrip(socket, false);
+    }
+
     #[test]
     fn test_serialize_map() {
         let mut test_map = std::collections::HashMap::new();
         test_map.insert("k".to_string(), false);
-        assert_eq!(to_vec(&test_map).unwrap(), b"\x3c\x1ak\x02",);
+        assert_eq!(to_vec(&test_map).unwrap(), b"\x3c\x17k\x02",);
+    }
+
+    #[test]
+    fn test_sets_roundtrip_as_arrays() {
+        use std::collections::{BTreeSet, HashSet};
+        let tags: BTreeSet<i32> = [3, 1, 2].into_iter().collect();
+        let blob = to_vec(&tags).unwrap();
+        assert_eq!(blob, b"\x6b\x131\x132\x133");
+        assert_eq!(crate::from_slice::<BTreeSet<i32>>(&blob).unwrap(), tags);
+
+        let tags: HashSet<i32> = [3, 1, 2].into_iter().collect();
+        let blob = to_vec(&tags).unwrap();
+        assert_eq!(blob[0], 0x6b);
+        assert_eq!(crate::from_slice::<HashSet<i32>>(&blob).unwrap(), tags);
+
+        // repeated elements are silently deduplicated: [2, 1, 2]
+        let blob = b"\x6b\x132\x131\x132";
+        assert_eq!(
+            crate::from_slice::<BTreeSet<i32>>(blob).unwrap(),
+            BTreeSet::from([1, 2])
+        );
+        assert_eq!(
+            crate::from_slice::<HashSet<i32>>(blob).unwrap(),
+            HashSet::from([1, 2])
+        );
+    }
+
+    #[test]
+    fn test_serialize_sorted_keys_is_deterministic() {
+        use std::collections::{BTreeMap, HashMap};
+        let entries = (0..100).map(|i| (format!("key {i}"), i));
+        let btree: BTreeMap<String, i32> = entries.clone().collect();
+        let hash: HashMap<String, i32> = entries.collect();
+        let options = Options {
+            sort_keys: true,
+            ..Options::default()
+        };
+        let btree_blob = to_vec(&btree).unwrap();
+        assert_eq!(btree_blob, to_vec(&btree).unwrap());
+        assert_eq!(btree_blob, to_vec_with_options(&btree, options).unwrap());
+        assert_eq!(btree_blob, to_vec_with_options(&hash, options).unwrap());
+    }
+
+    #[test]
+    fn test_serialize_sorted_keys_keep_their_values() {
+        use serde::ser::{SerializeMap, Serializer as _};
+        /// Calls `serialize_key` and `serialize_value` separately
+        struct Unsorted;
+        impl Serialize for Unsorted {
+            fn serialize<S: ser::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                let mut map = serializer.serialize_map(Some(3))?;
+                map.serialize_key("zz")?;
+                map.serialize_value(&[1, 2])?;
+                map.serialize_key("a")?;
+                map.serialize_value("first")?;
+                map.serialize_key("m")?;
+                map.serialize_value(&true)?;
+                map.end()
+            }
+        }
+        let options = Options {
+            sort_keys: true,
+            ..Options::default()
+        };
+        // {"a": "first", "m": true, "zz": [1, 2]}
+        let expected = b"\xcc\x13\x17a\x57first\x17m\x01\x27zz\x4b\x131\x132";
+        assert_eq!(to_vec_with_options(&Unsorted, options).unwrap(), expected);
+        // the same entries through serialize_entry
+        let mut serializer = Serializer::from_options(options);
+        let mut map = (&mut serializer).serialize_map(None).unwrap();
+        map.serialize_entry("m", &true).unwrap();
+        map.serialize_entry("zz", &[1, 2]).unwrap();
+        map.serialize_entry("a", "first").unwrap();
+        map.end().unwrap();
+        assert_eq!(serializer.as_bytes(), expected);
+    }
+
+    #[test]
+    fn test_serialize_sorted_struct_variant() {
+        #[derive(serde_derive::Serialize)]
+        enum E {
+            S { z: u8, a: u8 },
+        }
+        let options = Options {
+            sort_keys: true,
+            ..Options::default()
+        };
+        // {"S": {"a": 2, "z": 1}}
+        assert_eq!(
+            to_vec_with_options(&E::S { z: 1, a: 2 }, options).unwrap(),
+            b"\xbc\x17S\x8c\x17a\x132\x17z\x131"
+        );
+        // ["S", {"a": 2, "z": 1}]
+        let options = Options {
+            enum_as_tuple: true,
+            ..options
+        };
+        assert_eq!(
+            to_vec_with_options(&E::S { z: 1, a: 2 }, options).unwrap(),
+            b"\xbb\x17S\x8c\x17a\x132\x17z\x131"
+        );
+    }
+
+    #[test]
+    fn test_serialize_sorted_escaped_keys() {
+        use std::collections::HashMap;
+        let options = Options {
+            sort_keys: true,
+            escape_policy: Options::sqlite_strings,
+            ..Options::default()
+        };
+        // the TextJ key `a\"` sorts as `a"`, before the Text key `a#`
+        let map = HashMap::from([("a#", 2), ("a\"", 1)]);
+        assert_eq!(
+            to_vec_with_options(&map, options).unwrap(),
+            b"\xbc\x38a\\\"\x131\x27a#\x132"
+        );
     }
 
     #[test]
@@ -647,6 +1551,31 @@ mod tests {
         assert_eq!(to_vec(&()).unwrap(), b"\x00");
     }
 
+    #[test]
+    fn test_serialize_units_in_collections() {
+        let units = vec![(), ()];
+        let blob = to_vec(&units).unwrap();
+        assert_eq!(blob, b"\x2b\x00\x00");
+        assert_eq!(crate::from_slice::<Vec<()>>(&blob).unwrap(), units);
+
+        let map: std::collections::HashMap<String, ()> =
+            [("a".to_string(), ()), ("b".to_string(), ())].into();
+        let blob = to_vec_with_options(
+            &map,
+            Options {
+                sort_keys: true,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(blob, b"\x6c\x17a\x00\x17b\x00");
+        assert_eq!(
+            crate::from_slice::<std::collections::HashMap<String, ()>>(&blob)
+                .unwrap(),
+            map
+        );
+    }
+
     #[test]
     fn test_serialize_unit_struct() {
         #[derive(serde_derive::Serialize)]
@@ -655,6 +1584,151 @@ mod tests {
         assert_eq!(to_vec(&UnitStruct).unwrap(), b"\x00");
     }
 
+    #[test]
+    fn test_transparent_struct() {
+        #[derive(Debug, PartialEq, serde_derive::Serialize, Deserialize)]
+        #[serde(transparent)]
+        struct Wrapper {
+            inner: String,
+        }
+
+        let inner = "hello".to_string();
+        let encoded = to_vec(&Wrapper {
+            inner: inner.clone(),
+        })
+        .unwrap();
+        assert_eq!(encoded, to_vec(&inner).unwrap());
+        assert_eq!(encoded, b"\x57hello");
+        assert_eq!(
+            crate::from_slice::<Wrapper>(&encoded).unwrap(),
+            Wrapper { inner }
+        );
+    }
+
+    #[test]
+    fn test_to_writer() {
+        let values = vec![1.5f32, -2.0];
+        let mut written = Vec::new();
+        to_writer(&mut written, &values).unwrap();
+        assert_eq!(written, to_vec(&values).unwrap());
+        // values written one after the other can be read back in turn
+        let options = Options {
+            binary_float: true,
+            ..Options::default()
+        };
+        to_writer_with_options(&mut written, &values, options).unwrap();
+        let mut de = crate::Deserializer::from_bytes(&written);
+        for _ in 0..2 {
+            let read: Vec<f32> = Deserialize::deserialize(&mut de).unwrap();
+            assert_eq!(read, values);
+        }
+
+        struct FailingWriter;
+        impl Write for FailingWriter {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        assert!(matches!(
+            to_writer(FailingWriter, &values),
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::BrokenPipe
+        ));
+    }
+
+    #[test]
+    fn test_newtype_struct() {
+        #[derive(Debug, PartialEq, serde_derive::Serialize, Deserialize)]
+        struct Millis(u64);
+
+        let encoded = to_vec(&Millis(1234)).unwrap();
+        assert_eq!(encoded, to_vec(&1234u64).unwrap());
+        assert_eq!(encoded, b"\x431234");
+        assert_eq!(
+            crate::from_slice::<Millis>(&encoded).unwrap(),
+            Millis(1234)
+        );
+
+        let durations = vec![Millis(0), Millis(1234), Millis(u64::MAX)];
+        let encoded = to_vec(&durations).unwrap();
+        assert_eq!(encoded, to_vec(&[0u64, 1234, u64::MAX]).unwrap());
+        assert_eq!(
+            crate::from_slice::<Vec<Millis>>(&encoded).unwrap(),
+            durations
+        );
+    }
+
+    #[test]
+    fn test_newtype_struct_of_float() {
+        #[derive(Debug, PartialEq, serde_derive::Serialize, Deserialize)]
+        struct Meters(f64);
+
+        #[derive(Debug, PartialEq, serde_derive::Serialize, Deserialize)]
+        struct Route {
+            legs: Vec<Meters>,
+        }
+
+        assert_eq!(to_vec(&Meters(2.5)).unwrap(), to_vec(&2.5f64).unwrap());
+        let route = Route {
+            legs: vec![Meters(0.5), Meters(-12.25)],
+        };
+        let encoded = to_vec(&route).unwrap();
+        // {"legs": [0.5, -12.25]}
+        assert_eq!(encoded, b"\xcc\x11\x47legs\xbb\x350.5\x65-12.25");
+        assert_eq!(crate::from_slice::<Route>(&encoded).unwrap(), route);
+    }
+
+    #[test]
+    fn test_phantom_data() {
+        use std::marker::PhantomData;
+
+        #[derive(Debug, PartialEq, serde_derive::Serialize, Deserialize)]
+        struct Id<T> {
+            value: u32,
+            marker: PhantomData<T>,
+        }
+
+        let blob = to_vec(&PhantomData::<u32>).unwrap();
+        assert_eq!(blob, b"\x00");
+        crate::from_slice::<PhantomData<u32>>(&blob).unwrap();
+
+        let id = Id::<String> {
+            value: 1,
+            marker: PhantomData,
+        };
+        // {"value": 1, "marker": null}
+        let blob = to_vec(&id).unwrap();
+        assert_eq!(blob, b"\xcc\x10\x57value\x131\x67marker\x00");
+        assert_eq!(crate::from_slice::<Id<String>>(&blob).unwrap(), id);
+        // {"value": 1}: like other unit fields, the marker cannot be missing
+        let without_marker = b"\x8c\x57value\x131";
+        assert_eq!(
+            crate::from_slice::<Id<String>>(without_marker)
+                .unwrap_err()
+                .to_string(),
+            "missing field `marker`"
+        );
+
+        // unless it is skipped
+        #[derive(Debug, PartialEq, serde_derive::Serialize, Deserialize)]
+        struct SkippedId<T> {
+            value: u32,
+            #[serde(skip)]
+            marker: PhantomData<T>,
+        }
+        let id = SkippedId::<String> {
+            value: 1,
+            marker: PhantomData,
+        };
+        assert_eq!(to_vec(&id).unwrap(), without_marker);
+        assert_eq!(
+            crate::from_slice::<SkippedId<String>>(without_marker).unwrap(),
+            id
+        );
+    }
+
     #[test]
     fn test_serialize_enum_unit_variants() {
         #[derive(serde_derive::Serialize)]
@@ -663,8 +1737,8 @@ mod tests {
             B,
         }
 
-        assert_eq!(to_vec(&Enum::A).unwrap(), b"\x1aA");
-        assert_eq!(to_vec(&Enum::B).unwrap(), b"\x1aB");
+        assert_eq!(to_vec(&Enum::A).unwrap(), b"\x17A");
+        assert_eq!(to_vec(&Enum::B).unwrap(), b"\x17B");
     }
 
     #[test]
@@ -674,7 +1748,46 @@ mod tests {
             A(i32),
         }
 
-        assert_eq!(to_vec(&Enum::A(42)).unwrap(), b"\x5c\x1aA\x2342");
+        assert_eq!(to_vec(&Enum::A(42)).unwrap(), b"\x5c\x17A\x2342");
+    }
+
+    #[test]
+    fn test_serialize_enum_empty_variants() {
+        #[derive(Debug, PartialEq, serde_derive::Serialize, Deserialize)]
+        enum Enum {
+            T(),
+            S {},
+        }
+
+        // {"T": []}
+        let blob = to_vec(&Enum::T()).unwrap();
+        assert_eq!(blob, b"\x3c\x17T\x0b");
+        assert_eq!(crate::from_slice::<Enum>(&blob).unwrap(), Enum::T());
+        // {"S": {}}
+        let blob = to_vec(&Enum::S {}).unwrap();
+        assert_eq!(blob, b"\x3c\x17S\x0c");
+        assert_eq!(crate::from_slice::<Enum>(&blob).unwrap(), Enum::S {});
+    }
+
+    #[test]
+    fn test_serialize_result() {
+        let ok: std::result::Result<i32, String> = Ok(42);
+        let blob = to_vec(&ok).unwrap();
+        assert_eq!(blob, b"\x6c\x27Ok\x2342");
+        assert_eq!(
+            crate::from_slice::<std::result::Result<i32, String>>(&blob)
+                .unwrap(),
+            ok
+        );
+
+        let err: std::result::Result<i32, String> = Err("bad".to_string());
+        let blob = to_vec(&err).unwrap();
+        assert_eq!(blob, b"\x8c\x37Err\x37bad");
+        assert_eq!(
+            crate::from_slice::<std::result::Result<i32, String>>(&blob)
+                .unwrap(),
+            err
+        );
     }
 
     #[test]
@@ -684,7 +1797,7 @@ mod tests {
             A(i32, i32),
         }
 
-        assert_eq!(to_vec(&Enum::A(1, 2)).unwrap(), b"\x7c\x1aA\x4b\x131\x132");
+        assert_eq!(to_vec(&Enum::A(1, 2)).unwrap(), b"\x7c\x17A\x4b\x131\x132");
     }
 
     #[test]
@@ -694,19 +1807,104 @@ mod tests {
             S { x: bool },
         }
         let test_struct = E::S { x: true };
-        assert_eq!(to_vec(&test_struct).unwrap(), b"\x6c\x1aS\x3c\x1ax\x01");
+        assert_eq!(to_vec(&test_struct).unwrap(), b"\x6c\x17S\x3c\x17x\x01");
+    }
+
+    #[test]
+    fn test_serialize_enum_as_tuple() {
+        #[derive(Debug, PartialEq, serde_derive::Serialize, Deserialize)]
+        enum E {
+            Unit,
+            Newtype(u8),
+            Tuple(u8, bool),
+            Struct { x: bool },
+        }
+        let options = Options {
+            enum_as_tuple: true,
+            ..Options::default()
+        };
+        for (value, expected) in [
+            (E::Unit, &b"\x47Unit"[..]),
+            (E::Newtype(1), b"\xab\x77Newtype\x131"),
+            (E::Tuple(1, true), b"\xab\x57Tuple\x3b\x131\x01"),
+            (E::Struct { x: true }, b"\xbb\x67Struct\x3c\x17x\x01"),
+        ] {
+            let blob = to_vec_with_options(&value, options).unwrap();
+            assert_eq!(blob, expected, "{value:?}");
+            let decoded: E =
+                crate::from_slice_with_options(&blob, options).unwrap();
+            assert_eq!(decoded, value);
+            // the default object form still round-trips
+            let blob = to_vec(&value).unwrap();
+            assert_eq!(crate::from_slice::<E>(&blob).unwrap(), value);
+        }
+        // exactly two elements are expected
+        let three = b"\xcb\x0c\x77Newtype\x131\x132";
+        assert!(crate::from_slice_with_options::<E>(three, options).is_err());
+    }
+
+    #[cfg(feature = "half")]
+    #[test]
+    fn test_serialize_binary_half_float() {
+        use crate::F16;
+        use half::f16;
+        let options = Options {
+            binary_float: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            to_vec_with_options(&F16(f16::from_f32(1.0)), options).unwrap(),
+            b"\x2f\x00\x3c",
+            "1.0f16 in little-endian IEEE 754"
+        );
+        let halves: Vec<F16> = [1.0, -2.5, 0.1, 65504.0]
+            .into_iter()
+            .map(|v| F16(f16::from_f32(v)))
+            .collect();
+        let blob = to_vec_with_options(&halves, options).unwrap();
+        assert_eq!(crate::from_slice::<Vec<F16>>(&blob).unwrap(), halves);
+        let floats: Vec<f32> = halves.iter().map(|h| h.0.to_f32()).collect();
+        let f32_blob = to_vec_with_options(&floats, options).unwrap();
+        assert!(blob.len() < f32_blob.len());
+        assert_eq!(crate::from_slice::<Vec<f32>>(&blob).unwrap(), floats);
+
+        // without binary_float, F16 is stored as a regular float
+        let blob = to_vec(&F16(f16::from_f32(-2.5))).unwrap();
+        assert_eq!(blob, b"\x45-2.5");
+        assert_eq!(
+            crate::from_slice::<F16>(&blob).unwrap(),
+            F16(f16::from_f32(-2.5))
+        );
+    }
+
+    #[cfg(feature = "half")]
+    #[test]
+    fn test_newtype_named_f16_is_not_a_half_float() {
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, PartialEq, serde_derive::Serialize, Deserialize)]
+        struct f16(u16);
+        let options = Options {
+            binary_float: true,
+            ..Options::default()
+        };
+        let blob = to_vec_with_options(&f16(15360), options).unwrap();
+        assert_eq!(blob, b"\x5315360");
+        assert_eq!(crate::from_slice::<f16>(&blob).unwrap(), f16(15360));
     }
 
     #[test]
     fn test_serialize_binary_float() {
-        let options = Options { binary_float: true };
+        let options = Options {
+            binary_float: true,
+            ..Options::default()
+        };
         assert_eq!(
-            to_vec_with_options(&1.0f32, options.clone()).unwrap(),
+            to_vec_with_options(&1.0f32, options).unwrap(),
             b"\x4f\x00\x00\x80\x3f",
             "1.0f32 in little-endian IEEE 754"
         );
         assert_eq!(
-            to_vec_with_options(&-2.5f64, options.clone()).unwrap(),
+            to_vec_with_options(&-2.5f64, options).unwrap(),
             b"\x8f\x00\x00\x00\x00\x00\x00\x04\xc0",
             "-2.5f64 in little-endian IEEE 754"
         );
@@ -715,8 +1913,211 @@ mod tests {
         //     &serde_json::json!({
         //         "num": -2.5f64,
         //     }),
-        //     options.clone(),
+        //     options,
         // );
         // println!("{:?}", blob);
     }
+
+    #[test]
+    fn test_binary_float_roundtrip_keeps_width() {
+        let options = Options {
+            binary_float: true,
+            ..Options::default()
+        };
+        let floats32 = vec![0.1f32, -3.75, f32::MAX, f32::MIN_POSITIVE];
+        let blob = to_vec_with_options(&floats32, options).unwrap();
+        // four BinaryFloat elements of 4 bytes each
+        assert_eq!(blob.len(), 2 + 4 * 5);
+        assert_eq!(&blob[..3], b"\xcb\x14\x4f");
+        assert_eq!(crate::from_slice::<Vec<f32>>(&blob).unwrap(), floats32);
+        // 4-byte floats are read as f32, then widened exactly
+        let widened: Vec<f64> = floats32.iter().map(|&f| f.into()).collect();
+        assert_eq!(crate::from_slice::<Vec<f64>>(&blob).unwrap(), widened);
+
+        let floats64 = vec![0.1f64, -3.75, f64::MAX, f64::MIN_POSITIVE];
+        let blob = to_vec_with_options(&floats64, options).unwrap();
+        // four BinaryFloat elements of 8 bytes each
+        assert_eq!(blob.len(), 2 + 4 * 9);
+        assert_eq!(&blob[..3], b"\xcb\x24\x8f");
+        assert_eq!(crate::from_slice::<Vec<f64>>(&blob).unwrap(), floats64);
+    }
+
+    #[test]
+    fn test_serialize_non_finite_floats() {
+        assert_eq!(to_vec(&f64::NAN).unwrap(), b"\x00");
+        assert_eq!(to_vec(&f32::INFINITY).unwrap(), b"\x559e999");
+        assert_eq!(to_vec(&f64::NEG_INFINITY).unwrap(), b"\x65-9e999");
+        let reject = Options {
+            non_finite_floats: NonFiniteFloats::Reject,
+            ..Options::default()
+        };
+        assert!(matches!(
+            to_vec_with_options(&[1.0, f64::INFINITY], reject),
+            Err(Error::NonFiniteFloat(f)) if f == f64::INFINITY
+        ));
+        assert!(matches!(
+            to_vec_with_options(&f32::NAN, reject),
+            Err(Error::NonFiniteFloat(f)) if f.is_nan()
+        ));
+        assert_eq!(to_vec_with_options(&1.5, reject).unwrap(), b"\x351.5");
+        // binary floats can hold any value
+        let binary = Options {
+            binary_float: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            to_vec_with_options(&f32::NAN, binary).unwrap(),
+            [&b"\x4f"[..], &f32::NAN.to_le_bytes()].concat()
+        );
+    }
+
+    /// A map with float keys, which std maps do not allow
+    #[derive(Debug, PartialEq)]
+    struct FloatKeys(Vec<(f64, u8)>);
+
+    impl Serialize for FloatKeys {
+        fn serialize<S: ser::Serializer>(
+            &self,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error> {
+            serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for FloatKeys {
+        fn deserialize<D: serde::Deserializer<'de>>(
+            deserializer: D,
+        ) -> std::result::Result<Self, D::Error> {
+            struct EntriesVisitor;
+            impl<'de> serde::de::Visitor<'de> for EntriesVisitor {
+                type Value = FloatKeys;
+                fn expecting(
+                    &self,
+                    f: &mut std::fmt::Formatter,
+                ) -> std::fmt::Result {
+                    f.write_str("a map")
+                }
+                fn visit_map<A: serde::de::MapAccess<'de>>(
+                    self,
+                    mut map: A,
+                ) -> std::result::Result<FloatKeys, A::Error> {
+                    let mut entries = Vec::new();
+                    while let Some(entry) = map.next_entry()? {
+                        entries.push(entry);
+                    }
+                    Ok(FloatKeys(entries))
+                }
+            }
+            deserializer.deserialize_map(EntriesVisitor)
+        }
+    }
+
+    #[test]
+    fn test_serialize_stringified_map_keys() {
+        let options = Options {
+            stringify_map_keys: true,
+            ..Options::default()
+        };
+        let keys = FloatKeys(vec![(1.5, 1), (-2.0, 2), (f64::INFINITY, 3)]);
+        let blob = to_vec_with_options(&keys, options).unwrap();
+        // keys have the same text as float values
+        assert_eq!(to_vec(&-2.0).unwrap(), b"\x25-2");
+        assert_eq!(blob, b"\xcc\x13\x371.5\x131\x27-2\x132\x579e999\x133");
+        // and are parsed back into floats
+        assert_eq!(crate::from_slice::<FloatKeys>(&blob).unwrap(), keys);
+        assert!(matches!(
+            to_vec_with_options(&FloatKeys(vec![(f64::NAN, 1)]), options),
+            Err(Error::NonFiniteFloat(f)) if f.is_nan()
+        ));
+        let reject = Options {
+            non_finite_floats: NonFiniteFloats::Reject,
+            ..options
+        };
+        assert!(matches!(
+            to_vec_with_options(&FloatKeys(vec![(f64::INFINITY, 1)]), reject),
+            Err(Error::NonFiniteFloat(_))
+        ));
+
+        let map: std::collections::BTreeMap<i32, bool> =
+            [(-1, true), (10, false)].into();
+        let blob = to_vec_with_options(&map, options).unwrap();
+        assert_eq!(blob, b"\x8c\x27-1\x01\x2710\x02");
+        assert_eq!(
+            crate::from_slice::<std::collections::BTreeMap<String, bool>>(
+                &blob
+            )
+            .unwrap(),
+            [("-1".to_string(), true), ("10".to_string(), false)].into()
+        );
+        assert_eq!(
+            crate::from_slice::<std::collections::BTreeMap<i32, bool>>(&blob)
+                .unwrap(),
+            map
+        );
+        assert!(matches!(
+            crate::from_slice::<std::collections::BTreeMap<u8, bool>>(&blob),
+            Err(Error::UnexpectedType {
+                found: ElementType::Text,
+                expected: Some("integer")
+            })
+        ));
+        let map: std::collections::BTreeMap<bool, ()> = [(true, ())].into();
+        assert_eq!(
+            to_vec_with_options(&map, options).unwrap(),
+            b"\x6c\x47true\x00"
+        );
+        let map: std::collections::HashMap<bool, u8> =
+            [(true, 1), (false, 0)].into();
+        let blob = to_vec_with_options(&map, options).unwrap();
+        assert_eq!(
+            crate::from_slice::<std::collections::HashMap<bool, u8>>(&blob)
+                .unwrap(),
+            map
+        );
+        let map: std::collections::BTreeMap<u128, ()> =
+            [(u128::MAX, ())].into();
+        let blob = to_vec_with_options(&map, options).unwrap();
+        assert_eq!(
+            crate::from_slice::<std::collections::BTreeMap<u128, ()>>(&blob)
+                .unwrap(),
+            map
+        );
+        let map: std::collections::BTreeMap<(u8, u8), ()> =
+            [((1, 2), ())].into();
+        assert!(to_vec_with_options(&map, options).is_err());
+        // without the option, keys keep their type
+        let map: std::collections::BTreeMap<u8, ()> = [(1, ())].into();
+        assert_eq!(to_vec(&map).unwrap(), b"\x3c\x131\x00");
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_serde_json_value_roundtrip() {
+        use serde_json::{json, Value};
+        // integers stay integers, and floats stay floats
+        assert_eq!(to_vec(&json!(1)).unwrap(), b"\x131");
+        assert_eq!(to_vec(&json!(-7)).unwrap(), b"\x23-7");
+        // a Float element, even when written without a fraction
+        assert_eq!(to_vec(&json!(1.0)).unwrap(), b"\x151");
+        assert_eq!(to_vec(&json!([2, 2.5])).unwrap(), b"\x6b\x132\x352.5");
+
+        let value = json!({
+            "id": 42,
+            "big": u64::MAX,
+            "small": i64::MIN,
+            "ratio": 0.25,
+            "whole": 1.0,
+            "tags": ["a", null, [null, {"deep": null}]],
+            "empty": {},
+            "none": null,
+        });
+        let blob = to_vec(&value).unwrap();
+        let decoded: Value = crate::from_slice(&blob).unwrap();
+        assert_eq!(decoded, value);
+        assert!(decoded["big"].is_u64());
+        assert!(decoded["small"].is_i64());
+        assert!(decoded["ratio"].is_f64());
+        assert!(decoded["whole"].is_f64());
+        assert_eq!(crate::to_json_value(&blob).unwrap(), value);
+    }
 }
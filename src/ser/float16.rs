@@ -0,0 +1,147 @@
+//! Serialization of [`F16`](crate::F16) values.
+
+use super::Serializer;
+use crate::error::{Error, Result};
+use serde::ser::{self, Impossible, Serialize};
+
+/// Writes the `f32` that an [`F16`](crate::F16) serializes as, as a 2-byte
+/// `BinaryFloat` with [`binary_float`](crate::Options::binary_float).
+/// Anything but an `f32` is an error.
+pub(super) struct F16Serializer<'a>(pub(super) &'a mut Serializer);
+
+fn expected_f16() -> Error {
+    Error::Message("expected an f16 value".into())
+}
+
+macro_rules! reject {
+    ($($method:ident: $ty:ty),*) => {
+        $(
+            fn $method(self, _v: $ty) -> Result<()> {
+                Err(expected_f16())
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for F16Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    reject!(
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+        serialize_bytes: &[u8],
+        serialize_unit_struct: &'static str
+    );
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        let v = half::f16::from_f32(v);
+        self.0.write_float(v.to_f32(), v.to_le_bytes())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Err(expected_f16())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<()> {
+        Err(expected_f16())
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Err(expected_f16())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        Err(expected_f16())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        Err(expected_f16())
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        Err(expected_f16())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(expected_f16())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(expected_f16())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(expected_f16())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(expected_f16())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(expected_f16())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct> {
+        Err(expected_f16())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(expected_f16())
+    }
+}
@@ -0,0 +1,176 @@
+//! Serialization of map keys as strings, for
+//! [`Options::stringify_map_keys`](crate::Options::stringify_map_keys).
+
+use super::{check_non_finite, FloatText, Serializer};
+use crate::error::{Error, Result};
+use serde::ser::{self, Impossible, Serialize};
+
+/// Serializes a map key as a string element. Strings are written as they
+/// are, and numbers, booleans and unit variants as their text; floats use
+/// the same text as float values. Other keys are an error.
+pub(super) struct MapKeySerializer<'a>(pub(super) &'a mut Serializer);
+
+fn key_must_be_a_string() -> Error {
+    Error::Message("map key must be a string".into())
+}
+
+impl MapKeySerializer<'_> {
+    fn serialize_float<F>(self, v: F) -> Result<()>
+    where
+        F: std::fmt::Display + Into<f64> + Copy,
+    {
+        let f: f64 = v.into();
+        check_non_finite(f, self.0.options)?;
+        if f.is_nan() {
+            return Err(Error::NonFiniteFloat(f));
+        }
+        ser::Serializer::serialize_str(self.0, &FloatText(v).to_string())
+    }
+}
+
+macro_rules! serialize_displayed {
+    ($($method:ident: $ty:ty),*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<()> {
+                ser::Serializer::serialize_str(self.0, &v.to_string())
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for MapKeySerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    serialize_displayed!(
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128
+    );
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.serialize_float(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.serialize_float(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        ser::Serializer::serialize_char(self.0, v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        ser::Serializer::serialize_str(self.0, v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<()> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(key_must_be_a_string())
+    }
+}
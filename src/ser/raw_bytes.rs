@@ -0,0 +1,148 @@
+//! Copying of already encoded elements, for [`RawElement`](crate::RawElement)
+//! and [`RawJsonb`](crate::RawJsonb).
+
+use crate::error::{Error, Result};
+use serde::ser::{self, Impossible, Serialize};
+use std::io::Write;
+
+/// Writes the bytes of an encoded element as they are, whatever the
+/// [`bytes_encoding`](crate::Options::bytes_encoding). Anything but bytes
+/// is an error.
+pub(super) struct RawBytesSerializer<W>(pub(super) W);
+
+fn expected_raw_bytes() -> Error {
+    Error::Message("expected the bytes of an encoded element".into())
+}
+
+macro_rules! reject {
+    ($($method:ident: $ty:ty),*) => {
+        $(
+            fn $method(self, _v: $ty) -> Result<()> {
+                Err(expected_raw_bytes())
+            }
+        )*
+    };
+}
+
+impl<W: Write> ser::Serializer for RawBytesSerializer<W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    reject!(
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+        serialize_unit_struct: &'static str
+    );
+
+    fn serialize_bytes(mut self, v: &[u8]) -> Result<()> {
+        self.0.write_all(v)?;
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Err(expected_raw_bytes())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<()> {
+        Err(expected_raw_bytes())
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Err(expected_raw_bytes())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        Err(expected_raw_bytes())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        Err(expected_raw_bytes())
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        Err(expected_raw_bytes())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(expected_raw_bytes())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(expected_raw_bytes())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(expected_raw_bytes())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(expected_raw_bytes())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(expected_raw_bytes())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct> {
+        Err(expected_raw_bytes())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(expected_raw_bytes())
+    }
+}
@@ -0,0 +1,723 @@
+//! Serialization straight into a seekable writer, without holding the whole
+//! value in memory.
+
+use super::{sort_entries, MapKeySerializer, RawBytesSerializer, Serializer};
+use crate::error::{Error, Result};
+use crate::header::{ElementType, Header};
+use crate::options::Options;
+use serde::ser::{self, Serialize};
+use std::io::{Seek, SeekFrom, Write};
+
+/// Serialize a value into a writer that can seek, such as a file or an
+/// `SQLite` blob handle, without building the whole JSONB value in memory.
+///
+/// Each array and object is written with a placeholder header, followed by
+/// its elements; then the writer seeks back to fill in the payload size.
+/// Because a header cannot shrink once the payload follows it, containers
+/// always get 9-byte headers, where [`to_vec`](crate::to_vec) uses the
+/// smallest header that fits. Both are valid JSONB and decode to the same
+/// value, and all the other elements are written exactly like `to_vec` does.
+///
+/// The value is written at the current position of the writer.
+///
+/// # Errors
+///
+/// Returns an error if serialization fails, or if writing or seeking fails.
+pub fn to_seekable_writer<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: Write + Seek,
+    T: Serialize,
+{
+    to_seekable_writer_with_options(writer, value, Options::default())
+}
+
+/// Serialize a value into a writer that can seek, using the given options.
+/// See [`to_seekable_writer`] for how containers are written.
+///
+/// With [`Options::sort_keys`], the entries of each object are built in
+/// memory to be sorted, before they are written. No checksum is appended
+/// with [`Options::append_crc32`], since it would cover headers that are
+/// only known at the end.
+///
+/// # Errors
+///
+/// Returns an error if serialization fails, or if writing or seeking fails.
+pub fn to_seekable_writer_with_options<W, T>(
+    writer: W,
+    value: &T,
+    options: Options,
+) -> Result<()>
+where
+    W: Write + Seek,
+    T: Serialize,
+{
+    value.serialize(&mut SeekableSerializer {
+        writer,
+        scalars: Serializer::from_options(options),
+    })
+}
+
+struct SeekableSerializer<W> {
+    writer: W,
+    /// Writes the elements that do not contain other elements, with the
+    /// options of the whole value. Its buffer is reused for each of them.
+    scalars: Serializer,
+}
+
+/// A container header that was written before its payload size was known
+struct Placeholder {
+    element_type: ElementType,
+    header_start: u64,
+}
+
+impl<W: Write + Seek> SeekableSerializer<W> {
+    fn options(&self) -> Options {
+        self.scalars.options
+    }
+
+    /// Serialize an element in memory and copy it to the writer.
+    /// Used for the elements that do not contain other elements.
+    fn write_buffered(
+        &mut self,
+        f: impl FnOnce(&mut Serializer) -> Result<()>,
+    ) -> Result<()> {
+        self.scalars.buffer.clear();
+        f(&mut self.scalars)?;
+        self.writer.write_all(&self.scalars.buffer)?;
+        Ok(())
+    }
+
+    fn start_container(
+        &mut self,
+        element_type: ElementType,
+    ) -> Result<Placeholder> {
+        let header_start = self.writer.stream_position()?;
+        let header = Header {
+            element_type,
+            payload_size: 0,
+        };
+        self.writer.write_all(&header.serialize())?;
+        Ok(Placeholder {
+            element_type,
+            header_start,
+        })
+    }
+
+    /// Write the real header of a container once all its elements are written
+    fn end_container(&mut self, placeholder: &Placeholder) -> Result<()> {
+        let end = self.writer.stream_position()?;
+        let header = Header {
+            element_type: placeholder.element_type,
+            payload_size: end - placeholder.header_start - 9,
+        };
+        self.writer
+            .seek(SeekFrom::Start(placeholder.header_start))?;
+        self.writer.write_all(&header.serialize())?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        Ok(())
+    }
+
+    /// Start an enum variant holding data: an object with the variant name as
+    /// its only key and the data as its value, or an array of the name and
+    /// the data with [`Options::enum_as_tuple`].
+    fn start_variant(&mut self, variant: &'static str) -> Result<Placeholder> {
+        let element_type = if self.options().enum_as_tuple {
+            ElementType::Array
+        } else {
+            ElementType::Object
+        };
+        let placeholder = self.start_container(element_type)?;
+        self.write_buffered(|s| ser::Serializer::serialize_str(s, variant))?;
+        Ok(placeholder)
+    }
+
+    fn container(
+        &mut self,
+        element_type: ElementType,
+        variant: Option<&'static str>,
+    ) -> Result<Container<'_, W>> {
+        let variant = match variant {
+            Some(variant) => Some(self.start_variant(variant)?),
+            None => None,
+        };
+        let placeholder = self.start_container(element_type)?;
+        Ok(Container {
+            ser: self,
+            placeholder,
+            variant,
+            sorted: None,
+        })
+    }
+
+    /// Like [`SeekableSerializer::container`] for an object whose keys are
+    /// sorted with [`Options::sort_keys`]
+    fn object(
+        &mut self,
+        variant: Option<&'static str>,
+    ) -> Result<Container<'_, W>> {
+        let sort_keys = self.options().sort_keys;
+        let mut container = self.container(ElementType::Object, variant)?;
+        if sort_keys {
+            container.sorted = Some(SortedEntries::default());
+        }
+        Ok(container)
+    }
+}
+
+/// Serializes the elements of an array or an object,
+/// possibly wrapped in an enum variant object.
+struct Container<'a, W> {
+    ser: &'a mut SeekableSerializer<W>,
+    placeholder: Placeholder,
+    variant: Option<Placeholder>,
+    /// The entries of an object, kept in memory until they are sorted
+    sorted: Option<SortedEntries>,
+}
+
+/// Object entries serialized in memory, and where each of them starts
+#[derive(Default)]
+struct SortedEntries {
+    buffer: Vec<u8>,
+    entry_starts: Vec<usize>,
+}
+
+impl<W: Write + Seek> Container<'_, W> {
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        match &mut self.sorted {
+            Some(sorted) => {
+                let options = self.ser.options();
+                serialize_into(&mut sorted.buffer, options, |s| {
+                    value.serialize(s)
+                })
+            }
+            None => value.serialize(&mut *self.ser),
+        }
+    }
+
+    fn key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
+        let options = self.ser.options();
+        if let Some(sorted) = &mut self.sorted {
+            sorted.entry_starts.push(sorted.buffer.len());
+        }
+        if !options.stringify_map_keys {
+            return self.element(key);
+        }
+        match &mut self.sorted {
+            Some(sorted) => serialize_into(&mut sorted.buffer, options, |s| {
+                key.serialize(MapKeySerializer(s))
+            }),
+            None => self
+                .ser
+                .write_buffered(|s| key.serialize(MapKeySerializer(s))),
+        }
+    }
+
+    fn end(self) -> Result<()> {
+        if let Some(mut sorted) = self.sorted {
+            if !sorted.entry_starts.is_empty() {
+                sort_entries(&mut sorted.buffer, &sorted.entry_starts);
+            }
+            self.ser.writer.write_all(&sorted.buffer)?;
+        }
+        self.ser.end_container(&self.placeholder)?;
+        if let Some(variant) = &self.variant {
+            self.ser.end_container(variant)?;
+        }
+        Ok(())
+    }
+}
+
+/// Serialize with the in-memory serializer, appending to `buffer`
+fn serialize_into(
+    buffer: &mut Vec<u8>,
+    options: Options,
+    f: impl FnOnce(&mut Serializer) -> Result<()>,
+) -> Result<()> {
+    let mut serializer = Serializer::from_options(options);
+    std::mem::swap(buffer, &mut serializer.buffer);
+    let r = f(&mut serializer);
+    std::mem::swap(buffer, &mut serializer.buffer);
+    r
+}
+
+/// Forward serializer methods to the in-memory serializer
+macro_rules! buffered {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<()> {
+                self.write_buffered(|s| ser::Serializer::$method(s, $($arg),*))
+            }
+        )*
+    };
+}
+
+impl<'a, W: Write + Seek> ser::Serializer for &'a mut SeekableSerializer<W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Container<'a, W>;
+    type SerializeTuple = Container<'a, W>;
+    type SerializeTupleStruct = Container<'a, W>;
+    type SerializeTupleVariant = Container<'a, W>;
+    type SerializeMap = Container<'a, W>;
+    type SerializeStruct = Container<'a, W>;
+    type SerializeStructVariant = Container<'a, W>;
+
+    fn is_human_readable(&self) -> bool {
+        self.options().human_readable
+    }
+
+    buffered! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i64(v: i64);
+        serialize_i128(v: i128);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_u128(v: u128);
+        serialize_f32(v: f32);
+        serialize_f64(v: f64);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str
+        );
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        if name == crate::raw::RAW_ELEMENT_TOKEN {
+            return value.serialize(RawBytesSerializer(&mut self.writer));
+        }
+        #[cfg(feature = "half")]
+        let is_scalar = name == crate::number::RAW_NUMBER_TOKEN
+            || name == crate::float16::F16_TOKEN;
+        #[cfg(not(feature = "half"))]
+        let is_scalar = name == crate::number::RAW_NUMBER_TOKEN;
+        if is_scalar {
+            return self.write_buffered(|s| {
+                ser::Serializer::serialize_newtype_struct(s, name, value)
+            });
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        let placeholder = self.start_variant(variant)?;
+        value.serialize(&mut *self)?;
+        self.end_container(&placeholder)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Container<'a, W>> {
+        self.container(ElementType::Array, None)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Container<'a, W>> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Container<'a, W>> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Container<'a, W>> {
+        self.container(ElementType::Array, Some(variant))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Container<'a, W>> {
+        self.object(None)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Container<'a, W>> {
+        self.object(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Container<'a, W>> {
+        self.object(Some(variant))
+    }
+}
+
+impl<W: Write + Seek> ser::SerializeSeq for Container<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Container::end(self)
+    }
+}
+
+impl<W: Write + Seek> ser::SerializeTuple for Container<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Container::end(self)
+    }
+}
+
+impl<W: Write + Seek> ser::SerializeTupleStruct for Container<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Container::end(self)
+    }
+}
+
+impl<W: Write + Seek> ser::SerializeTupleVariant for Container<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Container::end(self)
+    }
+}
+
+impl<W: Write + Seek> ser::SerializeMap for Container<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
+        self.key(key)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Container::end(self)
+    }
+}
+
+impl<W: Write + Seek> ser::SerializeStruct for Container<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.key(key)?;
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Container::end(self)
+    }
+}
+
+impl<W: Write + Seek> ser::SerializeStructVariant for Container<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.key(key)?;
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Container::end(self)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::zero_sized_map_values)]
+mod tests {
+    use super::*;
+    use crate::bytes::BytesEncoding;
+    use crate::extract::split_element;
+    use crate::header::write_minimal_header;
+    use crate::options::NonFiniteFloats;
+    use crate::{RawElement, RawNumber};
+    use serde_derive::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Dot,
+        Circle(f64),
+        Line(i32, i32),
+        // out of order, to be sorted with `sort_keys`
+        Rect { w: u8, h: u8 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Drawing {
+        name: String,
+        shapes: Vec<Shape>,
+        tags: BTreeMap<String, Vec<Option<bool>>>,
+        origin: (i8, i8),
+    }
+
+    fn to_cursor<T: Serialize>(value: &T) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        to_seekable_writer(&mut cursor, value).unwrap();
+        cursor.into_inner()
+    }
+
+    /// Re-encode every array and object with the smallest header
+    fn minimize(data: &[u8]) -> Vec<u8> {
+        let (element, rest) = split_element(data).unwrap();
+        assert!(rest.is_empty());
+        let mut out = Vec::new();
+        match element.header.element_type {
+            ElementType::Array | ElementType::Object => {
+                let mut payload = Vec::new();
+                let mut children = element.payload;
+                while !children.is_empty() {
+                    let (child, rest) = split_element(children).unwrap();
+                    payload.extend(minimize(child.bytes));
+                    children = rest;
+                }
+                write_minimal_header(
+                    &mut out,
+                    element.header.element_type,
+                    payload.len() as u64,
+                );
+                out.extend(payload);
+            }
+            _ => out.extend_from_slice(element.bytes),
+        }
+        out
+    }
+
+    #[test]
+    fn test_scalars_match_to_vec() {
+        assert_eq!(to_cursor(&42), crate::to_vec(&42).unwrap());
+        assert_eq!(to_cursor(&"hi"), crate::to_vec(&"hi").unwrap());
+        assert_eq!(to_cursor(&Shape::Dot), crate::to_vec(&Shape::Dot).unwrap());
+    }
+
+    #[test]
+    fn test_nested_containers_are_backpatched() {
+        // [[1]]
+        assert_eq!(
+            to_cursor(&vec![vec![1]]),
+            b"\xfb\0\0\0\0\0\0\0\x0b\xfb\0\0\0\0\0\0\0\x02\x131"
+        );
+        let drawing = Drawing {
+            name: "sketch".to_string(),
+            shapes: vec![
+                Shape::Dot,
+                Shape::Circle(1.5),
+                Shape::Line(-1, 2),
+                Shape::Rect { w: 3, h: 4 },
+            ],
+            tags: [
+                ("a".to_string(), vec![Some(true), None]),
+                ("b".to_string(), vec![]),
+            ]
+            .into_iter()
+            .collect(),
+            origin: (0, -1),
+        };
+        let streamed = to_cursor(&drawing);
+        let in_memory = crate::to_vec(&drawing).unwrap();
+        assert!(streamed.len() > in_memory.len());
+        assert_eq!(crate::from_slice::<Drawing>(&streamed).unwrap(), drawing);
+        assert_eq!(crate::from_slice::<Drawing>(&in_memory).unwrap(), drawing);
+    }
+
+    #[test]
+    fn test_writes_at_the_current_position() {
+        let mut cursor = Cursor::new(b"prefix".to_vec());
+        cursor.seek(SeekFrom::End(0)).unwrap();
+        to_seekable_writer(&mut cursor, &vec![true]).unwrap();
+        let data = cursor.into_inner();
+        assert_eq!(&data[..6], b"prefix");
+        assert_eq!(crate::from_slice::<Vec<bool>>(&data[6..]).unwrap(), [true]);
+    }
+
+    #[test]
+    fn test_options_match_to_vec_with_options() {
+        #[serde_with::serde_as]
+        #[derive(Serialize)]
+        struct Doc {
+            zeta: f64,
+            alpha: String,
+            #[serde_as(as = "serde_with::Bytes")]
+            bytes: Vec<u8>,
+            shapes: Vec<Shape>,
+            counts: BTreeMap<i32, f32>,
+            flags: BTreeMap<bool, ()>,
+            infinite: f64,
+            address: std::net::Ipv4Addr,
+            raw: RawElement,
+            number: RawNumber,
+        }
+        let doc = Doc {
+            zeta: 1.5,
+            alpha: "a\"b\u{1}".to_string(),
+            bytes: vec![0, 255],
+            shapes: vec![
+                Shape::Dot,
+                Shape::Circle(0.25),
+                Shape::Line(-1, 2),
+                Shape::Rect { w: 3, h: 4 },
+            ],
+            counts: [(-1, 0.5), (2, 1.0), (10, -2.0)].into_iter().collect(),
+            flags: [(false, ()), (true, ())].into_iter().collect(),
+            infinite: f64::NEG_INFINITY,
+            address: std::net::Ipv4Addr::LOCALHOST,
+            raw: RawElement {
+                element_type: ElementType::Array,
+                payload: b"\x131".to_vec(),
+            },
+            number: crate::from_slice(b"\x440x1f").unwrap(),
+        };
+        let all = Options {
+            sort_keys: true,
+            binary_float: true,
+            binary_int: true,
+            escape_policy: Options::sqlite_strings,
+            bytes_encoding: BytesEncoding::Base64,
+            enum_as_tuple: true,
+            stringify_map_keys: true,
+            human_readable: false,
+            ..Options::default()
+        };
+        let option_sets = [
+            Options::default(),
+            Options {
+                sort_keys: true,
+                ..Options::default()
+            },
+            Options {
+                binary_float: true,
+                ..Options::default()
+            },
+            Options {
+                escape_policy: Options::sqlite_strings,
+                ..Options::default()
+            },
+            Options {
+                bytes_encoding: BytesEncoding::Hex,
+                ..Options::default()
+            },
+            Options {
+                enum_as_tuple: true,
+                ..Options::default()
+            },
+            Options {
+                stringify_map_keys: true,
+                ..Options::default()
+            },
+            Options {
+                human_readable: false,
+                ..Options::default()
+            },
+            all,
+        ];
+        for options in option_sets {
+            let mut cursor = Cursor::new(Vec::new());
+            to_seekable_writer_with_options(&mut cursor, &doc, options)
+                .unwrap();
+            let expected = crate::to_vec_with_options(&doc, options).unwrap();
+            assert_eq!(minimize(&cursor.into_inner()), expected, "{options:?}");
+        }
+    }
+
+    #[test]
+    fn test_newtype_structs_are_streamed() {
+        #[derive(Serialize)]
+        struct Wrapper(Vec<i32>);
+
+        // [1], with the placeholder header of a streamed array
+        assert_eq!(
+            to_cursor(&Wrapper(vec![1])),
+            b"\xfb\0\0\0\0\0\0\0\x02\x131"
+        );
+    }
+
+    #[test]
+    fn test_rejected_non_finite_float() {
+        let options = Options {
+            non_finite_floats: NonFiniteFloats::Reject,
+            ..Options::default()
+        };
+        let mut cursor = Cursor::new(Vec::new());
+        let result =
+            to_seekable_writer_with_options(&mut cursor, &[f64::NAN], options);
+        assert!(matches!(result, Err(Error::NonFiniteFloat(_))));
+    }
+}
@@ -0,0 +1,44 @@
+use rusqlite::{Row, RowIndex, Statement};
+use serde::de::DeserializeOwned;
+
+/// Read JSONB columns from [`rusqlite`] rows directly into Rust values.
+///
+/// ```no_run
+/// use serde_sqlite_jsonb::RowJsonbExt;
+/// # fn f(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+/// let tags: Vec<String> =
+///     conn.query_row("select jsonb('[\"a\", \"b\"]')", [], |row| {
+///         row.get_jsonb(0)
+///     })?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait RowJsonbExt {
+    /// Fetch the blob in column `idx`, and deserialize it from JSONB.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`rusqlite::Error::FromSqlConversionFailure`] if the column
+    /// does not contain a blob, or if it cannot be deserialized into `T`.
+    fn get_jsonb<I: RowIndex, T: DeserializeOwned>(
+        &self,
+        idx: I,
+    ) -> rusqlite::Result<T>;
+}
+
+impl RowJsonbExt for Row<'_> {
+    fn get_jsonb<I: RowIndex, T: DeserializeOwned>(
+        &self,
+        idx: I,
+    ) -> rusqlite::Result<T> {
+        let idx = idx.idx(AsRef::<Statement>::as_ref(self))?;
+        let value = self.get_ref(idx)?;
+        let conversion_failure = |e: Box<
+            dyn std::error::Error + Send + Sync,
+        >| {
+            rusqlite::Error::FromSqlConversionFailure(idx, value.data_type(), e)
+        };
+        let blob = value.as_blob().map_err(|e| conversion_failure(e.into()))?;
+        crate::from_slice(blob).map_err(|e| conversion_failure(e.into()))
+    }
+}
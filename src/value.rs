@@ -0,0 +1,201 @@
+//! Conversion of a JSONB blob to a `serde_json::Value` without recursion.
+
+use crate::error::{Error, Result};
+use crate::extract::{split_element, Element};
+use crate::header::ElementType;
+use serde_json::{Map, Value};
+
+/// Decode the JSONB blob in `data` into a [`serde_json::Value`].
+///
+/// This gives the same result as [`from_slice`](crate::from_slice), but
+/// walks nested arrays and objects with a stack on the heap instead of
+/// recursive calls, so that values nested however deeply cannot overflow
+/// the stack.
+///
+/// ```
+/// // {"a": [1, true]}
+/// let value = serde_sqlite_jsonb::to_json_value(b"\x6c\x17a\x3b\x131\x01")?;
+/// assert_eq!(value, serde_json::json!({"a": [1, true]}));
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `data` is not a single valid JSONB element.
+pub fn to_json_value(data: &[u8]) -> Result<Value> {
+    let (mut element, rest) = split_element(data)?;
+    if !rest.is_empty() {
+        return Err(Error::TrailingCharacters);
+    }
+    let mut stack: Vec<Frame> = Vec::new();
+    loop {
+        let mut value = match element.header.element_type {
+            ElementType::Array => {
+                stack.push(Frame::new(element, Container::Array(Vec::new())));
+                None
+            }
+            ElementType::Object => {
+                let container = Container::Object(Map::new(), None);
+                stack.push(Frame::new(element, container));
+                None
+            }
+            _ => Some(scalar_value(&element)?),
+        };
+        // Add the value to its container, closing the containers that are
+        // complete, until an element remains to be read.
+        element = loop {
+            let frame = match stack.last_mut() {
+                Some(frame) => frame,
+                None => return value.ok_or(Error::Empty),
+            };
+            if let Some(v) = value.take() {
+                frame.container.push(v);
+            }
+            if frame.rest.is_empty() {
+                value = stack.pop().map(Frame::finish).transpose()?;
+                continue;
+            }
+            let (next, rest) = split_element(frame.rest)?;
+            frame.rest = rest;
+            if let Container::Object(_, key @ None) = &mut frame.container {
+                *key = Some(key_string(&next)?);
+                continue;
+            }
+            break next;
+        };
+    }
+}
+
+/// An array or object being decoded, with its elements that remain to be read
+struct Frame<'a> {
+    rest: &'a [u8],
+    container: Container,
+}
+
+enum Container {
+    Array(Vec<Value>),
+    /// The entries read so far, and the key whose value comes next
+    Object(Map<String, Value>, Option<String>),
+}
+
+impl<'a> Frame<'a> {
+    fn new(element: Element<'a>, container: Container) -> Self {
+        Frame {
+            rest: element.payload,
+            container,
+        }
+    }
+
+    fn finish(self) -> Result<Value> {
+        match self.container {
+            Container::Array(items) => Ok(Value::Array(items)),
+            Container::Object(map, None) => Ok(Value::Object(map)),
+            Container::Object(_, Some(key)) => {
+                Err(Error::KeyWithoutValue(Some(key)))
+            }
+        }
+    }
+}
+
+impl Container {
+    fn push(&mut self, value: Value) {
+        match self {
+            Container::Array(items) => items.push(value),
+            // like serde_json, the last value of a repeated key is kept
+            Container::Object(map, key) => {
+                if let Some(key) = key.take() {
+                    map.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+fn key_string(element: &Element) -> Result<String> {
+    match element.header.element_type {
+        ElementType::Text | ElementType::TextRaw => {
+            String::from_utf8(element.payload.to_vec()).map_err(Error::Utf8)
+        }
+        ElementType::TextJ | ElementType::Text5 => {
+            crate::from_slice(element.bytes)
+        }
+        t => Err(Error::NonStringKey(t)),
+    }
+}
+
+fn scalar_value(element: &Element) -> Result<Value> {
+    match element.header.element_type {
+        ElementType::Text | ElementType::TextRaw => {
+            key_string(element).map(Value::String)
+        }
+        _ => crate::from_slice(element.bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::write_minimal_header;
+    use serde_json::json;
+
+    #[test]
+    fn test_to_json_value() {
+        // {"a": [1, 2.5, "x\n"], "b": {"c": null, "d": false}, "e": {}}
+        let blob = b"\xcc\x19\x17a\xab\x131\x352.5\x38x\\n\x17b\x6c\x17c\x00\x17d\x02\x17e\x0c";
+        let expected = json!({"a": [1, 2.5, "x\n"], "b": {"c": null, "d": false}, "e": {}});
+        assert_eq!(to_json_value(blob).unwrap(), expected);
+        assert_eq!(
+            to_json_value(blob).unwrap(),
+            crate::from_slice::<Value>(blob).unwrap()
+        );
+        assert_eq!(to_json_value(b"\x2a\xc3\xa9").unwrap(), json!("é"));
+        assert_eq!(to_json_value(b"\x0b").unwrap(), json!([]));
+        // repeated keys keep their last value
+        assert_eq!(
+            to_json_value(b"\x8c\x17a\x131\x17a\x132").unwrap(),
+            json!({"a": 2})
+        );
+    }
+
+    #[test]
+    fn test_to_json_value_errors() {
+        assert!(matches!(
+            to_json_value(b"\x0b\x00"),
+            Err(Error::TrailingCharacters)
+        ));
+        assert!(matches!(
+            to_json_value(b"\x2c\x17a"),
+            Err(Error::KeyWithoutValue(Some(k))) if k == "a"
+        ));
+        // a key that is not a string
+        assert!(matches!(
+            to_json_value(b"\x3c\x131\x00"),
+            Err(Error::NonStringKey(ElementType::Int))
+        ));
+        // the array announces more bytes than it holds
+        assert!(to_json_value(b"\x3b\x0b").is_err());
+    }
+
+    #[test]
+    fn test_deeply_nested_array() {
+        const DEPTH: usize = 10_000;
+        // the headers of the arrays, from the innermost one outwards
+        let mut headers = Vec::with_capacity(DEPTH);
+        let mut payload_size = 0u64;
+        for _ in 0..DEPTH {
+            let mut header = Vec::new();
+            write_minimal_header(&mut header, ElementType::Array, payload_size);
+            payload_size += header.len() as u64;
+            headers.push(header);
+        }
+        let blob: Vec<u8> = headers.into_iter().rev().flatten().collect();
+        let mut value = to_json_value(&blob).unwrap();
+        for _ in 1..DEPTH {
+            value = match value {
+                Value::Array(mut items) if items.len() == 1 => items.remove(0),
+                other => panic!("unexpected value: {other}"),
+            };
+        }
+        assert_eq!(value, json!([]));
+    }
+}
@@ -0,0 +1,237 @@
+//! A read-only view over a JSONB blob, navigated without deserializing it.
+
+use crate::error::{Error, Result};
+use crate::extract::{key_matches, split_element, Element};
+use crate::header::{is_jsonb, ElementType};
+use crate::number::Number;
+use std::borrow::Cow;
+
+/// A borrowed view of a single JSONB element.
+///
+/// Creating a view only checks that the blob holds exactly one element.
+/// Navigating into objects and arrays skips over the elements it does not
+/// need using their headers, and nested elements are only checked when
+/// they are reached.
+///
+/// ```
+/// use serde_sqlite_jsonb::JsonbView;
+///
+/// // {"id": 1, "tags": ["a"]}
+/// let blob: &[u8] = b"\xcc\x0d\x27id\x131\x47tags\x2b\x17a";
+/// let view = JsonbView::try_from(blob)?;
+/// assert_eq!(view.get("id")?.and_then(|id| id.as_i64()), Some(1));
+/// let tags = view.get("tags")?.unwrap();
+/// assert_eq!(tags.index(0)?.unwrap().as_str().as_deref(), Some("a"));
+/// # Ok::<(), serde_sqlite_jsonb::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct JsonbView<'a> {
+    element: Element<'a>,
+}
+
+impl<'a> TryFrom<&'a [u8]> for JsonbView<'a> {
+    type Error = Error;
+
+    fn try_from(data: &'a [u8]) -> Result<Self> {
+        is_jsonb(data)?;
+        let (element, _) = split_element(data)?;
+        Ok(JsonbView { element })
+    }
+}
+
+impl<'a> JsonbView<'a> {
+    /// The type of the element
+    #[must_use]
+    pub fn element_type(&self) -> ElementType {
+        self.element.header.element_type
+    }
+
+    /// The encoded element, header included
+    #[must_use]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.element.bytes
+    }
+
+    /// Whether the element is a JSON `null`
+    #[must_use]
+    pub fn is_null(&self) -> bool {
+        self.element_type() == ElementType::Null
+    }
+
+    /// The value of a `true` or `false` element
+    #[must_use]
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.element_type() {
+            ElementType::True => Some(true),
+            ElementType::False => Some(false),
+            _ => None,
+        }
+    }
+
+    /// The value of an integer element, if it fits in an `i64`
+    #[must_use]
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.element_type() {
+            ElementType::Int | ElementType::Int5 | ElementType::Reserved14 => {
+                match crate::from_slice(self.element.bytes).ok()? {
+                    Number::Int(i) => i64::try_from(i).ok(),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The value of a numeric element, as a float
+    #[must_use]
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.element_type() {
+            ElementType::Int
+            | ElementType::Int5
+            | ElementType::Float
+            | ElementType::Float5
+            | ElementType::BinaryFloat
+            | ElementType::Reserved14 => {
+                crate::from_slice(self.element.bytes).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// The value of a string element. Strings without escapes are borrowed
+    /// from the blob, the others are decoded.
+    #[must_use]
+    pub fn as_str(&self) -> Option<Cow<'a, str>> {
+        match self.element_type() {
+            ElementType::Text | ElementType::TextRaw => {
+                std::str::from_utf8(self.element.payload)
+                    .ok()
+                    .map(Cow::Borrowed)
+            }
+            ElementType::TextJ | ElementType::Text5 => {
+                crate::from_slice::<String>(self.element.bytes)
+                    .ok()
+                    .map(Cow::Owned)
+            }
+            _ => None,
+        }
+    }
+
+    /// The value of the field `key` of an object.
+    /// Returns `None` if there is no such key.
+    /// If the key appears several times, the first occurrence is used.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the element is not an object, or if it is truncated.
+    pub fn get(&self, key: &str) -> Result<Option<JsonbView<'a>>> {
+        let mut entries = self.container_payload(ElementType::Object)?;
+        while !entries.is_empty() {
+            let (key_element, rest) = split_element(entries)?;
+            let (value, rest) = split_element(rest)?;
+            if key_matches(&key_element, key)? {
+                return Ok(Some(JsonbView { element: value }));
+            }
+            entries = rest;
+        }
+        Ok(None)
+    }
+
+    /// The `n`th item of an array, starting from zero.
+    /// Returns `None` if the array is shorter than that.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the element is not an array, or if it is truncated.
+    pub fn index(&self, n: usize) -> Result<Option<JsonbView<'a>>> {
+        let mut items = self.container_payload(ElementType::Array)?;
+        for i in 0.. {
+            if items.is_empty() {
+                break;
+            }
+            let (item, rest) = split_element(items)?;
+            if i == n {
+                return Ok(Some(JsonbView { element: item }));
+            }
+            items = rest;
+        }
+        Ok(None)
+    }
+
+    fn container_payload(&self, expected: ElementType) -> Result<&'a [u8]> {
+        if self.element_type() == expected {
+            Ok(self.element.payload)
+        } else {
+            Err(Error::expected(expected.name(), self.element_type()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(data: &[u8]) -> JsonbView<'_> {
+        JsonbView::try_from(data).unwrap()
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert!(view(b"\x00").is_null());
+        assert_eq!(view(b"\x01").as_bool(), Some(true));
+        assert_eq!(view(b"\x02").as_bool(), Some(false));
+        assert_eq!(view(b"\x23-7").as_i64(), Some(-7));
+        assert_eq!(view(b"\x440x1f").as_i64(), Some(31));
+        assert_eq!(view(b"\x351.5").as_f64(), Some(1.5));
+        assert_eq!(view(b"\x2342").as_f64(), Some(42.0));
+        assert_eq!(view(b"\x351.5").as_i64(), None);
+        assert_eq!(view(b"\x17a").as_i64(), None);
+        assert!(matches!(
+            view(b"\x27hi").as_str(),
+            Some(Cow::Borrowed("hi"))
+        ));
+        assert_eq!(view(b"\x48a\\\"b").as_str().as_deref(), Some("a\"b"));
+        assert_eq!(view(b"\x01").as_str(), None);
+        assert_eq!(view(b"\x27hi").element_type(), ElementType::Text);
+        assert_eq!(view(b"\x27hi").as_bytes(), b"\x27hi");
+    }
+
+    #[test]
+    fn test_nested() {
+        // {"a": {"b": [1, "x", null]}, "c": true}
+        let blob = b"\xcc\x0e\x17a\x8c\x17b\x5b\x131\x17x\x00\x17c\x01";
+        let root = view(blob);
+        let b = root.get("a").unwrap().unwrap().get("b").unwrap().unwrap();
+        assert_eq!(b.element_type(), ElementType::Array);
+        assert_eq!(b.index(0).unwrap().unwrap().as_i64(), Some(1));
+        assert_eq!(b.index(1).unwrap().unwrap().as_str().unwrap(), "x");
+        assert!(b.index(2).unwrap().unwrap().is_null());
+        assert!(b.index(3).unwrap().is_none());
+        assert_eq!(root.get("c").unwrap().unwrap().as_bool(), Some(true));
+        assert!(root.get("missing").unwrap().is_none());
+        assert!(matches!(
+            root.index(0),
+            Err(Error::UnexpectedType {
+                found: ElementType::Object,
+                ..
+            })
+        ));
+        assert!(matches!(
+            b.get("a"),
+            Err(Error::UnexpectedType {
+                found: ElementType::Array,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(JsonbView::try_from(&b""[..]).is_err());
+        // trailing bytes after the element
+        assert!(JsonbView::try_from(&b"\x00\x00"[..]).is_err());
+        // the nested item claims more bytes than its array holds
+        let truncated = view(b"\x2b\x27a");
+        assert!(truncated.index(0).is_err());
+    }
+}
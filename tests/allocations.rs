@@ -0,0 +1,77 @@
+//! Count the allocations made while deserializing, to catch regressions
+//! in the reuse of the deserializer's scratch buffer.
+//! Numbers are parsed with `serde_json`: the json5 parser allocates on its own.
+#![cfg(feature = "serde_json")]
+
+use serde_derive::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// The same shape as the struct of the `vs_json` benchmark
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Person {
+    id: usize,
+    name: String,
+    phone_numbers: Vec<String>,
+    active: bool,
+    data: String,
+}
+
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let value = f();
+    (value, ALLOCATIONS.load(Ordering::Relaxed) - before)
+}
+
+#[test]
+fn test_field_names_do_not_allocate() {
+    let person = Person {
+        id: 123,
+        name: "John Doe".to_string(),
+        phone_numbers: vec!["1234".to_string()],
+        active: true,
+        data: "x".repeat(1000),
+    };
+    let blob = serde_sqlite_jsonb::to_vec(&person).unwrap();
+    let (decoded, allocations) = count_allocations(|| {
+        serde_sqlite_jsonb::from_slice::<Person>(&blob).unwrap()
+    });
+    assert_eq!(decoded, person);
+    // the four strings and the vector, plus the growth of the scratch buffer
+    assert!(allocations <= 8, "{allocations} allocations");
+
+    let people: Vec<Person> = (0..100)
+        .map(|id| Person {
+            id,
+            name: format!("person {id}"),
+            phone_numbers: vec![],
+            active: id % 2 == 0,
+            data: String::new(),
+        })
+        .collect();
+    let blob = serde_sqlite_jsonb::to_vec(&people).unwrap();
+    let (decoded, allocations) = count_allocations(|| {
+        serde_sqlite_jsonb::from_slice::<Vec<Person>>(&blob).unwrap()
+    });
+    assert_eq!(decoded, people);
+    // one per name, and not one per field name (this used to be 606)
+    assert!(allocations < 120, "{allocations} allocations");
+}
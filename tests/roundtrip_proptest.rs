@@ -0,0 +1,74 @@
+//! Property tests checking that arbitrary JSON documents survive
+//! a round-trip through the JSONB serializer and deserializer.
+//!
+//! Run them with `cargo test --test roundtrip_proptest`.
+//! Set the `PROPTEST_CASES` environment variable to run more cases,
+//! for instance `PROPTEST_CASES=10000 cargo test --test roundtrip_proptest`.
+#![cfg(feature = "serde_json")]
+
+use proptest::prelude::*;
+use serde_json::{Map, Number, Value};
+use serde_sqlite_jsonb::Options;
+
+fn arb_number() -> impl Strategy<Value = Number> {
+    prop_oneof![
+        any::<i64>().prop_map(Number::from),
+        any::<u64>().prop_map(Number::from),
+        any::<f64>().prop_filter_map("finite floats only", Number::from_f64),
+    ]
+}
+
+fn arb_json() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        arb_number().prop_map(Value::Number),
+        any::<String>().prop_map(Value::String),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..8).prop_map(Value::Array),
+            prop::collection::btree_map(any::<String>(), inner, 0..8).prop_map(
+                |m| Value::Object(m.into_iter().collect::<Map<_, _>>())
+            ),
+        ]
+    })
+}
+
+fn assert_roundtrip(value: &Value, options: Options) {
+    let blob = serde_sqlite_jsonb::to_vec_with_options(value, options).unwrap();
+    let decoded: Value =
+        serde_sqlite_jsonb::from_slice_with_options(&blob, options).unwrap();
+    assert_eq!(&decoded, value);
+}
+
+proptest! {
+    #[test]
+    fn roundtrip_default(value in arb_json()) {
+        assert_roundtrip(&value, Options::default());
+    }
+
+    #[test]
+    fn roundtrip_binary_float(value in arb_json()) {
+        assert_roundtrip(&value, Options {
+            binary_float: true,
+            ..Options::default()
+        });
+    }
+
+    #[test]
+    fn roundtrip_sort_keys(value in arb_json()) {
+        assert_roundtrip(&value, Options {
+            sort_keys: true,
+            ..Options::default()
+        });
+    }
+
+    #[test]
+    fn roundtrip_not_human_readable(value in arb_json()) {
+        assert_roundtrip(&value, Options {
+            human_readable: false,
+            ..Options::default()
+        });
+    }
+}
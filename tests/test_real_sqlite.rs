@@ -21,6 +21,7 @@ enum PhoneNumber {
         number: String,
     },
     Custom(Option<i32>, String),
+    Unlisted,
 }
 
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
@@ -85,6 +86,28 @@ fn test_fetch_json_object() -> rusqlite::Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "rusqlite")]
+#[test]
+fn test_fetch_json_object_with_get_jsonb() -> rusqlite::Result<()> {
+    use serde_sqlite_jsonb::RowJsonbExt;
+    let conn = Connection::open_in_memory()?;
+    let person: Person = conn.query_row(
+        r#"select jsonb('{
+        "id": 1,
+        "name": "John Doe",
+        "phone_numbers": [{"National": "1234"}],
+        "is_champion": true,
+        "data": [1, 2, 3]
+    }')"#,
+        [],
+        |row| row.get_jsonb(0),
+    )?;
+    assert_eq!(person.name, "John Doe");
+    assert_eq!(person.data, vec![1, 2, 3]);
+
+    Ok(())
+}
+
 #[test]
 fn test_large_object_as_blob() -> rusqlite::Result<()> {
     let conn = Connection::open_in_memory()?;
@@ -345,6 +368,76 @@ fn test_normal_enum_array() -> rusqlite::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_phone_number_roundtrip_without_sqlite() {
+    let numbers = [
+        PhoneNumber::Internal(-42),
+        PhoneNumber::National("1234".to_string()),
+        PhoneNumber::International {
+            country_code: Some(33),
+            number: "1234".to_string(),
+        },
+        PhoneNumber::International {
+            country_code: None,
+            number: String::new(),
+        },
+        PhoneNumber::Custom(Some(1), "5678".to_string()),
+        PhoneNumber::Custom(None, "5678".to_string()),
+        PhoneNumber::Unlisted,
+    ];
+    let enum_as_tuple = serde_sqlite_jsonb::Options {
+        enum_as_tuple: true,
+        ..Default::default()
+    };
+    for options in [Default::default(), enum_as_tuple] {
+        for number in &numbers {
+            let encoded =
+                serde_sqlite_jsonb::to_vec_with_options(number, options)
+                    .unwrap();
+            let decoded: PhoneNumber =
+                serde_sqlite_jsonb::from_slice_with_options(&encoded, options)
+                    .unwrap();
+            assert_eq!(&decoded, number, "{encoded:x?}");
+        }
+        let encoded =
+            serde_sqlite_jsonb::to_vec_with_options(&numbers, options).unwrap();
+        let decoded: Vec<PhoneNumber> =
+            serde_sqlite_jsonb::from_slice_with_options(&encoded, options)
+                .unwrap();
+        assert_eq!(decoded, numbers);
+    }
+}
+
+#[test]
+fn test_mixed_unit_and_data_variants_array() -> rusqlite::Result<()> {
+    // unit variants are strings and data variants objects, in the same array
+    let numbers = vec![
+        PhoneNumber::Unlisted,
+        PhoneNumber::National("1234".to_string()),
+        PhoneNumber::Unlisted,
+        PhoneNumber::Internal(7),
+    ];
+    let encoded = serde_sqlite_jsonb::to_vec(&numbers).unwrap();
+    let decoded: Vec<PhoneNumber> =
+        serde_sqlite_jsonb::from_slice(&encoded).unwrap();
+    assert_eq!(decoded, numbers);
+
+    let conn = Connection::open_in_memory()?;
+    let json: String =
+        conn.query_row("select json(?)", [&encoded], |row| row.get(0))?;
+    assert_eq!(
+        json,
+        r#"["Unlisted",{"National":"1234"},"Unlisted",{"Internal":7}]"#
+    );
+    let blob: Vec<u8> =
+        conn.query_row("select jsonb(?)", [&json], |row| row.get(0))?;
+    let decoded: Vec<PhoneNumber> =
+        serde_sqlite_jsonb::from_slice(&blob).unwrap();
+    assert_eq!(decoded, numbers);
+
+    Ok(())
+}
+
 #[test]
 fn test_externally_tagged_enum_array() -> rusqlite::Result<()> {
     let conn = Connection::open_in_memory()?;
@@ -392,3 +485,445 @@ fn test_print_test() -> rusqlite::Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn test_flattened_catch_all_map() -> rusqlite::Result<()> {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Record {
+        id: i32,
+        name: String,
+        #[serde(flatten)]
+        extra: HashMap<String, serde_json::Value>,
+    }
+
+    let conn = Connection::open_in_memory()?;
+    let blob: Vec<u8> = conn.query_row(
+        r#"select jsonb('{
+            "tags": ["a", "b"],
+            "id": 7,
+            "nested": {"deep": [1, 2.5, null]},
+            "name": "seven",
+            "active": false
+        }')"#,
+        [],
+        |row| row.get(0),
+    )?;
+    let record: Record = serde_sqlite_jsonb::from_slice(&blob).unwrap();
+    assert_eq!(
+        record,
+        Record {
+            id: 7,
+            name: "seven".to_string(),
+            extra: [
+                ("tags".to_string(), serde_json::json!(["a", "b"])),
+                (
+                    "nested".to_string(),
+                    serde_json::json!({"deep": [1, 2.5, null]})
+                ),
+                ("active".to_string(), serde_json::json!(false)),
+            ]
+            .into_iter()
+            .collect()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_flattened_typed_map() -> rusqlite::Result<()> {
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Config {
+        name: String,
+        #[serde(flatten)]
+        settings: BTreeMap<String, i64>,
+    }
+
+    let conn = Connection::open_in_memory()?;
+    let blob: Vec<u8> = conn.query_row(
+        r#"select jsonb('{
+            "retries": 3,
+            "name": "worker",
+            "timeout": -1,
+            "limit": 9007199254740993,
+            "port": 8080
+        }')"#,
+        [],
+        |row| row.get(0),
+    )?;
+    let config: Config = serde_sqlite_jsonb::from_slice(&blob).unwrap();
+    let expected = Config {
+        name: "worker".to_string(),
+        settings: [
+            ("retries".to_string(), 3),
+            ("timeout".to_string(), -1),
+            ("limit".to_string(), 9_007_199_254_740_993),
+            ("port".to_string(), 8080),
+        ]
+        .into_iter()
+        .collect(),
+    };
+    assert_eq!(config, expected);
+
+    // the flattened entries are written back next to the named field
+    let encoded = serde_sqlite_jsonb::to_vec(&config).unwrap();
+    let json: String =
+        conn.query_row("select json(?)", [&encoded], |row| row.get(0))?;
+    assert_eq!(
+        json,
+        r#"{"name":"worker","limit":9007199254740993,"port":8080,"retries":3,"timeout":-1}"#
+    );
+
+    // a catch-all value of the wrong type is an error, not a dropped entry
+    let blob: Vec<u8> = conn.query_row(
+        r#"select jsonb('{"name": "worker", "retries": "many"}')"#,
+        [],
+        |row| row.get(0),
+    )?;
+    assert!(serde_sqlite_jsonb::from_slice::<Config>(&blob).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_envelope_with_raw_payload() -> rusqlite::Result<()> {
+    use serde_sqlite_jsonb::RawJsonb;
+
+    #[derive(Debug, Deserialize)]
+    struct Envelope {
+        kind: String,
+        payload: RawJsonb,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Message {
+        Point(Point),
+        Tags(Vec<String>),
+    }
+
+    let conn = Connection::open_in_memory()?;
+    let blob: Vec<u8> = conn.query_row(
+        r#"select jsonb('[
+            {"kind": "point", "payload": {"x": 1, "y": -2}},
+            {"payload": ["a", "b\n"], "kind": "tags"}
+        ]')"#,
+        [],
+        |row| row.get(0),
+    )?;
+    let envelopes: Vec<Envelope> =
+        serde_sqlite_jsonb::from_slice(&blob).unwrap();
+    let messages: Vec<Message> = envelopes
+        .iter()
+        .map(|envelope| {
+            let payload = envelope.payload.as_bytes();
+            match envelope.kind.as_str() {
+                "point" => Message::Point(
+                    serde_sqlite_jsonb::from_slice(payload).unwrap(),
+                ),
+                "tags" => Message::Tags(
+                    serde_sqlite_jsonb::from_slice(payload).unwrap(),
+                ),
+                kind => panic!("unknown kind {kind}"),
+            }
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            Message::Point(Point { x: 1, y: -2 }),
+            Message::Tags(vec!["a".to_string(), "b\n".to_string()]),
+        ]
+    );
+    // the payload is captured exactly as SQLite stored it
+    let json: String = conn.query_row(
+        "select json(?)",
+        [envelopes[1].payload.as_bytes()],
+        |row| row.get(0),
+    )?;
+    assert_eq!(json, r#"["a","b\n"]"#);
+
+    Ok(())
+}
+
+#[test]
+fn test_128_bit_integers() -> rusqlite::Result<()> {
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Wide {
+        big: u128,
+        small: i128,
+    }
+
+    let conn = Connection::open_in_memory()?;
+    let value = Wide {
+        big: u128::MAX,
+        small: i128::MIN,
+    };
+    let blob = serde_sqlite_jsonb::to_vec(&value).unwrap();
+    let json: String =
+        conn.query_row("select json(?)", [&blob], |row| row.get(0))?;
+    assert_eq!(
+        json,
+        r#"{"big":340282366920938463463374607431768211455,"small":-170141183460469231731687303715884105728}"#
+    );
+    // SQLite keeps the integers as they are when re-encoding them
+    let reencoded: Vec<u8> =
+        conn.query_row("select jsonb(json(?))", [&blob], |row| row.get(0))?;
+    let decoded: Wide = serde_sqlite_jsonb::from_slice(&reencoded).unwrap();
+    assert_eq!(decoded, value);
+    let big_type: String =
+        conn.query_row("select json_type(?, '$.big')", [&blob], |row| {
+            row.get(0)
+        })?;
+    assert_eq!(big_type, "integer");
+    // but extracting one as an SQL value loses precision
+    let extracted: rusqlite::types::Value =
+        conn.query_row("select json_extract(?, '$.big')", [&blob], |row| {
+            row.get(0)
+        })?;
+    assert_eq!(extracted, rusqlite::types::Value::Real(u128::MAX as f64));
+
+    Ok(())
+}
+
+#[test]
+fn test_seekable_writer_output() -> rusqlite::Result<()> {
+    let conn = Connection::open_in_memory()?;
+    let drawing = Drawing {
+        shapes: vec![
+            Shape::Circle { radius: 1.0 },
+            Shape::Square { size: 2.5 },
+        ],
+    };
+    let mut cursor = std::io::Cursor::new(Vec::new());
+    serde_sqlite_jsonb::to_seekable_writer(&mut cursor, &drawing).unwrap();
+    let streamed = cursor.into_inner();
+    let in_memory = serde_sqlite_jsonb::to_vec(&drawing).unwrap();
+    let (streamed_json, in_memory_json): (String, String) = conn.query_row(
+        "select json(?1), json(?2)",
+        [&streamed, &in_memory],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    assert_eq!(streamed_json, in_memory_json);
+
+    Ok(())
+}
+
+#[test]
+fn test_sqlite_strings_escape_policy() -> rusqlite::Result<()> {
+    let conn = Connection::open_in_memory()?;
+    let strings = ("plain", "quote\"back\\slash", "line\nbreak\u{1}");
+    let expected: Vec<u8> =
+        conn.query_row("select jsonb_array(?, ?, ?)", strings, |row| {
+            row.get(0)
+        })?;
+    let options = serde_sqlite_jsonb::Options {
+        escape_policy: serde_sqlite_jsonb::Options::sqlite_strings,
+        ..Default::default()
+    };
+    let blob =
+        serde_sqlite_jsonb::to_vec_with_options(&strings, options).unwrap();
+    assert_eq!(blob, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_chars_needing_escapes() -> rusqlite::Result<()> {
+    let conn = Connection::open_in_memory()?;
+    let chars = ('"', '\n', '\\', 'a');
+    let blob = serde_sqlite_jsonb::to_vec(&chars).unwrap();
+    let json: String =
+        conn.query_row("select json(?)", [&blob], |row| row.get(0))?;
+    assert_eq!(json, r#"["\"","\n","\\","a"]"#);
+
+    // back through sqlite, which stores the escaped chars as TextJ
+    let reencoded: Vec<u8> =
+        conn.query_row("select jsonb(?)", [&json], |row| row.get(0))?;
+    let decoded: (char, char, char, char) =
+        serde_sqlite_jsonb::from_slice(&reencoded).unwrap();
+    assert_eq!(decoded, chars);
+    let decoded: (char, char, char, char) =
+        serde_sqlite_jsonb::from_slice(&blob).unwrap();
+    assert_eq!(decoded, chars);
+
+    Ok(())
+}
+
+#[test]
+fn test_non_finite_floats() -> rusqlite::Result<()> {
+    let conn = Connection::open_in_memory()?;
+    let floats = [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1.5];
+    let blob = serde_sqlite_jsonb::to_vec(&floats).unwrap();
+    let expected: Vec<u8> = conn.query_row(
+        "select jsonb('[NaN, Infinity, -Infinity, 1.5]')",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!(blob, expected);
+    let json: String =
+        conn.query_row("select json(?)", [&blob], |row| row.get(0))?;
+    assert_eq!(json, "[null,9e999,-9e999,1.5]");
+    let decoded: Vec<Option<f64>> =
+        serde_sqlite_jsonb::from_slice(&blob).unwrap();
+    assert_eq!(
+        decoded,
+        [
+            None,
+            Some(f64::INFINITY),
+            Some(f64::NEG_INFINITY),
+            Some(1.5)
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_renamed_and_skipped_fields() -> rusqlite::Result<()> {
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Account {
+        user_name: String,
+        #[serde(rename = "mail")]
+        email_address: String,
+        #[serde(skip)]
+        session_token: Option<String>,
+        is_admin: bool,
+    }
+
+    let conn = Connection::open_in_memory()?;
+    let account = Account {
+        user_name: "ada".to_string(),
+        email_address: "ada@example.com".to_string(),
+        session_token: Some("secret".to_string()),
+        is_admin: true,
+    };
+    let blob = serde_sqlite_jsonb::to_vec(&account).unwrap();
+    let json: String =
+        conn.query_row("select json(?)", [&blob], |row| row.get(0))?;
+    assert_eq!(
+        json,
+        r#"{"userName":"ada","mail":"ada@example.com","isAdmin":true}"#
+    );
+    let user_name: String =
+        conn.query_row("select ? ->> '$.userName'", [&blob], |row| row.get(0))?;
+    assert_eq!(user_name, "ada");
+
+    let decoded: Account = serde_sqlite_jsonb::from_slice(&blob).unwrap();
+    assert_eq!(
+        decoded,
+        Account {
+            session_token: None,
+            ..account
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_lowercase_renamed_enums() -> rusqlite::Result<()> {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(tag = "kind", rename_all = "lowercase")]
+    enum Tagged {
+        Circle { radius: f64 },
+        BigSquare { size: f64 },
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum External {
+        Circle(f64),
+        Unit,
+    }
+
+    let conn = Connection::open_in_memory()?;
+    let blob: Vec<u8> = conn.query_row(
+        r#"select jsonb('[{"kind": "circle", "radius": 1.5},
+                         {"size": 2, "kind": "bigsquare"}]')"#,
+        [],
+        |row| row.get(0),
+    )?;
+    let shapes: Vec<Tagged> = serde_sqlite_jsonb::from_slice(&blob).unwrap();
+    assert_eq!(
+        shapes,
+        vec![
+            Tagged::Circle { radius: 1.5 },
+            Tagged::BigSquare { size: 2.0 }
+        ]
+    );
+
+    let blob: Vec<u8> = conn.query_row(
+        r#"select jsonb('[{"circle": 0.5}, "unit"]')"#,
+        [],
+        |row| row.get(0),
+    )?;
+    let values: Vec<External> = serde_sqlite_jsonb::from_slice(&blob).unwrap();
+    assert_eq!(values, vec![External::Circle(0.5), External::Unit]);
+
+    // the original variant names are not accepted
+    let blob: Vec<u8> =
+        conn.query_row(r#"select jsonb('"Unit"')"#, [], |row| row.get(0))?;
+    assert!(serde_sqlite_jsonb::from_slice::<External>(&blob).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_float5_from_sqlite() -> rusqlite::Result<()> {
+    let conn = Connection::open_in_memory()?;
+    // the floats are stored as Float5, except 1e+5, which is valid JSON
+    let blob: Vec<u8> = conn.query_row(
+        "select jsonb('[.5, 5., -.5e-2, 1e+5, 0x1F]')",
+        [],
+        |row| row.get(0),
+    )?;
+    let decoded = serde_sqlite_jsonb::from_slice::<Vec<f64>>(&blob);
+    if cfg!(feature = "serde_json5") {
+        assert_eq!(decoded.unwrap(), [0.5, 5.0, -0.005, 1e5, 31.0]);
+    } else {
+        assert!(matches!(
+            decoded,
+            Err(serde_sqlite_jsonb::Error::Json5Error(_))
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_matches_json_patch() -> rusqlite::Result<()> {
+    let conn = Connection::open_in_memory()?;
+    let cases = [
+        (r#"{"a":1,"b":2}"#, r#"{"a":3}"#),
+        (r#"{"a":1,"b":2}"#, r#"{"c":[true]}"#),
+        (r#"{"a":1,"b":2}"#, r#"{"a":null,"z":null}"#),
+        (r#"{"o":{"x":1,"y":2}}"#, r#"{"o":{"y":null,"z":"é"}}"#),
+        (r#"{}"#, r#"{"n":{"a":null,"b":1}}"#),
+        (r#"[1,2]"#, r#"{"a":1}"#),
+        (r#"{"a":1}"#, r#"[1,2]"#),
+    ];
+    for (base, patch) in cases {
+        let (base, patch, expected): (Vec<u8>, Vec<u8>, String) = conn
+            .query_row(
+                "select jsonb(?1), jsonb(?2), json_patch(?1, ?2)",
+                [base, patch],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )?;
+        let merged = serde_sqlite_jsonb::merge(&base, &patch).unwrap();
+        let merged_json: String =
+            conn.query_row("select json(?1)", [&merged], |row| row.get(0))?;
+        assert_eq!(merged_json, expected);
+    }
+
+    Ok(())
+}